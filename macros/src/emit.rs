@@ -0,0 +1,159 @@
+use std::fmt::Write;
+
+use crate::input::Enum;
+use crate::table::{Action, Grammar, Tables};
+use crate::Names;
+
+// action encoding in the generated tables: 0 is an error, shifts are positive, reduces
+// negative, both off by one
+const ACCEPT: i32 = i32::MAX;
+
+/// Source of the nonterminal and tree types and of `parse`, driven by `tables`.
+pub fn parser(def: &Enum, names: &Names, grammar: &Grammar, tables: &Tables) -> String {
+    let vis = &def.vis;
+    let token = &def.name;
+    let mut out = String::new();
+
+    writeln!(out, "#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]").unwrap();
+    writeln!(out, "{} enum {}NonTerminal {{ {} }}", vis, token, names.non_terminals.join(", ")).unwrap();
+
+    writeln!(out, "#[derive(Debug, Clone, Eq, PartialEq)]").unwrap();
+    writeln!(
+        out,
+        "{vis} enum {t}Tree<T = {t}> {{ Leaf(T), Empty, Node({t}NonTerminal, Vec<{t}Tree<T>>) }}",
+        vis = vis,
+        t = token
+    )
+    .unwrap();
+
+    writeln!(out, "/// The `position`th token, or the end of input, was not expected.").unwrap();
+    writeln!(out, "#[derive(Debug, Clone, Eq, PartialEq)]").unwrap();
+    writeln!(
+        out,
+        "{vis} struct {t}Error<T = {t}> {{ pub position: usize, pub found: Option<T>, pub expected: Vec<&'static str> }}",
+        vis = vis,
+        t = token
+    )
+    .unwrap();
+
+    let eof = grammar.eof();
+    let mut terminals: Vec<String> = names.terminals.iter().map(|t| format!("{:?}", t)).collect();
+    terminals.push("\"end of input\"".to_string());
+    let kinds: Vec<String> = def
+        .variants
+        .iter()
+        .map(|v| {
+            let t = names.terminals.iter().position(|&t| Some(t) == v.token.as_deref()).unwrap();
+            format!("{}::{} {{ .. }} => {}", token, v.name, t)
+        })
+        .collect();
+    let action: Vec<String> = tables
+        .action
+        .iter()
+        .map(|row| {
+            let row: Vec<_> = row.iter().map(|a| encode(*a).to_string()).collect();
+            format!("[{}]", row.join(", "))
+        })
+        .collect();
+    let goto: Vec<String> = tables
+        .goto
+        .iter()
+        .map(|row| {
+            let row: Vec<_> = row.iter().map(|g| g.map_or(-1, |s| s as i32).to_string()).collect();
+            format!("[{}]", row.join(", "))
+        })
+        .collect();
+    let productions: Vec<String> = grammar
+        .productions
+        .iter()
+        .map(|(left, right)| format!("({}, {})", left, right.len()))
+        .collect();
+    let non_terminals: Vec<String> = names
+        .non_terminals
+        .iter()
+        .map(|nt| format!("{}NonTerminal::{}", token, nt))
+        .collect();
+
+    write!(
+        out,
+        r#"
+impl {t} {{
+    /// Parses `tokens` into a tree rooted at `{start}`.
+    {vis} fn parse<I: IntoIterator<Item = {t}>>(tokens: I) -> Result<{t}Tree, {t}Error> {{
+        const EOF: usize = {eof};
+        const ACCEPT: i32 = {accept};
+        const TERMINALS: [&str; {nt}] = [{terminals}];
+        const NON_TERMINALS: [{t}NonTerminal; {nn}] = [{non_terminals}];
+        const ACTION: [[i32; {nt}]; {ns}] = [{action}];
+        const GOTO: [[i32; {nn}]; {ns}] = [{goto}];
+        const PRODUCTIONS: [(usize, usize); {np}] = [{productions}];
+
+        fn kind(token: &{t}) -> usize {{
+            match token {{ {kinds} }}
+        }}
+
+        let mut tokens = tokens.into_iter();
+        let mut current = tokens.next();
+        let mut position = 0;
+        let mut states = vec![0usize];
+        let mut trees: Vec<{t}Tree> = vec![];
+        loop {{
+            let state = *states.last().unwrap();
+            let t = current.as_ref().map_or(EOF, kind);
+            match ACTION[state][t] {{
+                0 => {{
+                    let expected = (0..=EOF).filter(|&t| ACTION[state][t] != 0).map(|t| TERMINALS[t]).collect();
+                    return Err({t}Error {{ position, found: current, expected }});
+                }}
+                ACCEPT => return Ok(trees.pop().unwrap()),
+                a if a > 0 => {{
+                    states.push(a as usize - 1);
+                    trees.push({t}Tree::Leaf(current.take().unwrap()));
+                    current = tokens.next();
+                    position += 1;
+                }}
+                a => {{
+                    let (left, len) = PRODUCTIONS[(-a - 1) as usize];
+                    let children = if len == 0 {{
+                        vec![{t}Tree::Empty]
+                    }} else {{
+                        trees.split_off(trees.len() - len)
+                    }};
+                    states.truncate(states.len() - len);
+                    trees.push({t}Tree::Node(NON_TERMINALS[left], children));
+                    let state = *states.last().unwrap();
+                    states.push(GOTO[state][left] as usize);
+                }}
+            }}
+        }}
+    }}
+}}
+"#,
+        t = token,
+        vis = vis,
+        start = names.non_terminals[0],
+        eof = eof,
+        accept = ACCEPT,
+        nt = eof + 1,
+        nn = names.non_terminals.len(),
+        ns = tables.action.len(),
+        np = productions.len(),
+        terminals = terminals.join(", "),
+        non_terminals = non_terminals.join(", "),
+        action = action.join(", "),
+        goto = goto.join(", "),
+        productions = productions.join(", "),
+        kinds = kinds.join(", "),
+    )
+    .unwrap();
+    out
+}
+
+fn encode(action: Action) -> i32 {
+    match action {
+        Action::Error => 0,
+        Action::Shift(s) => s as i32 + 1,
+        Action::Reduce(p) => -(p as i32) - 1,
+        Action::Accept => ACCEPT,
+    }
+}
@@ -0,0 +1,224 @@
+use std::iter::FromIterator;
+
+use proc_macro::{Delimiter, Group, Ident, Literal, Punct, Spacing, Span, TokenStream, TokenTree};
+
+/// A message for the user, reported as a `compile_error!` at `span`.
+pub struct Error {
+    pub span: Span,
+    pub message: String,
+}
+
+impl Error {
+    pub fn new<S: Into<String>>(span: Span, message: S) -> Self {
+        Error {
+            span,
+            message: message.into(),
+        }
+    }
+
+    pub fn to_compile_error(&self) -> TokenStream {
+        let mut message = Literal::string(&self.message);
+        message.set_span(self.span);
+        let mut bang = Punct::new('!', Spacing::Alone);
+        bang.set_span(self.span);
+        let mut args = Group::new(Delimiter::Parenthesis, TokenStream::from(TokenTree::Literal(message)));
+        args.set_span(self.span);
+        let mut semi = Punct::new(';', Spacing::Alone);
+        semi.set_span(self.span);
+        TokenStream::from_iter(vec![
+            TokenTree::Ident(Ident::new("compile_error", self.span)),
+            TokenTree::Punct(bang),
+            TokenTree::Group(args),
+            TokenTree::Punct(semi),
+        ])
+    }
+}
+
+/// The annotated enum, with the `#[token(..)]` attributes taken off its variants.
+pub struct Enum {
+    pub vis: String,
+    pub name: String,
+    pub variants: Vec<Variant>,
+    pub stripped: TokenStream,
+}
+
+pub struct Variant {
+    pub name: String,
+    pub span: Span,
+    /// The literal of its `#[token(..)]` as written, quotes included.
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Symbol {
+    T(String),
+    NT(String),
+}
+
+pub struct Rule {
+    pub left: String,
+    pub alternatives: Vec<Alternative>,
+}
+
+pub struct Alternative {
+    /// The first token of the alternative, conflicts point here.
+    pub span: Span,
+    pub symbols: Vec<(Symbol, Span)>,
+}
+
+pub fn parse_enum(item: TokenStream) -> Result<Enum, Error> {
+    let mut tokens: Vec<TokenTree> = item.into_iter().collect();
+    let pos = tokens
+        .iter()
+        .position(|t| matches!(t, TokenTree::Ident(i) if i.to_string() == "enum"))
+        .ok_or_else(|| Error::new(Span::call_site(), "expected an enum"))?;
+    let name = match tokens.get(pos + 1) {
+        Some(TokenTree::Ident(i)) => i.to_string(),
+        _ => return Err(Error::new(tokens[pos].span(), "expected the name of the enum")),
+    };
+    let body = match tokens.get(pos + 2) {
+        Some(TokenTree::Group(g)) if g.delimiter() == Delimiter::Brace => g.clone(),
+        Some(t) => return Err(Error::new(t.span(), "generic enums are not supported")),
+        None => return Err(Error::new(tokens[pos].span(), "expected the variants of the enum")),
+    };
+
+    // what is left of the outer attributes is the visibility
+    let mut vis = vec![];
+    let mut i = 0;
+    while i < pos {
+        match &tokens[i] {
+            TokenTree::Punct(p) if p.as_char() == '#' => i += 2,
+            t => {
+                vis.push(t.clone());
+                i += 1;
+            }
+        }
+    }
+
+    let mut variants = vec![];
+    let mut kept = vec![];
+    let body_tokens: Vec<TokenTree> = body.stream().into_iter().collect();
+    for chunk in body_tokens.split(|t| matches!(t, TokenTree::Punct(p) if p.as_char() == ',')) {
+        let mut token = None;
+        let mut i = 0;
+        while let Some(TokenTree::Punct(p)) = chunk.get(i) {
+            if p.as_char() != '#' {
+                break;
+            }
+            match chunk.get(i + 1) {
+                Some(TokenTree::Group(attr)) if is_token_attr(attr) => token = Some(token_literal(attr)?),
+                Some(attr) => kept.extend(vec![chunk[i].clone(), attr.clone()]),
+                None => break,
+            }
+            i += 2;
+        }
+        match chunk.get(i) {
+            Some(TokenTree::Ident(ident)) => variants.push(Variant {
+                name: ident.to_string(),
+                span: ident.span(),
+                token,
+            }),
+            Some(t) => return Err(Error::new(t.span(), "expected a variant")),
+            None => continue,
+        }
+        kept.extend(chunk[i..].iter().cloned());
+        kept.push(TokenTree::Punct(Punct::new(',', Spacing::Alone)));
+    }
+    let mut stripped = Group::new(Delimiter::Brace, TokenStream::from_iter(kept));
+    stripped.set_span(body.span());
+    tokens[pos + 2] = TokenTree::Group(stripped);
+
+    Ok(Enum {
+        vis: TokenStream::from_iter(vis).to_string(),
+        name,
+        variants,
+        stripped: TokenStream::from_iter(tokens),
+    })
+}
+
+fn is_token_attr(attr: &Group) -> bool {
+    matches!(attr.stream().into_iter().next(), Some(TokenTree::Ident(i)) if i.to_string() == "token")
+}
+
+fn token_literal(attr: &Group) -> Result<String, Error> {
+    let args = attr.stream().into_iter().nth(1);
+    if let Some(TokenTree::Group(args)) = &args {
+        let inner: Vec<_> = args.stream().into_iter().collect();
+        if let [TokenTree::Literal(lit)] = &inner[..] {
+            let text = lit.to_string();
+            if text.starts_with('"') {
+                return Ok(text);
+            }
+        }
+    }
+    Err(Error::new(attr.span(), "expected `token(\"...\")`"))
+}
+
+/// `Left -> a "b" | () ; ...`, the same syntax as `grammar!`.
+pub fn parse_grammar(attr: TokenStream) -> Result<Vec<Rule>, Error> {
+    let tokens: Vec<TokenTree> = attr.into_iter().collect();
+    let mut rules = vec![];
+    let mut i = 0;
+    while i < tokens.len() {
+        let left = match &tokens[i] {
+            TokenTree::Ident(left) => left,
+            t => return Err(Error::new(t.span(), "expected a nonterminal")),
+        };
+        match (tokens.get(i + 1), tokens.get(i + 2)) {
+            (Some(TokenTree::Punct(a)), Some(TokenTree::Punct(b))) if a.as_char() == '-' && b.as_char() == '>' => {}
+            _ => return Err(Error::new(left.span(), "expected `->` after the nonterminal")),
+        }
+        i += 3;
+
+        let mut alternatives = vec![];
+        let mut current = Alternative {
+            span: tokens.get(i).map_or(left.span(), |t| t.span()),
+            symbols: vec![],
+        };
+        let mut empty = false;
+        loop {
+            let end = match tokens.get(i) {
+                None => true,
+                Some(TokenTree::Punct(p)) if p.as_char() == ';' => true,
+                Some(TokenTree::Punct(p)) if p.as_char() == '|' => false,
+                Some(TokenTree::Literal(lit)) if lit.to_string().starts_with('"') => {
+                    current.symbols.push((Symbol::T(lit.to_string()), lit.span()));
+                    i += 1;
+                    continue;
+                }
+                Some(TokenTree::Ident(nt)) => {
+                    current.symbols.push((Symbol::NT(nt.to_string()), nt.span()));
+                    i += 1;
+                    continue;
+                }
+                Some(TokenTree::Group(g)) if g.delimiter() == Delimiter::Parenthesis && g.stream().is_empty() => {
+                    empty = true;
+                    i += 1;
+                    continue;
+                }
+                Some(t) => return Err(Error::new(t.span(), "expected a string literal, a nonterminal or `()`")),
+            };
+            if current.symbols.is_empty() && !empty {
+                return Err(Error::new(current.span, "empty alternative, write `()` for ε"));
+            }
+            if empty && !current.symbols.is_empty() {
+                return Err(Error::new(current.span, "`()` must be an alternative on its own"));
+            }
+            i += 1;
+            let next = tokens.get(i).map_or(current.span, |t| t.span());
+            alternatives.push(std::mem::replace(&mut current, Alternative { span: next, symbols: vec![] }));
+            empty = false;
+            if end {
+                break;
+            }
+        }
+        rules.push(Rule {
+            left: left.to_string(),
+            alternatives,
+        });
+    }
+    if rules.is_empty() {
+        return Err(Error::new(Span::call_site(), "expected a grammar, e.g. `#[lr1(S -> \"a\" S | ())]`"));
+    }
+    Ok(rules)
+}
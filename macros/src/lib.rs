@@ -0,0 +1,223 @@
+//! Parsers generated at compile time. The grammar goes on an enum of tokens, each variant
+//! naming the terminal it stands for:
+//!
+//! ```ignore
+//! #[lr1(
+//!     Expr -> Expr "+" Term | Term;
+//!     Term -> "num" | "(" Expr ")";
+//! )]
+//! enum Token {
+//!     #[token("+")] Plus,
+//!     #[token("num")] Num(u64),
+//!     #[token("(")] LParen,
+//!     #[token(")")] RParen,
+//! }
+//! ```
+//!
+//! Besides the enum this emits `TokenNonTerminal`, `TokenTree`, `TokenError` and
+//! `Token::parse`. Conflicts in the grammar are compile errors on the productions involved.
+//! `1 + 2 + 3` can be grouped either way, a shift/reduce conflict:
+//!
+//! ```compile_fail
+//! use eac2_macros::lr1;
+//!
+//! #[lr1(Expr -> Expr "+" Expr | "num")]
+//! enum Token {
+//!     #[token("+")] Plus,
+//!     #[token("num")] Num,
+//! }
+//! ```
+//!
+//! and `x` is both an `A` and a `B`, a reduce/reduce conflict:
+//!
+//! ```compile_fail
+//! use eac2_macros::lr1;
+//!
+//! #[lr1(
+//!     S -> A | B;
+//!     A -> "x";
+//!     B -> "x";
+//! )]
+//! enum Token {
+//!     #[token("x")] X,
+//! }
+//! ```
+//!
+//! and with `S -> S` a complete `S` could be the whole input or reduced to another `S`:
+//!
+//! ```compile_fail
+//! use eac2_macros::lr1;
+//!
+//! #[lr1(S -> S | "x")]
+//! enum Token {
+//!     #[token("x")] X,
+//! }
+//! ```
+
+mod emit;
+mod input;
+mod table;
+#[cfg(test)]
+mod test;
+
+use std::iter::FromIterator;
+
+use proc_macro::{Span, TokenStream};
+
+use self::input::{Enum, Error, Rule, Symbol};
+use self::table::{Conflict, Grammar, Kind};
+
+/// A canonical LR(1) parser.
+#[proc_macro_attribute]
+pub fn lr1(attr: TokenStream, item: TokenStream) -> TokenStream {
+    expand(attr, item, Kind::Lr1)
+}
+
+/// An LALR(1) parser, with smaller tables than `lr1` but fewer grammars it accepts.
+#[proc_macro_attribute]
+pub fn lalr1(attr: TokenStream, item: TokenStream) -> TokenStream {
+    expand(attr, item, Kind::Lalr1)
+}
+
+fn expand(attr: TokenStream, item: TokenStream, kind: Kind) -> TokenStream {
+    let def = match input::parse_enum(item.clone()) {
+        Ok(def) => def,
+        Err(e) => return TokenStream::from_iter(vec![item, e.to_compile_error()]),
+    };
+    let mut out = def.stripped.clone();
+    match generate(&def, attr, kind) {
+        Ok(code) => out.extend(code),
+        Err(errors) => out.extend(errors.iter().map(|e| e.to_compile_error())),
+    }
+    out
+}
+
+fn generate(def: &Enum, attr: TokenStream, kind: Kind) -> Result<TokenStream, Vec<Error>> {
+    let rules = input::parse_grammar(attr).map_err(|e| vec![e])?;
+    let mut errors = vec![];
+
+    let mut terminals: Vec<&str> = vec![];
+    for v in &def.variants {
+        match &v.token {
+            Some(t) if terminals.contains(&&t[..]) => {
+                errors.push(Error::new(v.span, format!("{} is already the token of another variant", t)))
+            }
+            Some(t) => terminals.push(t),
+            None => errors.push(Error::new(v.span, "variant without a `#[token(\"...\")]` attribute")),
+        }
+    }
+    let mut non_terminals: Vec<&str> = vec![];
+    for rule in &rules {
+        if !non_terminals.contains(&&rule.left[..]) {
+            non_terminals.push(&rule.left);
+        }
+    }
+
+    let mut productions = vec![];
+    let mut spans = vec![];
+    for rule in &rules {
+        let left = non_terminals.iter().position(|&nt| nt == rule.left).unwrap();
+        for alt in &rule.alternatives {
+            let mut right = vec![];
+            for (sym, span) in &alt.symbols {
+                match sym {
+                    Symbol::T(t) => match terminals.iter().position(|x| x == t) {
+                        Some(i) => right.push(table::Symbol::T(i)),
+                        None => errors.push(Error::new(*span, format!("no variant has the token {}", t))),
+                    },
+                    Symbol::NT(nt) => match non_terminals.iter().position(|x| x == nt) {
+                        Some(i) => right.push(table::Symbol::NT(i)),
+                        None => errors.push(Error::new(*span, format!("`{}` has no productions", nt))),
+                    },
+                }
+            }
+            productions.push((left, right));
+            spans.push(alt.span);
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let grammar = Grammar::new(terminals.len(), non_terminals.len(), 0, productions);
+    let names = Names {
+        terminals: &terminals,
+        non_terminals: &non_terminals,
+        rules: &rules,
+    };
+    let tables = table::build(&grammar, kind).map_err(|conflicts| {
+        conflicts
+            .iter()
+            .map(|c| conflict_error(c, &names, &spans, kind))
+            .collect::<Vec<_>>()
+    })?;
+    let code = emit::parser(def, &names, &grammar, &tables);
+    Ok(code.parse().expect("generated code is valid Rust"))
+}
+
+pub(crate) struct Names<'a> {
+    pub terminals: &'a [&'a str],
+    pub non_terminals: &'a [&'a str],
+    pub rules: &'a [Rule],
+}
+
+impl Names<'_> {
+    fn terminal(&self, t: usize) -> &str {
+        self.terminals.get(t).cloned().unwrap_or("end of input")
+    }
+
+    // production `p` as `A -> b "c"`, counting across rules
+    fn production(&self, p: usize) -> String {
+        let mut alternatives = self.rules.iter().flat_map(|r| r.alternatives.iter().map(move |a| (r, a)));
+        let (rule, alt) = alternatives.nth(p).unwrap();
+        let right: Vec<_> = alt
+            .symbols
+            .iter()
+            .map(|(sym, _)| match sym {
+                Symbol::T(t) | Symbol::NT(t) => &t[..],
+            })
+            .collect();
+        if right.is_empty() {
+            format!("{} -> ()", rule.left)
+        } else {
+            format!("{} -> {}", rule.left, right.join(" "))
+        }
+    }
+}
+
+fn conflict_error(conflict: &Conflict, names: &Names, spans: &[Span], kind: Kind) -> Error {
+    let algorithm = match kind {
+        Kind::Lr1 => "LR(1)",
+        Kind::Lalr1 => "LALR(1)",
+    };
+    match *conflict {
+        Conflict::ShiftReduce { terminal, reduce } => Error::new(
+            spans[reduce],
+            format!(
+                "the grammar is not {}: shift/reduce conflict on {}, `{}` could be reduced or {} shifted",
+                algorithm,
+                names.terminal(terminal),
+                names.production(reduce),
+                names.terminal(terminal),
+            ),
+        ),
+        Conflict::ReduceReduce { terminal, first, second } => Error::new(
+            spans[second],
+            format!(
+                "the grammar is not {}: reduce/reduce conflict on {} between `{}` and `{}`",
+                algorithm,
+                names.terminal(terminal),
+                names.production(first),
+                names.production(second),
+            ),
+        ),
+        Conflict::AcceptReduce { reduce } => Error::new(
+            spans[reduce],
+            format!(
+                "the grammar is not {}: conflict on end of input, the input could be accepted or `{}` reduced",
+                algorithm,
+                names.production(reduce),
+            ),
+        ),
+    }
+}
@@ -0,0 +1,268 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+pub type State = usize;
+// production number, dot position and lookahead
+type Item = (usize, usize, usize);
+type ItemSet = BTreeSet<Item>;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Symbol {
+    T(usize),
+    NT(usize),
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Kind {
+    Lr1,
+    Lalr1,
+}
+
+/// Terminals and nonterminals are numbered from 0. The end of input is terminal `terminals`,
+/// production 0 is the added `S' -> S` over nonterminal `non_terminals`.
+#[derive(Debug, Clone)]
+pub struct Grammar {
+    pub terminals: usize,
+    pub non_terminals: usize,
+    pub productions: Vec<(usize, Vec<Symbol>)>,
+}
+
+impl Grammar {
+    pub fn new(terminals: usize, non_terminals: usize, start: usize, productions: Vec<(usize, Vec<Symbol>)>) -> Self {
+        let mut all = vec![(non_terminals, vec![Symbol::NT(start)])];
+        all.extend(productions);
+        Grammar {
+            terminals,
+            non_terminals,
+            productions: all,
+        }
+    }
+
+    pub fn eof(&self) -> usize {
+        self.terminals
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Action {
+    Error,
+    Shift(State),
+    Reduce(usize),
+    Accept,
+}
+
+/// Two actions for one terminal in some state. Productions are numbered as given to
+/// `Grammar::new`, i.e. without the added one.
+// named after the two actions, which all happen to include a reduction
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
+pub enum Conflict {
+    ShiftReduce { terminal: usize, reduce: usize },
+    ReduceReduce { terminal: usize, first: usize, second: usize },
+    /// At the end of input, where the start symbol is complete but `reduce` could be reduced too.
+    AcceptReduce { reduce: usize },
+}
+
+#[derive(Debug, Clone)]
+pub struct Tables {
+    pub action: Vec<Vec<Action>>,
+    pub goto: Vec<Vec<Option<State>>>,
+}
+
+/// The parse tables, or every distinct conflict found building them.
+pub fn build(grammar: &Grammar, kind: Kind) -> Result<Tables, Vec<Conflict>> {
+    let first = First::new(grammar);
+    let (mut cc, mut transfer) = build_cc(grammar, &first);
+    if kind == Kind::Lalr1 {
+        let merged = merge_cores(&cc, &transfer);
+        cc = merged.0;
+        transfer = merged.1;
+    }
+
+    let mut conflicts = BTreeSet::new();
+    let mut action = vec![vec![Action::Error; grammar.terminals + 1]; cc.len()];
+    let mut goto = vec![vec![None; grammar.non_terminals]; cc.len()];
+    for (state, items) in cc.iter().enumerate() {
+        for &(prod, dot, la) in items {
+            let right = &grammar.productions[prod].1;
+            let (t, new) = match right.get(dot) {
+                Some(&Symbol::T(t)) => (t, Action::Shift(transfer[state][&Symbol::T(t)])),
+                Some(&Symbol::NT(_)) => continue,
+                None if prod == 0 => (la, Action::Accept),
+                None => (la, Action::Reduce(prod)),
+            };
+            let old = action[state][t];
+            action[state][t] = match (old, new) {
+                (Action::Error, _) => new,
+                _ if old == new => old,
+                (Action::Shift(_), Action::Reduce(p)) | (Action::Reduce(p), Action::Shift(_)) => {
+                    conflicts.insert(Conflict::ShiftReduce {
+                        terminal: t,
+                        reduce: p - 1,
+                    });
+                    old
+                }
+                (Action::Reduce(p), Action::Reduce(q)) => {
+                    conflicts.insert(Conflict::ReduceReduce {
+                        terminal: t,
+                        first: p.min(q) - 1,
+                        second: p.max(q) - 1,
+                    });
+                    old
+                }
+                (Action::Accept, Action::Reduce(p)) | (Action::Reduce(p), Action::Accept) => {
+                    conflicts.insert(Conflict::AcceptReduce { reduce: p - 1 });
+                    old
+                }
+                // accept only happens on the end of input, which is never shifted
+                _ => unreachable!(),
+            };
+        }
+        for (&sym, &to) in &transfer[state] {
+            if let Symbol::NT(nt) = sym {
+                goto[state][nt] = Some(to);
+            }
+        }
+    }
+
+    if conflicts.is_empty() {
+        Ok(Tables { action, goto })
+    } else {
+        Err(conflicts.into_iter().collect())
+    }
+}
+
+struct First {
+    nullable: Vec<bool>,
+    first: Vec<BTreeSet<usize>>,
+}
+
+impl First {
+    fn new(grammar: &Grammar) -> Self {
+        let n = grammar.non_terminals + 1;
+        let mut ret = First {
+            nullable: vec![false; n],
+            first: vec![BTreeSet::new(); n],
+        };
+        let mut updated = true;
+        while updated {
+            updated = false;
+            for (left, right) in &grammar.productions {
+                let (first, nullable) = ret.of(right);
+                if nullable && !ret.nullable[*left] {
+                    ret.nullable[*left] = true;
+                    updated = true;
+                }
+                let known = ret.first[*left].len();
+                ret.first[*left].extend(first);
+                updated |= ret.first[*left].len() != known;
+            }
+        }
+        ret
+    }
+
+    // FIRST of a sequence, and whether all of it can be empty
+    fn of(&self, seq: &[Symbol]) -> (BTreeSet<usize>, bool) {
+        let mut ret = BTreeSet::new();
+        for sym in seq {
+            match *sym {
+                Symbol::T(t) => {
+                    ret.insert(t);
+                    return (ret, false);
+                }
+                Symbol::NT(nt) => {
+                    ret.extend(&self.first[nt]);
+                    if !self.nullable[nt] {
+                        return (ret, false);
+                    }
+                }
+            }
+        }
+        (ret, true)
+    }
+}
+
+fn closure(grammar: &Grammar, first: &First, mut items: ItemSet) -> ItemSet {
+    let mut work: Vec<Item> = items.iter().cloned().collect();
+    while let Some((prod, dot, la)) = work.pop() {
+        let right = &grammar.productions[prod].1;
+        let nt = match right.get(dot) {
+            Some(&Symbol::NT(nt)) => nt,
+            _ => continue,
+        };
+        let (mut lookaheads, nullable) = first.of(&right[dot + 1..]);
+        if nullable {
+            lookaheads.insert(la);
+        }
+        for (p, (left, _)) in grammar.productions.iter().enumerate() {
+            if *left != nt {
+                continue;
+            }
+            for &la in &lookaheads {
+                if items.insert((p, 0, la)) {
+                    work.push((p, 0, la));
+                }
+            }
+        }
+    }
+    items
+}
+
+fn goto(grammar: &Grammar, first: &First, items: &ItemSet, sym: Symbol) -> ItemSet {
+    let moved = items
+        .iter()
+        .filter(|&&(prod, dot, _)| grammar.productions[prod].1.get(dot) == Some(&sym))
+        .map(|&(prod, dot, la)| (prod, dot + 1, la))
+        .collect();
+    closure(grammar, first, moved)
+}
+
+fn build_cc(grammar: &Grammar, first: &First) -> (Vec<ItemSet>, Vec<BTreeMap<Symbol, State>>) {
+    let start = closure(grammar, first, Some((0, 0, grammar.eof())).into_iter().collect());
+    let mut index = HashMap::new();
+    index.insert(start.clone(), 0);
+    let mut cc = vec![start];
+    let mut transfer = vec![];
+    let mut next = 0;
+    while next < cc.len() {
+        let symbols: BTreeSet<Symbol> = cc[next]
+            .iter()
+            .filter_map(|&(prod, dot, _)| grammar.productions[prod].1.get(dot).cloned())
+            .collect();
+        let mut edges = BTreeMap::new();
+        for sym in symbols {
+            let to = goto(grammar, first, &cc[next], sym);
+            let len = cc.len();
+            let state = *index.entry(to.clone()).or_insert(len);
+            if state == len {
+                cc.push(to);
+            }
+            edges.insert(sym, state);
+        }
+        transfer.push(edges);
+        next += 1;
+    }
+    (cc, transfer)
+}
+
+// LALR(1): states with the same items apart from the lookaheads become one
+fn merge_cores(
+    cc: &[ItemSet],
+    transfer: &[BTreeMap<Symbol, State>],
+) -> (Vec<ItemSet>, Vec<BTreeMap<Symbol, State>>) {
+    let mut cores = HashMap::new();
+    let mut renumber = vec![];
+    for items in cc {
+        let core: BTreeSet<_> = items.iter().map(|&(prod, dot, _)| (prod, dot)).collect();
+        let len = cores.len();
+        renumber.push(*cores.entry(core).or_insert(len));
+    }
+    let mut merged = vec![ItemSet::new(); cores.len()];
+    let mut edges = vec![BTreeMap::new(); cores.len()];
+    for (state, items) in cc.iter().enumerate() {
+        merged[renumber[state]].extend(items);
+        for (&sym, &to) in &transfer[state] {
+            edges[renumber[state]].insert(sym, renumber[to]);
+        }
+    }
+    (merged, edges)
+}
@@ -0,0 +1,91 @@
+use crate::table::{build, Action, Conflict, Grammar, Kind, Symbol};
+
+fn t(i: usize) -> Symbol {
+    Symbol::T(i)
+}
+
+fn nt(i: usize) -> Symbol {
+    Symbol::NT(i)
+}
+
+// E -> E + T | T; T -> num | ( E ), terminals + num ( )
+fn expr() -> Grammar {
+    Grammar::new(4, 2, 0, vec![
+        (0, vec![nt(0), t(0), nt(1)]),
+        (0, vec![nt(1)]),
+        (1, vec![t(1)]),
+        (1, vec![t(2), nt(0), t(3)]),
+    ])
+}
+
+// run the tables on a sentence of terminal numbers
+fn accepts(grammar: &Grammar, kind: Kind, input: &[usize]) -> bool {
+    let tables = build(grammar, kind).unwrap();
+    let mut states = vec![0];
+    let mut input = input.iter().cloned().chain(Some(grammar.eof())).peekable();
+    loop {
+        let state = *states.last().unwrap();
+        match tables.action[state][*input.peek().unwrap()] {
+            Action::Error => return false,
+            Action::Accept => return true,
+            Action::Shift(s) => {
+                states.push(s);
+                input.next();
+            }
+            Action::Reduce(p) => {
+                let (left, ref right) = grammar.productions[p];
+                states.truncate(states.len() - right.len());
+                states.push(tables.goto[*states.last().unwrap()][left].unwrap());
+            }
+        }
+    }
+}
+
+#[test]
+fn test_tables() {
+    let grammar = expr();
+    for &kind in &[Kind::Lr1, Kind::Lalr1] {
+        assert!(accepts(&grammar, kind, &[1]));
+        assert!(accepts(&grammar, kind, &[2, 1, 0, 1, 3, 0, 1]));
+        assert!(!accepts(&grammar, kind, &[1, 0]));
+        assert!(!accepts(&grammar, kind, &[2, 1]));
+        assert!(!accepts(&grammar, kind, &[]));
+    }
+    // the parenthesized copies of the states have other lookaheads only
+    let lr1 = build(&grammar, Kind::Lr1).unwrap();
+    let lalr1 = build(&grammar, Kind::Lalr1).unwrap();
+    assert!(lalr1.action.len() < lr1.action.len());
+
+    // S -> a S | ()
+    let grammar = Grammar::new(1, 1, 0, vec![(0, vec![t(0), nt(0)]), (0, vec![])]);
+    assert!(accepts(&grammar, Kind::Lalr1, &[]));
+    assert!(accepts(&grammar, Kind::Lalr1, &[0, 0, 0]));
+}
+
+#[test]
+fn test_conflicts() {
+    // E -> E + E | num
+    let grammar = Grammar::new(2, 1, 0, vec![(0, vec![nt(0), t(0), nt(0)]), (0, vec![t(1)])]);
+    let conflict = Conflict::ShiftReduce { terminal: 0, reduce: 0 };
+    assert_eq!(build(&grammar, Kind::Lr1).err().unwrap(), vec![conflict]);
+
+    // S -> a A d | b B d | a B e | b A e; A -> c; B -> c is LR(1) but not LALR(1)
+    let grammar = Grammar::new(5, 3, 0, vec![
+        (0, vec![t(0), nt(1), t(3)]),
+        (0, vec![t(1), nt(2), t(3)]),
+        (0, vec![t(0), nt(2), t(4)]),
+        (0, vec![t(1), nt(1), t(4)]),
+        (1, vec![t(2)]),
+        (2, vec![t(2)]),
+    ]);
+    assert!(build(&grammar, Kind::Lr1).is_ok());
+    assert_eq!(build(&grammar, Kind::Lalr1).err().unwrap(), vec![
+        Conflict::ReduceReduce { terminal: 3, first: 4, second: 5 },
+        Conflict::ReduceReduce { terminal: 4, first: 4, second: 5 },
+    ]);
+    // S -> S | x: with `S` on the stack at the end of input, accept or reduce `S -> S`
+    let grammar = Grammar::new(1, 1, 0, vec![(0, vec![nt(0)]), (0, vec![t(0)])]);
+    for &kind in &[Kind::Lr1, Kind::Lalr1] {
+        assert_eq!(build(&grammar, kind).err().unwrap(), vec![Conflict::AcceptReduce { reduce: 0 }]);
+    }
+}
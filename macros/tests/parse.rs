@@ -0,0 +1,75 @@
+use eac2_macros::{lalr1, lr1};
+
+#[lr1(
+    Expr -> Expr "+" Term | Term;
+    Term -> Term "*" Factor | Factor;
+    Factor -> "num" | "(" Expr ")";
+)]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Token {
+    #[token("+")]
+    Plus,
+    #[token("*")]
+    Times,
+    #[token("num")]
+    Num(u64),
+    #[token("(")]
+    LParen,
+    #[token(")")]
+    RParen,
+}
+
+// a private enum, an ε alternative and a struct variant
+#[lalr1(List -> "item" List | ())]
+#[derive(Debug, PartialEq, Eq)]
+enum Item {
+    #[token("item")]
+    Item { name: &'static str },
+}
+
+fn eval(tree: &TokenTree) -> u64 {
+    match tree {
+        TokenTree::Leaf(Token::Num(n)) => *n,
+        TokenTree::Node(_, children) if children.len() == 3 => match &children[1] {
+            TokenTree::Leaf(Token::Plus) => eval(&children[0]) + eval(&children[2]),
+            TokenTree::Leaf(Token::Times) => eval(&children[0]) * eval(&children[2]),
+            _ => eval(&children[1]),
+        },
+        TokenTree::Node(_, children) => eval(&children[0]),
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn test_lr1() {
+    use Token::*;
+
+    let tree = Token::parse(vec![Num(2), Plus, Num(3), Times, LParen, Num(4), Plus, Num(1), RParen]).unwrap();
+    assert_eq!(eval(&tree), 17);
+    match &tree {
+        TokenTree::Node(TokenNonTerminal::Expr, children) => assert_eq!(children.len(), 3),
+        _ => panic!("{:?}", tree),
+    }
+
+    let err = Token::parse(vec![Num(2), Plus, RParen]).unwrap_err();
+    assert_eq!(err.position, 2);
+    assert_eq!(err.found, Some(RParen));
+    assert_eq!(err.expected, vec!["\"num\"", "\"(\""]);
+
+    let err = Token::parse(vec![LParen, Num(1)]).unwrap_err();
+    assert_eq!((err.position, err.found), (2, None));
+    assert_eq!(err.expected, vec!["\"+\"", "\"*\"", "\")\""]);
+}
+
+#[test]
+fn test_lalr1() {
+    assert_eq!(Item::parse(vec![]), Ok(ItemTree::Node(ItemNonTerminal::List, vec![ItemTree::Empty])));
+    let tree = Item::parse(vec![Item::Item { name: "a" }]).unwrap();
+    assert_eq!(
+        tree,
+        ItemTree::Node(ItemNonTerminal::List, vec![
+            ItemTree::Leaf(Item::Item { name: "a" }),
+            ItemTree::Node(ItemNonTerminal::List, vec![ItemTree::Empty]),
+        ])
+    );
+}
@@ -0,0 +1,192 @@
+//! Runs every parser that applies to a grammar on the same inputs and reports the inputs they
+//! disagree on.
+
+use std::fmt;
+
+use crate::parser::backtrack_parse::backtrack_parse;
+use crate::parser::generate::{covering, sentences, Generator};
+use crate::parser::render::{to_sexpr, RenderOptions};
+use crate::parser::{ParseTree, Terminal as GenericTerminal};
+
+use super::*;
+use super::lr1::{build_action_and_goto_table, is_lr1, parse_tree_with_tables};
+
+// sentences up to this long are all checked
+const EXHAUSTIVE_LEN: usize = 4;
+// height of the random derivations
+const RANDOM_DEPTH: usize = 6;
+
+/// What a parser made of an input. Parsers that don't build trees accept with `None`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Outcome {
+    Accept(Option<ParseTree>),
+    Reject,
+}
+
+type Parser<'a> = Box<dyn Fn(&[GenericTerminal]) -> Outcome + 'a>;
+
+pub struct Harness<'a> {
+    cfg: crate::parser::CFG,
+    terminals: Vec<GenericTerminal>,
+    parsers: Vec<(&'static str, Parser<'a>)>,
+}
+
+/// An input the parsers don't agree on, with what each of them made of it.
+#[derive(Debug)]
+pub struct Disagreement {
+    pub input: Vec<GenericTerminal>,
+    pub outcomes: Vec<(&'static str, Outcome)>,
+}
+
+impl<'a> Harness<'a> {
+    /// Sets up every parser that applies to `cfg`: backtracking always, LR(1) and LL(1) when
+    /// the grammar has no conflicts for them.
+    pub fn new(cfg: &'a CFG) -> Self {
+        let mut ret = Harness {
+            cfg: cfg.into(),
+            terminals: cfg.terminals.iter().map(|t| t.into()).collect(),
+            parsers: vec![],
+        };
+
+        let generic = ret.cfg.clone();
+        ret.add("backtrack", move |tokens| match backtrack_parse(&generic, tokens) {
+            Ok(tree) => Outcome::Accept(Some(tree)),
+            Err(_) => Outcome::Reject,
+        });
+        if is_lr1(cfg) {
+            let (action, goto) = build_action_and_goto_table(cfg);
+            ret.add("lr1", move |tokens| match parse_tree_with_tables(tokens, &action, &goto) {
+                Ok(tree) => Outcome::Accept(Some(tree)),
+                Err(_) => Outcome::Reject,
+            });
+        }
+        let cfg = NoneLeftRecursionCFG(cfg.clone());
+        if ll1::is_ll1(&cfg) {
+            ret.add("ll1", move |tokens| match ll1::parse_tree(tokens, &cfg) {
+                Ok(tree) => Outcome::Accept(Some(tree)),
+                Err(_) => Outcome::Reject,
+            });
+        }
+        ret
+    }
+
+    /// Adds a parser that only knows some grammars, e.g. a hand written one.
+    pub fn add<F>(&mut self, name: &'static str, parse: F)
+    where
+        F: Fn(&[GenericTerminal]) -> Outcome + 'a,
+    {
+        self.parsers.push((name, Box::new(parse)));
+    }
+
+    pub fn parsers(&self) -> Vec<&'static str> {
+        self.parsers.iter().map(|(name, _)| *name).collect()
+    }
+
+    /// All parsers must accept or all must reject, and those that build trees must build
+    /// the same one.
+    pub fn check(&self, input: &[GenericTerminal]) -> Result<(), Disagreement> {
+        let outcomes: Vec<_> = self.parsers.iter().map(|(name, parse)| (*name, parse(input))).collect();
+        let accepted: Vec<_> = outcomes.iter().map(|(_, o)| o != &Outcome::Reject).collect();
+        let trees: Vec<_> = outcomes
+            .iter()
+            .filter_map(|(_, o)| match o {
+                Outcome::Accept(Some(tree)) => Some(tree),
+                _ => None,
+            })
+            .collect();
+        if accepted.windows(2).all(|w| w[0] == w[1]) && trees.windows(2).all(|w| w[0] == w[1]) {
+            Ok(())
+        } else {
+            Err(Disagreement {
+                input: input.to_vec(),
+                outcomes,
+            })
+        }
+    }
+
+    /// Checks every sentence of up to four tokens, sentences covering every production,
+    /// `rounds` random sentences, and a small edit of each of them, which is usually not a
+    /// sentence any more. Returns how many inputs were checked, or the smallest input the
+    /// parsers disagree on.
+    pub fn run(&self, seed: u64, rounds: usize) -> Result<usize, Disagreement> {
+        let mut valid = sentences(&self.cfg, EXHAUSTIVE_LEN);
+        valid.extend(covering(&self.cfg));
+        let mut gen = Generator::new(&self.cfg, seed);
+        valid.extend((0..rounds).filter_map(|_| gen.random(RANDOM_DEPTH)));
+
+        let mut inputs = valid;
+        let mutants: Vec<_> = inputs.iter().enumerate().map(|(k, s)| self.mutate(s, k)).collect();
+        inputs.extend(mutants);
+
+        let mut smallest: Option<&Vec<GenericTerminal>> = None;
+        for input in &inputs {
+            if smallest.is_none_or(|s| input.len() < s.len()) && self.check(input).is_err() {
+                smallest = Some(input);
+            }
+        }
+        match smallest {
+            None => Ok(inputs.len()),
+            Some(input) => Err(self.shrink(input.clone())),
+        }
+    }
+
+    // one of a few small edits, picked by `k` so that runs are reproducible
+    fn mutate(&self, sentence: &[GenericTerminal], k: usize) -> Vec<GenericTerminal> {
+        let mut ret = sentence.to_vec();
+        if self.terminals.is_empty() {
+            ret.pop();
+            return ret;
+        }
+        let t = self.terminals[k % self.terminals.len()].clone();
+        let pos = k / 4 % (ret.len() + 1);
+        match k % 4 {
+            0 if pos < ret.len() => {
+                ret.remove(pos);
+            }
+            2 if pos < ret.len() => ret[pos] = t,
+            3 if pos + 1 < ret.len() => ret.swap(pos, pos + 1),
+            _ => ret.insert(pos, t),
+        }
+        ret
+    }
+
+    // drops single tokens, then pairs of tokens, for as long as the parsers still disagree
+    fn shrink(&self, mut input: Vec<GenericTerminal>) -> Disagreement {
+        'smaller: loop {
+            let n = input.len();
+            let singles = (0..n).map(|i| (i, i));
+            let pairs = (0..n).flat_map(|i| (i + 1..n).map(move |j| (i, j)));
+            for (i, j) in singles.chain(pairs) {
+                let mut candidate = input.clone();
+                candidate.remove(j);
+                if i != j {
+                    candidate.remove(i);
+                }
+                if self.check(&candidate).is_err() {
+                    input = candidate;
+                    continue 'smaller;
+                }
+            }
+            return self.check(&input).unwrap_err();
+        }
+    }
+}
+
+impl fmt::Display for Disagreement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "parsers disagree on `")?;
+        for (i, t) in self.input.iter().enumerate() {
+            write!(f, "{}{}", if i > 0 { " " } else { "" }, t)?;
+        }
+        write!(f, "`:")?;
+        for (name, outcome) in &self.outcomes {
+            let what = match outcome {
+                Outcome::Accept(Some(tree)) => format!("accepts {}", to_sexpr(tree, &RenderOptions::new())),
+                Outcome::Accept(None) => "accepts".to_string(),
+                Outcome::Reject => "rejects".to_string(),
+            };
+            write!(f, "\n  {} {}", name, what)?;
+        }
+        Ok(())
+    }
+}
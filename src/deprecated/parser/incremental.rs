@@ -0,0 +1,209 @@
+use std::collections::HashSet;
+use std::ops::Range;
+use std::rc::Rc;
+use std::slice;
+
+use crate::parser::token::Kind;
+use crate::parser::{ParseTree, Terminal as GenericTerminal};
+
+use super::*;
+use super::lr1::{build_action_and_goto_table, Action, ActionTable, GotoTable, State};
+
+pub struct Error;
+
+/// Child indices leading from the root of a `ParseTree` to one of its nodes.
+pub type NodePath = Vec<usize>;
+
+/// Replaces the tokens in `range` of the previous input with `insert`.
+#[derive(Clone, Debug)]
+pub struct Edit<T = GenericTerminal> {
+    pub range: Range<usize>,
+    pub insert: Vec<T>,
+}
+
+/// LR(1) parser that keeps enough of every parse around to reuse it after an edit.
+pub struct IncrementalParser {
+    action: ActionTable,
+    goto: GotoTable,
+}
+
+/// Result of a parse: the tokens and the tree, whose nodes remember the LR state they were
+/// pushed on.
+pub struct Parsed<T = GenericTerminal> {
+    tokens: Vec<T>,
+    root: Rc<Node>,
+}
+
+struct Node {
+    token: Token,
+    // state on top of the stack right below this node
+    state: State,
+    // number of tokens covered
+    len: usize,
+    children: Vec<Rc<Node>>,
+}
+
+impl IncrementalParser {
+    pub fn new(cfg: &CFG) -> Self {
+        let (action, goto) = build_action_and_goto_table(cfg);
+        IncrementalParser {
+            action,
+            goto,
+        }
+    }
+
+    pub fn parse<T: Kind + Clone>(&self, tokens: &[T]) -> std::result::Result<Parsed<T>, Error> {
+        let root = self.drive(tokens, &mut |_, _| None)?;
+        Ok(Parsed {
+            tokens: tokens.to_vec(),
+            root,
+        })
+    }
+
+    /// Parses the input of `old` with `edit` applied. Subtrees of `old` whose tokens and
+    /// lookahead lie outside the edit are reused whenever the parser reaches them in the
+    /// state they were originally built from. Also returns the paths of the nodes that had
+    /// to be built anew, parents before children. An edit whose range isn't one of the old
+    /// tokens is an error, as is an input the edit breaks.
+    pub fn reparse<T: Kind + Clone>(
+        &self,
+        old: &Parsed<T>,
+        edit: &Edit<T>,
+    ) -> std::result::Result<(Parsed<T>, Vec<NodePath>), Error> {
+        let Edit { range, insert } = edit;
+        if range.start > range.end || range.end > old.tokens.len() {
+            return Err(Error);
+        }
+        let mut tokens = old.tokens[..range.start].to_vec();
+        tokens.extend(insert.iter().cloned());
+        tokens.extend(old.tokens[range.end..].iter().cloned());
+
+        // candidates[i] are the old subtrees starting at old token i, outermost first
+        let mut candidates = vec![vec![]; old.tokens.len()];
+        collect_candidates(&old.root, 0, range, &mut candidates);
+
+        let inserted_end = range.start + insert.len();
+        let mut reused = HashSet::new();
+        let root = self.drive(&tokens, &mut |pos, state| {
+            let old_pos = if pos < range.start {
+                pos
+            } else if pos >= inserted_end {
+                pos - inserted_end + range.end
+            } else {
+                return None;
+            };
+            let node = candidates
+                .get(old_pos)?
+                .iter()
+                .find(|n| n.state == state)?
+                .clone();
+            reused.insert(&*node as *const Node);
+            Some(node)
+        })?;
+
+        let mut changed = vec![];
+        collect_changed(&root, &reused, &mut vec![], &mut changed);
+        Ok((Parsed { tokens, root }, changed))
+    }
+
+    // `reuse` is asked for a finished subtree starting at the given token whenever a token is
+    // about to be shifted from the given state.
+    fn drive<T: Kind>(
+        &self,
+        tokens: &[T],
+        reuse: &mut dyn FnMut(usize, State) -> Option<Rc<Node>>,
+    ) -> std::result::Result<Rc<Node>, Error> {
+        let mut states = vec![0];
+        let mut nodes: Vec<Rc<Node>> = vec![];
+        let mut pos = 0;
+        loop {
+            let s = *states.last().unwrap();
+            let token = tokens.get(pos).map_or(Terminal::Eof, |t| t.kind().into());
+            match self.action.get(&(s, token.clone())) {
+                None => return Err(Error),
+                // the start symbol is all that's left on the stack
+                Some(Action::Accept) => return Ok(nodes.pop().unwrap()),
+                Some(Action::Reduce(p)) => {
+                    let n = p.tokens.iter().filter(|t| !t.is_empty()).count();
+                    let children = nodes.split_off(nodes.len() - n);
+                    states.truncate(states.len() - n);
+                    let below = *states.last().unwrap();
+                    nodes.push(Rc::new(Node {
+                        token: Token::NT(p.non_terminal.clone()),
+                        state: below,
+                        len: children.iter().map(|c| c.len).sum(),
+                        children,
+                    }));
+                    states.push(*self.goto.get(&(below, p.non_terminal.clone())).ok_or(Error)?);
+                }
+                Some(Action::Shift(to)) => {
+                    if let Some(node) = reuse(pos, s) {
+                        let nt = match &node.token {
+                            Token::NT(nt) => nt.clone(),
+                            Token::T(_) => unreachable!(),
+                        };
+                        pos += node.len;
+                        nodes.push(node);
+                        states.push(*self.goto.get(&(s, nt)).ok_or(Error)?);
+                        continue;
+                    }
+                    nodes.push(Rc::new(Node {
+                        token: Token::T(token),
+                        state: s,
+                        len: 1,
+                        children: vec![],
+                    }));
+                    states.push(*to);
+                    pos += 1;
+                }
+            }
+        }
+    }
+}
+
+impl<T: Clone> Parsed<T> {
+    pub fn tokens(&self) -> &[T] {
+        &self.tokens
+    }
+
+    pub fn tree(&self) -> ParseTree<T> {
+        to_parse_tree(&self.root, &mut self.tokens.iter())
+    }
+}
+
+// the leaves are the tokens in order, reused subtrees only cover tokens the edit kept
+fn to_parse_tree<T: Clone>(node: &Node, tokens: &mut slice::Iter<T>) -> ParseTree<T> {
+    match &node.token {
+        Token::T(_) => ParseTree::Leaf(tokens.next().unwrap().clone()),
+        Token::NT(nt) if node.children.is_empty() => ParseTree::Node(nt.into(), vec![ParseTree::Empty]),
+        Token::NT(nt) => ParseTree::Node(nt.into(), node.children.iter().map(|c| to_parse_tree(c, tokens)).collect()),
+    }
+}
+
+// A subtree can be reused if neither its tokens nor the lookahead that decided its last
+// reduction were touched by the edit.
+fn collect_candidates(node: &Rc<Node>, start: usize, edit: &Range<usize>, out: &mut Vec<Vec<Rc<Node>>>) {
+    let end = start + node.len;
+    let before = end < edit.start;
+    let after = start >= edit.end;
+    if node.len > 0 && node.token.is_non_terminal() && (before || after) {
+        out[start].push(node.clone());
+    }
+    let mut start = start;
+    for child in &node.children {
+        collect_candidates(child, start, edit, out);
+        start += child.len;
+    }
+}
+
+fn collect_changed(node: &Rc<Node>, reused: &HashSet<*const Node>, path: &mut NodePath, out: &mut Vec<NodePath>) {
+    if reused.contains(&(&**node as *const Node)) {
+        return;
+    }
+    out.push(path.clone());
+    for (i, child) in node.children.iter().enumerate() {
+        path.push(i);
+        collect_changed(child, reused, path, out);
+        path.pop();
+    }
+}
@@ -0,0 +1,475 @@
+//! A small document model with a JSON text form and a compact binary form, just enough to
+//! store grammars and parse tables.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Int(u64),
+    Str(String),
+    Array(Vec<Value>),
+    /// Keys keep their insertion order so the output is stable.
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    pub fn str<S: Into<String>>(s: S) -> Self {
+        Value::Str(s.into())
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_int(&self) -> Option<u64> {
+        match self {
+            Value::Int(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    pub fn as_object(&self) -> Option<&[(String, Value)]> {
+        match self {
+            Value::Object(fields) => Some(fields),
+            _ => None,
+        }
+    }
+}
+
+///////////////////////// text /////////////////////////////////////////////////////////////////////
+
+/// Pretty prints with two space indentation. Arrays holding only scalars, or arrays of scalars,
+/// stay on one line, which keeps table rows readable in diffs.
+pub fn to_string(value: &Value) -> String {
+    let mut ret = String::new();
+    write_value(value, 0, &mut ret);
+    ret.push('\n');
+    ret
+}
+
+fn write_value(value: &Value, indent: usize, out: &mut String) {
+    match value {
+        Value::Null => out.push_str("null"),
+        Value::Bool(b) => write!(out, "{}", b).unwrap(),
+        Value::Int(i) => write!(out, "{}", i).unwrap(),
+        Value::Str(s) => write_str(s, out),
+        Value::Array(items) if items.iter().all(is_flat) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                write_value(item, indent, out);
+            }
+            out.push(']');
+        }
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                out.push_str(if i > 0 { ",\n" } else { "\n" });
+                push_indent(indent + 1, out);
+                write_value(item, indent + 1, out);
+            }
+            out.push('\n');
+            push_indent(indent, out);
+            out.push(']');
+        }
+        Value::Object(fields) if fields.is_empty() => out.push_str("{}"),
+        Value::Object(fields) => {
+            out.push('{');
+            for (i, (k, v)) in fields.iter().enumerate() {
+                out.push_str(if i > 0 { ",\n" } else { "\n" });
+                push_indent(indent + 1, out);
+                write_str(k, out);
+                out.push_str(": ");
+                write_value(v, indent + 1, out);
+            }
+            out.push('\n');
+            push_indent(indent, out);
+            out.push('}');
+        }
+    }
+}
+
+fn is_flat(value: &Value) -> bool {
+    match value {
+        Value::Object(_) => false,
+        Value::Array(items) => items.iter().all(|v| !matches!(v, Value::Array(_) | Value::Object(_))),
+        _ => true,
+    }
+}
+
+fn push_indent(indent: usize, out: &mut String) {
+    for _ in 0..indent {
+        out.push_str("  ");
+    }
+}
+
+fn write_str(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+pub fn from_str(text: &str) -> Result<Value, String> {
+    let mut reader = Reader {
+        chars: text.char_indices().peekable(),
+        text,
+    };
+    let value = reader.value()?;
+    reader.skip_ws();
+    match reader.chars.next() {
+        None => Ok(value),
+        Some((i, _)) => Err(format!("trailing characters at byte {}", i)),
+    }
+}
+
+struct Reader<'a> {
+    chars: std::iter::Peekable<std::str::CharIndices<'a>>,
+    text: &'a str,
+}
+
+impl<'a> Reader<'a> {
+    fn skip_ws(&mut self) {
+        while let Some(&(_, c)) = self.chars.peek() {
+            if !c.is_whitespace() {
+                break;
+            }
+            self.chars.next();
+        }
+    }
+
+    fn pos(&mut self) -> usize {
+        self.chars.peek().map_or(self.text.len(), |&(i, _)| i)
+    }
+
+    fn expect(&mut self, want: char) -> Result<(), String> {
+        self.skip_ws();
+        let pos = self.pos();
+        match self.chars.next() {
+            Some((_, c)) if c == want => Ok(()),
+            Some((_, c)) => Err(format!("expected `{}` at byte {}, found `{}`", want, pos, c)),
+            None => Err(format!("expected `{}` at end of input", want)),
+        }
+    }
+
+    fn keyword(&mut self, word: &str, value: Value) -> Result<Value, String> {
+        let pos = self.pos();
+        if self.text[pos..].starts_with(word) {
+            for _ in 0..word.len() {
+                self.chars.next();
+            }
+            Ok(value)
+        } else {
+            Err(format!("unexpected input at byte {}", pos))
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.skip_ws();
+        let pos = self.pos();
+        match self.chars.peek().map(|&(_, c)| c) {
+            None => Err("unexpected end of input".to_string()),
+            Some('n') => self.keyword("null", Value::Null),
+            Some('t') => self.keyword("true", Value::Bool(true)),
+            Some('f') => self.keyword("false", Value::Bool(false)),
+            Some('"') => self.string().map(Value::Str),
+            Some('[') => {
+                self.chars.next();
+                let mut items = vec![];
+                self.skip_ws();
+                if self.chars.peek().map(|&(_, c)| c) == Some(']') {
+                    self.chars.next();
+                    return Ok(Value::Array(items));
+                }
+                loop {
+                    items.push(self.value()?);
+                    self.skip_ws();
+                    match self.chars.next() {
+                        Some((_, ',')) => continue,
+                        Some((_, ']')) => return Ok(Value::Array(items)),
+                        _ => return Err(format!("unterminated array starting at byte {}", pos)),
+                    }
+                }
+            }
+            Some('{') => {
+                self.chars.next();
+                let mut fields = vec![];
+                self.skip_ws();
+                if self.chars.peek().map(|&(_, c)| c) == Some('}') {
+                    self.chars.next();
+                    return Ok(Value::Object(fields));
+                }
+                loop {
+                    self.skip_ws();
+                    let key = self.string()?;
+                    self.expect(':')?;
+                    fields.push((key, self.value()?));
+                    self.skip_ws();
+                    match self.chars.next() {
+                        Some((_, ',')) => continue,
+                        Some((_, '}')) => return Ok(Value::Object(fields)),
+                        _ => return Err(format!("unterminated object starting at byte {}", pos)),
+                    }
+                }
+            }
+            Some(c) if c.is_ascii_digit() => {
+                let mut n: u64 = 0;
+                while let Some(&(_, c)) = self.chars.peek() {
+                    let d = match c.to_digit(10) {
+                        Some(d) => d,
+                        None => break,
+                    };
+                    n = n
+                        .checked_mul(10)
+                        .and_then(|n| n.checked_add(d as u64))
+                        .ok_or_else(|| format!("number too large at byte {}", pos))?;
+                    self.chars.next();
+                }
+                Ok(Value::Int(n))
+            }
+            Some(c) => Err(format!("unexpected `{}` at byte {}", c, pos)),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let pos = self.pos();
+        self.expect('"')?;
+        let mut ret = String::new();
+        loop {
+            match self.chars.next() {
+                None => return Err(format!("unterminated string starting at byte {}", pos)),
+                Some((_, '"')) => return Ok(ret),
+                Some((_, '\\')) => match self.chars.next() {
+                    Some((_, '"')) => ret.push('"'),
+                    Some((_, '\\')) => ret.push('\\'),
+                    Some((_, '/')) => ret.push('/'),
+                    Some((_, 'n')) => ret.push('\n'),
+                    Some((_, 't')) => ret.push('\t'),
+                    Some((_, 'r')) => ret.push('\r'),
+                    Some((i, 'u')) => {
+                        let hex = self.text.get(i + 1..i + 5).unwrap_or("");
+                        let c = u32::from_str_radix(hex, 16)
+                            .ok()
+                            .and_then(std::char::from_u32)
+                            .ok_or_else(|| format!("bad unicode escape at byte {}", i))?;
+                        for _ in 0..4 {
+                            self.chars.next();
+                        }
+                        ret.push(c);
+                    }
+                    _ => return Err(format!("bad escape in string starting at byte {}", pos)),
+                },
+                Some((_, c)) => ret.push(c),
+            }
+        }
+    }
+}
+
+///////////////////////// binary ///////////////////////////////////////////////////////////////////
+
+const MAGIC: &[u8] = b"EAC2";
+
+const NULL: u8 = 0;
+const FALSE: u8 = 1;
+const TRUE: u8 = 2;
+const INT: u8 = 3;
+const STR: u8 = 4;
+const ARRAY: u8 = 5;
+const OBJECT: u8 = 6;
+
+/// Binary form: the magic bytes, a table of every distinct string, then the value tree with
+/// strings replaced by their index in the table. All integers are LEB128 varints.
+pub fn to_bytes(value: &Value) -> Vec<u8> {
+    let mut strings = StringTable::default();
+    let mut body = vec![];
+    encode(value, &mut strings, &mut body);
+
+    let mut ret = MAGIC.to_vec();
+    put_varint(strings.list.len() as u64, &mut ret);
+    for s in &strings.list {
+        put_varint(s.len() as u64, &mut ret);
+        ret.extend_from_slice(s.as_bytes());
+    }
+    ret.extend(body);
+    ret
+}
+
+#[derive(Default)]
+struct StringTable<'a> {
+    list: Vec<&'a str>,
+    ids: HashMap<&'a str, u64>,
+}
+
+impl<'a> StringTable<'a> {
+    fn id(&mut self, s: &'a str) -> u64 {
+        let next = self.list.len() as u64;
+        let id = *self.ids.entry(s).or_insert(next);
+        if id == next {
+            self.list.push(s);
+        }
+        id
+    }
+}
+
+fn encode<'a>(value: &'a Value, strings: &mut StringTable<'a>, out: &mut Vec<u8>) {
+    match value {
+        Value::Null => out.push(NULL),
+        Value::Bool(false) => out.push(FALSE),
+        Value::Bool(true) => out.push(TRUE),
+        Value::Int(i) => {
+            out.push(INT);
+            put_varint(*i, out);
+        }
+        Value::Str(s) => {
+            out.push(STR);
+            put_varint(strings.id(s), out);
+        }
+        Value::Array(items) => {
+            out.push(ARRAY);
+            put_varint(items.len() as u64, out);
+            for item in items {
+                encode(item, strings, out);
+            }
+        }
+        Value::Object(fields) => {
+            out.push(OBJECT);
+            put_varint(fields.len() as u64, out);
+            for (k, v) in fields {
+                put_varint(strings.id(k), out);
+                encode(v, strings, out);
+            }
+        }
+    }
+}
+
+fn put_varint(mut n: u64, out: &mut Vec<u8>) {
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+        if n == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+pub fn from_bytes(bytes: &[u8]) -> Result<Value, String> {
+    if !bytes.starts_with(MAGIC) {
+        return Err("not a binary table file".to_string());
+    }
+    let mut decoder = Decoder {
+        bytes,
+        pos: MAGIC.len(),
+        strings: vec![],
+    };
+    let n = decoder.varint()?;
+    for _ in 0..n {
+        let len = decoder.varint()? as usize;
+        let raw = decoder.take(len)?;
+        let s = String::from_utf8(raw.to_vec()).map_err(|_| "string is not utf-8".to_string())?;
+        decoder.strings.push(s);
+    }
+    let value = decoder.value()?;
+    if decoder.pos != bytes.len() {
+        return Err(format!("trailing bytes at offset {}", decoder.pos));
+    }
+    Ok(value)
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    strings: Vec<String>,
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        if self.bytes.len() - self.pos < n {
+            return Err("unexpected end of data".to_string());
+        }
+        let ret = &self.bytes[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(ret)
+    }
+
+    fn varint(&mut self) -> Result<u64, String> {
+        let mut ret = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.take(1)?[0];
+            ret |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(ret);
+            }
+        }
+        Err(format!("varint too long at offset {}", self.pos))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let id = self.varint()? as usize;
+        self.strings
+            .get(id)
+            .cloned()
+            .ok_or_else(|| format!("unknown string {} at offset {}", id, self.pos))
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        let tag = self.take(1)?[0];
+        Ok(match tag {
+            NULL => Value::Null,
+            FALSE => Value::Bool(false),
+            TRUE => Value::Bool(true),
+            INT => Value::Int(self.varint()?),
+            STR => Value::Str(self.string()?),
+            ARRAY => {
+                let n = self.varint()?;
+                let mut items = vec![];
+                for _ in 0..n {
+                    items.push(self.value()?);
+                }
+                Value::Array(items)
+            }
+            OBJECT => {
+                let n = self.varint()?;
+                let mut fields = vec![];
+                for _ in 0..n {
+                    let k = self.string()?;
+                    fields.push((k, self.value()?));
+                }
+                Value::Object(fields)
+            }
+            t => return Err(format!("unknown tag {} at offset {}", t, self.pos - 1)),
+        })
+    }
+}
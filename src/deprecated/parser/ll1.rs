@@ -1,6 +1,13 @@
 use std::collections::HashMap;
+use std::slice;
+
+use crate::parser::stream::{TokenBuffer, TokenStream};
+use crate::parser::token::Kind;
+use crate::parser::{Error as ParseError, ParseTree};
 
 use super::*;
+use super::bitset::BitSet;
+use super::trace::Trace;
 
 type Table<'a> = HashMap<NoneTerminal, HashMap<Terminal, Option<&'a Production>>>;
 
@@ -8,48 +15,162 @@ pub struct Error;
 
 type Result = std::result::Result<(), Error>;
 
-pub fn parse(tokens: &[Terminal], cfg: &NoneLeftRecursionCFG) -> Result {
+#[derive(Clone, Debug)]
+pub enum Move {
+    Expand(Production),
+    Match(Terminal),
+    Accept,
+    Error,
+}
+
+pub type TraceStep = Trace<Token, Move>;
+
+pub fn parse<T: Kind + Clone>(tokens: &[T], cfg: &NoneLeftRecursionCFG) -> Result {
     let table = contruct_talbe(cfg);
-    let mut stack = vec![];
-    stack.push(Token::T(Terminal::eof()));
-    stack.push(Token::NT(NoneTerminal::start()));
-    let mut tokens = Tokens::new(tokens);
-
-    while let Some(token) = stack.pop() {
-        let tok = tokens.current();
-        if token.is_eof() && tok.is_eof() {
-            return Ok(())
-        } else if token.is_eof() || tok.is_eof() {
-            return Err(Error)
+    drive(TokenBuffer::new(tokens.iter().cloned()), &table, cfg.0.augmented(), None, None).map_err(|_| Error)
+}
+
+pub fn parse_stream<S>(tokens: S, cfg: &NoneLeftRecursionCFG) -> Result
+where
+    S: TokenStream,
+    S::Token: Kind + Clone,
+{
+    drive(tokens, &contruct_talbe(cfg), cfg.0.augmented(), None, None).map_err(|_| Error)
+}
+
+/// Parses with predict sets computed earlier, e.g. loaded by `serialize::Tables`.
+pub fn parse_with_predict<T: Kind + Clone>(tokens: &[T], cfg: &CFG, first: &First, predict: &Predict) -> Result {
+    let table = table_from_predict(cfg, first.index(), predict);
+    drive(TokenBuffer::new(tokens.iter().cloned()), &table, cfg.augmented(), None, None).map_err(|_| Error)
+}
+
+/// Parses `tokens` into a tree, built from the expansions the driver makes. The tokens end up
+/// in the leaves, or in the error at the first one that can't be matched.
+pub fn parse_tree<T>(tokens: &[T], cfg: &NoneLeftRecursionCFG) -> std::result::Result<ParseTree<T>, ParseError<T>>
+where
+    T: Kind + Clone,
+{
+    let mut expansions = vec![];
+    let table = contruct_talbe(cfg);
+    drive(TokenBuffer::new(tokens.iter().cloned()), &table, cfg.0.augmented(), Some(&mut expansions), None)?;
+    Ok(grow(&mut expansions.into_iter(), &mut tokens.iter()))
+}
+
+// the expansions of a leftmost derivation visit the tree in preorder
+fn grow<T: Clone>(expansions: &mut dyn Iterator<Item = Production>, tokens: &mut slice::Iter<T>) -> ParseTree<T> {
+    let p = expansions.next().unwrap();
+    let children = p
+        .tokens
+        .iter()
+        .map(|t| match t {
+            Token::T(t) if t.is_empty() => ParseTree::Empty,
+            Token::T(_) => ParseTree::Leaf(tokens.next().unwrap().clone()),
+            Token::NT(_) => grow(expansions, tokens),
+        })
+        .collect();
+    ParseTree::Node((&p.non_terminal).into(), children)
+}
+
+/// Same as `parse`, but reports every configuration of the driver to `on_step`
+/// before the move is made. Each report copies the stack and the rest of the input, it is
+/// meant for debugging.
+pub fn parse_with_trace<T, F>(tokens: &[T], cfg: &NoneLeftRecursionCFG, mut on_step: F) -> Result
+where
+    T: Kind + Clone,
+    F: FnMut(TraceStep),
+{
+    let table = contruct_talbe(cfg);
+    let tokens = TokenBuffer::new(tokens.iter().cloned());
+    drive(tokens, &table, cfg.0.augmented(), None, Some(&mut on_step)).map_err(|_| Error)
+}
+
+// starts out with `S' -> S $` already expanded; the expansions it makes go to `expansions`,
+// in the order of a leftmost derivation
+fn drive<S>(
+    mut tokens: S,
+    table: &Table,
+    augmented: Production,
+    mut expansions: Option<&mut Vec<Production>>,
+    mut on_step: Option<&mut dyn FnMut(TraceStep)>,
+) -> std::result::Result<(), ParseError<S::Token>>
+where
+    S: TokenStream,
+    S::Token: Kind + Clone,
+{
+    let mut stack: Vec<_> = augmented.tokens.into_iter().rev().collect();
+    let mut matched = 0;
+
+    while let Some(token) = stack.last().cloned() {
+        let tok = tokens.current().map_or(Terminal::Eof, |t| t.kind().into());
+        let mv = match &token {
+            Token::T(t) if t.is_eof() && tok.is_eof() => Move::Accept,
+            Token::NT(nt) => match table.get(nt).and_then(|row| row.get(&tok)) {
+                Some(&Some(p)) => Move::Expand(p.clone()),
+                _ => Move::Error,
+            },
+            Token::T(t) if t == &tok => Move::Match(tok.clone()),
+            Token::T(_) => Move::Error,
+        };
+        if let Some(on_step) = on_step.as_mut() {
+            let mut input: Vec<Terminal> = tokens.remaining().iter().map(|t| t.kind().into()).collect();
+            input.push(Terminal::Eof);
+            on_step(Trace {
+                stack: stack.clone(),
+                input,
+                action: mv.clone(),
+            });
         }
-        match token {
-            Token::NT(nt) => {
-                if let &Some(p) = table.get(&nt).unwrap().get(tok).unwrap() {
-                    if !p.tokens[0].is_empty() {
-                        stack.extend(p.tokens.clone().into_iter().rev());
-                    }
-                } else {
-                    return Err(Error)
-                }
+
+        stack.pop();
+        match mv {
+            Move::Accept => return Ok(()),
+            Move::Error => {
+                return Err(ParseError {
+                    position: matched,
+                    found: tokens.current().cloned(),
+                })
             }
-            Token::T(t) => {
-                if &t == tok {
-                    tokens.forward();
-                } else {
-                    return Err(Error)
+            Move::Expand(p) => {
+                if let Some(expansions) = expansions.as_mut() {
+                    expansions.push(p.clone());
+                }
+                if !p.tokens[0].is_empty() {
+                    stack.extend(p.tokens.into_iter().rev());
                 }
             }
+            Move::Match(_) => {
+                tokens.forward();
+                matched += 1;
+            }
         }
     }
     Ok(())
 }
 
-pub fn contruct_talbe(cfg: &NoneLeftRecursionCFG) -> Table {
+/// Whether no two alternatives of a nonterminal are predicted by the same terminal.
+pub fn is_ll1(cfg: &NoneLeftRecursionCFG) -> bool {
+    let first = first(&cfg.0);
+    let follow = follow(&cfg.0, &first);
+    let predict = predict(cfg, &first, &follow);
+    cfg.0.non_terminals.iter().all(|nt| {
+        let mut seen = BitSet::new();
+        (0..cfg.0.productions[nt].len()).all(|i| {
+            let set = &predict[&(nt.clone(), i)];
+            let disjoint = set.iter().all(|t| !seen.contains(t));
+            seen.union_with(set);
+            disjoint
+        })
+    })
+}
+
+pub fn contruct_talbe(cfg: &NoneLeftRecursionCFG) -> Table<'_> {
     let first = first(&cfg.0);
     let follow = follow(&cfg.0, &first);
     let predict = predict(cfg, &first, &follow);
-    let cfg = &cfg.0;
+    table_from_predict(&cfg.0, first.index(), &predict)
+}
 
+fn table_from_predict<'a>(cfg: &'a CFG, index: &TerminalIndex, predict: &Predict) -> Table<'a> {
     let mut table = HashMap::new();
     for nt in &cfg.non_terminals {
         let mut ps = HashMap::new();
@@ -57,9 +178,9 @@ pub fn contruct_talbe(cfg: &NoneLeftRecursionCFG) -> Table {
             ps.insert(t.clone(), None);
         }
 
-        for (i, p) in cfg.productions.get(nt).unwrap().into_iter().enumerate() {
+        for (i, p) in cfg.productions.get(nt).unwrap().iter().enumerate() {
             let pre = predict.get(&(nt.clone(), i)).unwrap();
-            for t in pre  {
+            for t in index.terminals(pre) {
                 ps.insert(t.clone(), Some(p));
             }
         }
@@ -68,32 +189,13 @@ pub fn contruct_talbe(cfg: &NoneLeftRecursionCFG) -> Table {
     table
 }
 
-struct Tokens<'a> {
-    tokens: &'a [Terminal],
-    idx: usize,
-    eof: Terminal,
-}
-
-impl<'a> Tokens<'a> {
-    fn new(tokens: &'a[Terminal]) -> Self {
-        Tokens {
-            tokens: tokens,
-            idx: 0,
-            eof: Terminal::eof(),
-        }
-    }
-
-    fn forward(&mut self) {
-        if self.idx != self.tokens.len() {
-            self.idx += 1;
-        }
-    }
-
-    fn current(&self) -> &Terminal {
-        if self.idx == self.tokens.len() {
-            &self.eof
-        } else {
-            &self.tokens[self.idx]
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> std::result::Result<(), fmt::Error> {
+        match self {
+            Move::Expand(p) => write!(f, "expand {}", p.to_string().trim_end()),
+            Move::Match(t) => write!(f, "match {}", t),
+            Move::Accept => write!(f, "accept"),
+            Move::Error => write!(f, "error"),
         }
     }
 }
@@ -1,48 +1,85 @@
-use std::collections::BTreeSet;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::parser::stream::{TokenBuffer, TokenStream};
+use crate::parser::token::Kind;
+use crate::parser::{Error as ParseError, ParseTree};
 
 use super::*;
+use super::bitset::BitSet;
+use super::trace::Trace;
 
 pub type State = usize;
-type ItemSet = Vec<Item>;
+// LR(0) item: production number and dot position
+type Core = (usize, usize);
+// items sharing a core are kept as one entry with all their lookaheads
+type ItemSet = BTreeMap<Core, BitSet>;
 type CanonicalCollection = Vec<ItemSet>;
-type GotoTable = HashMap<(State, NoneTerminal), State>;
-type ActionTable = HashMap<(State, Terminal), Action>;
+pub type GotoTable = HashMap<(State, NoneTerminal), State>;
+pub type ActionTable = HashMap<(State, Terminal), Action>;
 type StateTransfer = HashMap<(State, Token), State>;
 
 pub struct Error;
 
 type Result = std::result::Result<(), Error>;
 
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum Action {
     Reduce(Production),
     Shift(State),
     Accept
 }
 
-enum Step {
+#[derive(Clone, Debug)]
+pub enum Step {
     Token(Token),
     State(State),
 }
 
+#[derive(Clone, Debug)]
+pub enum Move {
+    Shift(State),
+    Reduce(Production),
+    Accept,
+    Error,
+}
+
+pub type TraceStep = Trace<Step, Move>;
+
 pub fn build_action_and_goto_table(cfg: &CFG) -> (ActionTable, GotoTable) {
-    let first = first(cfg);
-    let (cc, transfer) = build_cc(cfg, &first);
+    let (action, goto, _) = build(cfg);
+    (action, goto)
+}
+
+/// Whether the grammar is LR(1), i.e. no state calls for two different actions on a terminal.
+pub fn is_lr1(cfg: &CFG) -> bool {
+    build(cfg).2 == 0
+}
+
+// also counts the conflicts, the action inserted last wins
+fn build(cfg: &CFG) -> (ActionTable, GotoTable, usize) {
+    let grammar = Grammar::new(cfg);
+    let (cc, transfer) = build_cc(&grammar);
+    let index = grammar.first.index();
     let (mut action, mut goto) = (ActionTable::new(), GotoTable::new());
+    let mut conflicts = 0;
     for (i, items) in cc.iter().enumerate() {
-        for item in items {
-            if item.right.is_empty() {
-                let v = if item.nt == NoneTerminal::start() && item.lookahead == Terminal::eof() {
-                    Action::Accept
-                } else {
-                    Action::Reduce(Production::new(item.nt.clone(), item.left.clone()))
-                };
-                action.insert((i, item.lookahead.clone()), v);
+        for (&(p, dot), lookaheads) in items {
+            let prod = &grammar.prods[p];
+            let right = &grammar.rights[p];
+            if dot == right.len() {
+                for t in index.terminals(lookaheads) {
+                    let v = Action::Reduce(Production::new(prod.non_terminal.clone(), right.clone()));
+                    conflicts += set_action(&mut action, (i, t.clone()), v) as usize;
+                }
+                continue;
+            }
+            if p == AUGMENTED && dot == 1 {
+                conflicts += set_action(&mut action, (i, Terminal::Eof), Action::Accept) as usize;
                 continue;
             }
-            if let Token::T(t) = &item.right[0] {
-                let s = transfer.get(&(i, item.right[0].clone())).unwrap();
-                action.insert((i, t.clone()), Action::Shift(*s));
+            if let Token::T(t) = &right[dot] {
+                let s = transfer.get(&(i, right[dot].clone())).unwrap();
+                conflicts += set_action(&mut action, (i, t.clone()), Action::Shift(*s)) as usize;
             }
         }
         for nt in &cfg.non_terminals {
@@ -51,23 +88,123 @@ pub fn build_action_and_goto_table(cfg: &CFG) -> (ActionTable, GotoTable) {
             }
         }
     }
-    (action, goto)
+    (action, goto, conflicts)
+}
+
+// whether a different action was there already
+fn set_action(action: &mut ActionTable, key: (State, Terminal), v: Action) -> bool {
+    match action.insert(key, v.clone()) {
+        Some(old) => old != v,
+        None => false,
+    }
 }
 
-pub fn parse(tokens: &[Terminal], cfg: &CFG) -> Result {
+pub fn parse<T: Kind + Clone>(tokens: &[T], cfg: &CFG) -> Result {
     let (action, goto) = build_action_and_goto_table(cfg);
+    drive(TokenBuffer::new(tokens.iter().cloned()), &action, &goto, None, None).map_err(|_| Error)
+}
+
+pub fn parse_stream<S>(tokens: S, cfg: &CFG) -> Result
+where
+    S: TokenStream,
+    S::Token: Kind + Clone,
+{
+    let (action, goto) = build_action_and_goto_table(cfg);
+    drive(tokens, &action, &goto, None, None).map_err(|_| Error)
+}
+
+/// Parses with tables built earlier, e.g. loaded by `serialize::Tables`.
+pub fn parse_with_tables<T: Kind + Clone>(tokens: &[T], action: &ActionTable, goto: &GotoTable) -> Result {
+    drive(TokenBuffer::new(tokens.iter().cloned()), action, goto, None, None).map_err(|_| Error)
+}
+
+/// Parses `tokens` into a tree, built from the reductions the driver makes. The tokens end up
+/// in the leaves, or in the error at the first one that can't be shifted.
+pub fn parse_tree<T>(tokens: &[T], cfg: &CFG) -> std::result::Result<ParseTree<T>, ParseError<T>>
+where
+    T: Kind + Clone,
+{
+    let (action, goto) = build_action_and_goto_table(cfg);
+    parse_tree_with_tables(tokens, &action, &goto)
+}
+
+pub fn parse_tree_with_tables<T>(
+    tokens: &[T],
+    action: &ActionTable,
+    goto: &GotoTable,
+) -> std::result::Result<ParseTree<T>, ParseError<T>>
+where
+    T: Kind + Clone,
+{
+    let mut trees = vec![];
+    drive(TokenBuffer::new(tokens.iter().cloned()), action, goto, Some(&mut trees), None)?;
+    Ok(trees.pop().unwrap())
+}
+
+/// Same as `parse`, but reports every configuration of the driver to `on_step`
+/// before the action is applied. Each report copies the stack and the rest of the input, it
+/// is meant for debugging.
+pub fn parse_with_trace<T, F>(tokens: &[T], cfg: &CFG, mut on_step: F) -> Result
+where
+    T: Kind + Clone,
+    F: FnMut(TraceStep),
+{
+    let (action, goto) = build_action_and_goto_table(cfg);
+    drive(TokenBuffer::new(tokens.iter().cloned()), &action, &goto, None, Some(&mut on_step)).map_err(|_| Error)
+}
+
+// with `trees`, shifts push the token as a leaf and reductions replace their right-hand side
+// with a node, which leaves the tree of the input once it's accepted
+fn drive<S>(
+    mut tokens: S,
+    action: &ActionTable,
+    goto: &GotoTable,
+    mut trees: Option<&mut Vec<ParseTree<S::Token>>>,
+    mut on_step: Option<&mut dyn FnMut(TraceStep)>,
+) -> std::result::Result<(), ParseError<S::Token>>
+where
+    S: TokenStream,
+    S::Token: Kind + Clone,
+{
     let mut stack = vec![Step::State(0)];
-    let mut tokens = Tokens::new(tokens);
     let mut s = 0;
+    let mut shifted = 0;
     loop {
-        let mut token = tokens.current();
-        let act = match action.get(&(s, token.clone())) {
+        let token = tokens.current().map_or(Terminal::Eof, |t| t.kind().into());
+        let act = action.get(&(s, token.clone()));
+        if let Some(on_step) = on_step.as_mut() {
+            let mut input: Vec<Terminal> = tokens.remaining().iter().map(|t| t.kind().into()).collect();
+            input.push(Terminal::Eof);
+            on_step(Trace {
+                stack: stack.clone(),
+                input,
+                action: match act {
+                    Some(Action::Shift(s)) => Move::Shift(*s),
+                    Some(Action::Reduce(p)) => Move::Reduce(p.clone()),
+                    Some(Action::Accept) => Move::Accept,
+                    None => Move::Error,
+                },
+            });
+        }
+        let act = match act {
             Some(a) => a,
-            _ => return Err(Error),
+            _ => {
+                return Err(ParseError {
+                    position: shifted,
+                    found: tokens.current().cloned(),
+                })
+            }
         };
         match act {
             Action::Accept => break,
             Action::Reduce(p) => {
+                if let Some(trees) = trees.as_mut() {
+                    let mut children = trees.split_off(trees.len() - p.tokens.len());
+                    if children.is_empty() {
+                        children.push(ParseTree::Empty);
+                    }
+                    trees.push(ParseTree::Node((&p.non_terminal).into(), children));
+                }
                 for _ in 0..(2*p.tokens.len()) {
                     stack.pop();
                 }
@@ -81,178 +218,152 @@ pub fn parse(tokens: &[Terminal], cfg: &CFG) -> Result {
                 stack.push(Step::State(s));
             }
             Action::Shift(to) => {
-                stack.push(Step::Token(Token::T(token.clone())));
+                stack.push(Step::Token(Token::T(token)));
                 stack.push(Step::State(*to));
                 s = *to;
+                if let Some(trees) = trees.as_mut() {
+                    trees.push(ParseTree::Leaf(tokens.current().cloned().unwrap()));
+                }
                 tokens.forward();
+                shifted += 1;
             }
         }
     }
     Ok(())
 }
 
-#[derive(Eq, PartialEq, Ord, PartialOrd, Debug, Hash, Clone)]
-struct Item {
-    nt: NoneTerminal,
-    left: Vec<Token>,
-    right: Vec<Token>,
-    lookahead: Terminal,
-}
-
-fn closure(items: &mut ItemSet, cfg: &CFG, first: &First) {
-    let mut updated = true;
-    while updated {
-        updated = false;
-        let mut generated = ItemSet::new();
-        for item in &mut items.clone() {
-            let right = &item.right;
-            if right.is_empty() {
-                continue;
-            }
-
-            let nt = match &right[0] {
-                Token::NT(nt) => nt,
-                _ => continue,
-            };
-            let lookahead = Token::T(item.lookahead.clone());
-            let follow_token = if right.len() > 1 {
-                vec![right[1].clone(), lookahead]
-            } else {
-                vec![lookahead]
-            };
-            let first = first_of_tokens(&follow_token, first);
-            for p in cfg.productions.get(nt).unwrap() {
-                for t in &first {
-                    let item = Item {
-                        nt: nt.clone(),
-                        left: vec![],
-                        right: p.tokens.clone(),
-                        lookahead: t.clone(),
-                    };
-                    generated.push(item);
-                }
-            }
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter) -> std::result::Result<(), fmt::Error> {
+        match self {
+            Step::Token(t) => write!(f, "{}", t),
+            Step::State(s) => write!(f, "{}", s),
         }
-        updated = append(items, generated)
     }
 }
 
-fn goto(items: &ItemSet, token: &Token, cfg: &CFG, first: &First) -> ItemSet {
-    let mut ret = ItemSet::new();
-    for item in items {
-        if item.right.is_empty() || &item.right[0] != token {
-            continue;
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> std::result::Result<(), fmt::Error> {
+        match self {
+            Move::Shift(s) => write!(f, "shift {}", s),
+            Move::Reduce(p) => write!(f, "reduce {}", p.to_string().trim_end()),
+            Move::Accept => write!(f, "accept"),
+            Move::Error => write!(f, "error"),
         }
-        let mut item = item.clone();
-        item.left.push(item.right[0].clone());
-        item.right = Vec::from(&item.right[1..]);
-        ret.push(item);
     }
-    closure(&mut ret, cfg, first);
-    ret
 }
 
-fn build_cc(cfg: &CFG, first: &First) -> (CanonicalCollection, StateTransfer) {
-    let mut cc = CanonicalCollection::new();
-    let mut transfer = StateTransfer::new();
-    let seed = cfg
-        .productions
-        .get(&NoneTerminal::start())
-        .unwrap()
-        .clone()
-        .into_iter()
-        .map(|p| Item {
-            nt: p.non_terminal,
-            left: vec![],
-            right: p.tokens,
-            lookahead: Terminal::eof(),
-        })
-        .collect();
-    cc.push(seed);
-    
-    let mut updated = true;
-    let mut start_idx = 0;
-    while updated {
-        updated = false;
-        let mut generated = CanonicalCollection::new();
-        for (i, items) in cc.iter().enumerate().skip(start_idx) {
-            let next_tokens = next_tokens(items);
-            for token in &next_tokens {
-                let gen = goto(items, token, cfg, first);
-                let key = (i, token.clone());
-                if let Some(state) = find(&cc, &gen) {
-                    transfer.insert(key, state);
-                } else if let Some(state) = find(&generated, &gen) {
-                    transfer.insert(key, cc.len() + state);
-                } else {
-                    transfer.insert(key, cc.len() + generated.len());
-                    generated.push(gen);
-                }
+// `S' -> S $` comes first
+const AUGMENTED: usize = 0;
+
+/// The productions of the augmented grammar numbered for item construction.
+struct Grammar<'a> {
+    prods: Vec<Production>,
+    // right-hand sides without `Terminal::Empty`
+    rights: Vec<Vec<Token>>,
+    by_left: HashMap<&'a NoneTerminal, Vec<usize>>,
+    first: First,
+    // FIRST of every suffix `rights[p][dot..]` without `Terminal::Empty`, and whether it is nullable
+    suffixes: Vec<Vec<(BitSet, bool)>>,
+}
+
+impl<'a> Grammar<'a> {
+    fn new(cfg: &'a CFG) -> Self {
+        let first = first(cfg);
+        let mut prods = vec![cfg.augmented()];
+        let mut by_left = HashMap::new();
+        for nt in &cfg.non_terminals {
+            for p in cfg.productions.get(nt).into_iter().flatten() {
+                by_left.entry(nt).or_insert_with(Vec::new).push(prods.len());
+                prods.push(p.clone());
             }
         }
-        if !generated.is_empty() {
-            start_idx += cc.len();
-            cc.extend(generated);
-            updated = true;
+        let rights: Vec<Vec<Token>> = prods
+            .iter()
+            .map(|p| p.tokens.iter().filter(|t| !t.is_empty()).cloned().collect())
+            .collect();
+        let suffixes = rights
+            .iter()
+            .map(|right| {
+                (0..=right.len())
+                    .map(|dot| {
+                        let mut f = first.of_tokens(&right[dot..]);
+                        let nullable = f.remove(TerminalIndex::EMPTY);
+                        (f, nullable)
+                    })
+                    .collect()
+            })
+            .collect();
+        Grammar {
+            prods,
+            rights,
+            by_left,
+            first,
+            suffixes,
         }
     }
-    (cc, transfer)
 }
 
-fn append(dst: &mut ItemSet, src: ItemSet) -> bool {
-    let mut ret = false;
-    let mut hashed: HashSet<_> = dst.clone().into_iter().collect();
-    for t in src {
-        if hashed.get(&t).is_none() {
-            dst.push(t.clone());
-            hashed.insert(t);
-            ret = true;
+/// Closes `items` with a worklist: an item is expanded again only when its lookahead set grew.
+fn closure(mut items: ItemSet, grammar: &Grammar) -> ItemSet {
+    let mut work: Vec<Core> = items.keys().cloned().collect();
+    while let Some((p, dot)) = work.pop() {
+        let nt = match grammar.rights[p].get(dot) {
+            Some(Token::NT(nt)) => nt,
+            _ => continue,
+        };
+        let (ref first, nullable) = grammar.suffixes[p][dot + 1];
+        let mut lookaheads = first.clone();
+        if nullable {
+            lookaheads.union_with(&items[&(p, dot)]);
         }
-    }
-    ret
-}
-
-fn next_tokens(items: &ItemSet) -> Vec<Token> {
-    items.iter().filter_map(|item|item.right.get(0)).map(|t|t.clone()).collect()
-}
-
-fn find(cc: &CanonicalCollection, items: &ItemSet) -> Option<usize> {
-    for (i, c) in cc.iter().enumerate() {
-        let l: BTreeSet<_>= c.iter().collect();
-        let r: BTreeSet<_> = items.iter().collect();
-        if l == r {
-            return Some(i)
+        for &q in grammar.by_left.get(nt).into_iter().flatten() {
+            let grown = match items.get_mut(&(q, 0)) {
+                Some(old) => old.union_with(&lookaheads),
+                None => {
+                    items.insert((q, 0), lookaheads.clone());
+                    true
+                }
+            };
+            if grown {
+                work.push((q, 0));
+            }
         }
     }
-    None
-}
-
-struct Tokens<'a> {
-    tokens: &'a [Terminal],
-    idx: usize,
-    eof: Terminal,
+    items
 }
 
-impl<'a> Tokens<'a> {
-    fn new(tokens: &'a[Terminal]) -> Self {
-        Tokens {
-            tokens: tokens,
-            idx: 0,
-            eof: Terminal::eof(),
-        }
-    }
+/// Builds the canonical collection of LR(1) item sets. States are looked up by their kernel,
+/// the items reached by a transition before closure, so every new set is closed only once.
+fn build_cc(grammar: &Grammar) -> (CanonicalCollection, StateTransfer) {
+    let mut transfer = StateTransfer::new();
+    let mut kernels = HashMap::new();
+    // nothing follows `S' -> S $`, the `$` is matched by accepting
+    let mut seed = ItemSet::new();
+    seed.insert((AUGMENTED, 0), BitSet::new());
+    kernels.insert(seed.clone(), 0);
+    let mut cc = vec![closure(seed, grammar)];
 
-    fn forward(&mut self) {
-        if self.idx != self.tokens.len() {
-            self.idx += 1;
+    let mut i = 0;
+    while i < cc.len() {
+        let mut targets: BTreeMap<&Token, ItemSet> = BTreeMap::new();
+        for (&(p, dot), lookaheads) in &cc[i] {
+            if let Some(token) = grammar.rights[p].get(dot).filter(|t| !t.is_eof()) {
+                targets.entry(token).or_default().insert((p, dot + 1), lookaheads.clone());
+            }
         }
-    }
-
-    fn current(&self) -> &Terminal {
-        if self.idx == self.tokens.len() {
-            &self.eof
-        } else {
-            &self.tokens[self.idx]
+        for (token, kernel) in targets {
+            let state = match kernels.get(&kernel) {
+                Some(&state) => state,
+                None => {
+                    let state = cc.len();
+                    kernels.insert(kernel.clone(), state);
+                    cc.push(closure(kernel, grammar));
+                    state
+                }
+            };
+            transfer.insert((i, token.clone()), state);
         }
+        i += 1;
     }
+    (cc, transfer)
 }
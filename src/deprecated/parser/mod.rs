@@ -1,14 +1,33 @@
 pub mod recursive_descent;
 pub mod ll1;
 pub mod lr1;
-
-use std::collections::{HashMap, HashSet};
+pub mod incremental;
+pub mod trace;
+pub mod bitset;
+pub mod json;
+pub mod serialize;
+pub mod differential;
+
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fmt;
+use std::rc::Rc;
+
+use crate::parser::symbol::Symbol;
 
+use self::bitset::BitSet;
+
+/// A terminal of the grammar, or one of the two sentinels the algorithms need. The sentinels
+/// have no name, so they can't collide with any terminal of a user's grammar.
 #[derive(Eq, PartialEq, Clone, Hash, Ord, PartialOrd, Debug)]
-pub struct Terminal(String);
+pub enum Terminal {
+    Named(Symbol),
+    /// The empty string, in FIRST sets and as the only token of an empty right-hand side.
+    Empty,
+    /// End of input, written `$`.
+    Eof,
+}
 #[derive(Eq, PartialEq, Clone, Hash, Ord, PartialOrd, Debug)]
-pub struct NoneTerminal(String);
+pub struct NoneTerminal(Symbol);
 
 #[derive(Eq, PartialEq, Clone, Hash, Debug, Ord, PartialOrd)]
 pub enum Token {
@@ -18,17 +37,11 @@ pub enum Token {
 
 impl Token {
     pub fn is_terminal(&self) -> bool {
-        match self {
-            Token::T(_) => true,
-            _ => false,
-        }
+        matches!(self, Token::T(_))
     }
 
     pub fn is_non_terminal(&self) -> bool {
-        match self {
-            Token::NT(_) => true,
-            _ => false,
-        }
+        matches!(self, Token::NT(_))
     }
 
     pub fn is_eof(&self) -> bool {
@@ -47,7 +60,11 @@ impl Token {
 }
 impl fmt::Display for Terminal {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-        write!(f, "{}", self.0)
+        match self {
+            Terminal::Named(name) => write!(f, "{}", name),
+            Terminal::Empty => write!(f, "ε"),
+            Terminal::Eof => write!(f, "$"),
+        }
     }
 }
 
@@ -59,7 +76,11 @@ impl fmt::Display for NoneTerminal {
 
 impl fmt::Display for Production {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-        write!(f, "{}", self.to_string())
+        write!(f, "{} -> ", self.non_terminal)?;
+        for t in &self.tokens {
+            write!(f, "{} ", t)?;
+        }
+        Ok(())
     }
 }
 
@@ -85,23 +106,9 @@ impl Production {
             tokens
         }
     }
-
-    fn to_string(&self) -> String {
-        let mut ret = self.non_terminal.0.clone();
-        ret += " -> ";
-        for t in &self.tokens {
-            let s = match t {
-                Token::T(t) => &t.0,
-                Token::NT(nt) => &nt.0,
-            };
-            ret += s;
-            ret += " ";
-        }
-        ret
-    }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct CFG {
     pub terminals: Vec<Terminal>,
     pub non_terminals: Vec<NoneTerminal>,
@@ -112,18 +119,30 @@ pub struct CFG {
 #[derive(Debug)]
 pub struct NoneLeftRecursionCFG(pub CFG);
 
+impl fmt::Display for CFG {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        for ps in self.productions.values() {
+            for p in ps {
+                writeln!(f, "{}", p)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
 impl CFG {
-    pub fn new(prods: HashMap<NoneTerminal, Vec<Production>>) -> Self {
+    pub fn new(start: NoneTerminal, prods: HashMap<NoneTerminal, Vec<Production>>) -> Self {
         let mut ts = HashSet::new();
         let mut nts = HashSet::new();
-        for (nt, _) in &prods {
+        for nt in prods.keys() {
             nts.insert(nt.clone());
         }
 
-        for (_, ps) in &prods {
+        for ps in prods.values() {
             for p in ps {
                 for t in &p.tokens {
-                    if let Token::T(t) = t {
+                    if let Token::T(t @ Terminal::Named(_)) = t {
                         ts.insert(t.clone());
                     }
                 }
@@ -133,19 +152,28 @@ impl CFG {
             terminals: ts.into_iter().collect(),
             non_terminals: nts.into_iter().collect(),
             productions: prods,
-            start: NoneTerminal::start(),
+            start,
         }
     }
 
+    /// The production `S' -> S $` over a fresh nonterminal `S'`, which the parsers start from.
+    pub fn augmented(&self) -> Production {
+        let mut start = self.start.fork();
+        while self.productions.contains_key(&start) || self.non_terminals.contains(&start) {
+            start = start.fork();
+        }
+        Production::new(start, vec![Token::NT(self.start.clone()), Token::T(Terminal::Eof)])
+    }
+
     pub fn into_non_left_recursion(self) -> NoneLeftRecursionCFG {
-        let mut prods = HashMap::new();
+        let mut prods: HashMap<_, Vec<Production>> = HashMap::new();
         for current in &self.non_terminals {
             let mut ps = vec![];
             for prod in self.productions.get(current).unwrap() {
                 match &prod.tokens[0] {
                     Token::NT(nt) if self.is_preceed(current, nt) => {
                             let processed = prods.get(nt).unwrap();
-                            let new = replace(prod, &processed);
+                            let new = replace(prod, processed);
                             ps.extend(new);
                     }
                     _ => ps.push(prod.clone())
@@ -157,19 +185,7 @@ impl CFG {
                 prods.insert(rhs[0].non_terminal.clone(), rhs);
             }
         }
-        NoneLeftRecursionCFG(Self::new(prods))
-    }
-
-    pub fn to_string(&self) -> String {
-        let mut ret = String::new();
-        for ps in self.productions.values() {
-            for p in ps {
-                ret += &p.to_string();
-                ret += "\n";
-            }
-            ret += "\n";
-        }
-        ret
+        NoneLeftRecursionCFG(Self::new(self.start.clone(), prods))
     }
 
     fn is_preceed(&self, lhs: &NoneTerminal, rhs: &NoneTerminal) -> bool {
@@ -188,52 +204,102 @@ impl CFG {
 }
 
 impl NoneTerminal {
-    pub fn new<T: Into<String>>(t: T) -> Self {
-        NoneTerminal(t.into())
+    pub fn new<T: AsRef<str>>(t: T) -> Self {
+        NoneTerminal(Symbol::intern(t.as_ref()))
     }
 
     pub fn fork(&self) -> Self {
-        let s = self.0.clone() + "@";
+        let s = self.0.to_string() + "@";
         Self::new(s)
     }
-
-    pub fn start() -> Self {
-        Self::new("Goal")
-    }
 }
 
 impl Terminal {
-    pub fn new<T: Into<String>>(t: T) -> Self {
-        Terminal(t.into())
+    pub fn new<T: AsRef<str>>(t: T) -> Self {
+        Terminal::Named(Symbol::intern(t.as_ref()))
     }
 
-    pub fn empty() -> Self {
-        Self::new("empty@@")
+    /// The name of a terminal of the grammar, `None` for the sentinels.
+    pub fn name(&self) -> Option<&'static str> {
+        match self {
+            Terminal::Named(name) => Some(name.as_str()),
+            _ => None,
+        }
     }
 
     pub fn is_empty(&self) -> bool {
-        self.0 == "empty@@"
+        *self == Terminal::Empty
     }
 
-    pub fn eof() -> Self {
-        Self::new("eof@@")
+    pub fn is_eof(&self) -> bool {
+        *self == Terminal::Eof
     }
+}
 
-    pub fn is_eof(&self) -> bool {
-        self.0 == "eof@@"
+// the sentinels never end up in a tree, should one get there it keeps its display name
+impl<'a> From<&'a Terminal> for crate::parser::Terminal {
+    fn from(t: &'a Terminal) -> Self {
+        match t {
+            Terminal::Named(name) => crate::parser::Terminal::from(*name),
+            t => crate::parser::Terminal::new(t.to_string()),
+        }
     }
 }
 
-pub fn terminal<T: Into<String>>(t: T) -> Terminal {
-    Terminal(t.into())
+impl<'a> From<&'a crate::parser::Terminal> for Terminal {
+    fn from(t: &'a crate::parser::Terminal) -> Self {
+        Terminal::Named(t.symbol())
+    }
 }
 
-pub fn none_terminal<T: Into<String>>(t: T) -> NoneTerminal {
-    NoneTerminal(t.into())
+impl<'a> From<&'a NoneTerminal> for crate::parser::NonTerminal {
+    fn from(nt: &'a NoneTerminal) -> Self {
+        crate::parser::NonTerminal::from(nt.0)
+    }
 }
 
-fn replace(prod: &Production, replicas: &Vec<Production>) -> Vec<Production> {
-    let mut ret = replicas.clone();
+impl<'a> From<&'a CFG> for crate::parser::CFG {
+    fn from(cfg: &'a CFG) -> Self {
+        use crate::parser::{Element, ProdBlock};
+
+        let productions = cfg
+            .non_terminals
+            .iter()
+            .map(|nt| {
+                let prods = cfg.productions.get(nt).into_iter().flatten().map(|p| crate::parser::Production {
+                    left: nt.into(),
+                    right: p
+                        .tokens
+                        .iter()
+                        .map(|t| match t {
+                            Token::T(t) if t.is_empty() => Element::Empty,
+                            Token::T(t) => Element::T(t.into()),
+                            Token::NT(nt) => Element::NT(nt.into()),
+                        })
+                        .collect(),
+                });
+                ProdBlock::new(nt.into(), prods.collect())
+            })
+            .collect();
+        crate::parser::CFG {
+            start: (&cfg.start).into(),
+            non_terminals: cfg.non_terminals.iter().map(|nt| nt.into()).collect(),
+            terminals: cfg.terminals.iter().map(|t| t.into()).collect(),
+            productions,
+        }
+    }
+}
+
+pub fn terminal<T: AsRef<str>>(t: T) -> Terminal {
+    Terminal::new(t)
+}
+
+pub fn none_terminal<T: AsRef<str>>(t: T) -> NoneTerminal {
+    NoneTerminal::new(t)
+}
+
+fn replace(prod: &Production, replicas: &[Production]) -> Vec<Production> {
+    let mut ret = replicas.to_vec();
     for p in &mut ret {
         p.tokens.extend_from_slice(&prod.tokens[1..]);
         p.non_terminal = prod.non_terminal.clone();
@@ -263,7 +329,7 @@ fn eliminate_direct_left_recursion(prods: Vec<Production>) -> (Vec<Production>,
     }
     let e = Production {
         non_terminal: new_nt.clone(),
-        tokens: vec![Token::T(Terminal::empty())],
+        tokens: vec![Token::T(Terminal::Empty)],
     };
     rret.push(e);
 
@@ -273,19 +339,110 @@ fn eliminate_direct_left_recursion(prods: Vec<Production>) -> (Vec<Production>,
     (lret, Some(rret))
 }
 
-pub type First = HashMap<Token, Vec<Terminal>>;
-pub type Follow = HashMap<NoneTerminal, Vec<Terminal>>;
-pub type Predict = HashMap<(NoneTerminal, usize), Vec<Terminal>>;
+/// Dense numbering of the terminals of one grammar, used as bit positions in terminal sets.
+/// The sentinels always come first.
+#[derive(Debug)]
+pub struct TerminalIndex {
+    terminals: Vec<Terminal>,
+    ids: HashMap<Terminal, usize>,
+}
+
+impl TerminalIndex {
+    pub const EMPTY: usize = 0;
+    pub const EOF: usize = 1;
+
+    pub fn new(cfg: &CFG) -> Self {
+        let mut ret = TerminalIndex {
+            terminals: vec![],
+            ids: HashMap::new(),
+        };
+        ret.add(Terminal::Empty);
+        ret.add(Terminal::Eof);
+        for t in &cfg.terminals {
+            ret.add(t.clone());
+        }
+        ret
+    }
+
+    fn add(&mut self, t: Terminal) {
+        if !self.ids.contains_key(&t) {
+            self.ids.insert(t.clone(), self.terminals.len());
+            self.terminals.push(t);
+        }
+    }
+
+    pub fn id(&self, t: &Terminal) -> Option<usize> {
+        self.ids.get(t).cloned()
+    }
+
+    pub fn terminal(&self, id: usize) -> &Terminal {
+        &self.terminals[id]
+    }
+
+    pub fn len(&self) -> usize {
+        self.terminals.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terminals.is_empty()
+    }
+
+    pub fn terminals<'a>(&'a self, set: &'a BitSet) -> impl Iterator<Item = &'a Terminal> + 'a {
+        set.iter().map(move |id| &self.terminals[id])
+    }
+}
+
+#[derive(Debug)]
+pub struct First {
+    index: Rc<TerminalIndex>,
+    sets: HashMap<Token, BitSet>,
+}
+
+#[derive(Debug)]
+pub struct Follow {
+    index: Rc<TerminalIndex>,
+    sets: HashMap<NoneTerminal, BitSet>,
+}
+
+pub type Predict = HashMap<(NoneTerminal, usize), BitSet>;
+
+impl First {
+    pub fn index(&self) -> &TerminalIndex {
+        &self.index
+    }
+
+    pub fn get(&self, token: &Token) -> Option<&BitSet> {
+        self.sets.get(token)
+    }
+
+    pub fn terminals(&self, token: &Token) -> Option<Vec<Terminal>> {
+        self.get(token).map(|set| self.index.terminals(set).cloned().collect())
+    }
+
+    /// FIRST of a sentential form; holds `Terminal::Empty` if every token can derive it.
+    pub fn of_tokens(&self, tokens: &[Token]) -> BitSet {
+        first_of_tokens(&self.sets, tokens)
+    }
+}
+
+impl Follow {
+    pub fn get(&self, nt: &NoneTerminal) -> Option<&BitSet> {
+        self.sets.get(nt)
+    }
+
+    pub fn terminals(&self, nt: &NoneTerminal) -> Option<Vec<Terminal>> {
+        self.get(nt).map(|set| self.index.terminals(set).cloned().collect())
+    }
+}
 
 pub fn predict(cfg: &NoneLeftRecursionCFG, first: &First, follow: &Follow) -> Predict {
     let cfg = &cfg.0;
     let mut predict = HashMap::new();
     for nt in &cfg.non_terminals {
-        for (i, p) in cfg.productions.get(nt).unwrap().into_iter().enumerate() {
-            let mut f = first_of_production(p, first) ;
-            if exist_empty(&f) {
-                let fol = follow.get(nt).unwrap().clone();
-                f.extend(fol);
+        for (i, p) in cfg.productions.get(nt).unwrap().iter().enumerate() {
+            let mut f = first.of_tokens(&p.tokens);
+            if f.contains(TerminalIndex::EMPTY) {
+                f.union_with(follow.get(nt).unwrap());
             }
             predict.insert((nt.clone(), i), f);
         }
@@ -293,134 +450,126 @@ pub fn predict(cfg: &NoneLeftRecursionCFG, first: &First, follow: &Follow) -> Pr
     predict
 }
 
-fn first_of_production(p: &Production, first: &First) -> Vec<Terminal> {
-    let mut ret = vec![];
-    for token in &p.tokens {
-        let mut f = first.get(token).unwrap().clone();
-        if !exist_empty(&f) {
-            ret.extend(f);
-            return ret;
-        }
-        remove_empty(&mut f);
-        ret.extend(f)
-    }
-    ret.push(Terminal::empty());
-    ret
-}
-
-fn first_of_tokens(tokens: &Vec<Token>, first: &First) -> Vec<Terminal> {
-    let mut ret = vec![];
+fn first_of_tokens(sets: &HashMap<Token, BitSet>, tokens: &[Token]) -> BitSet {
+    let mut ret = BitSet::new();
     for token in tokens {
-        let mut f = first.get(token).unwrap().clone();
-        if !exist_empty(&f) {
-            ret.extend(f);
+        let f = &sets[token];
+        ret.union_with(f);
+        if !f.contains(TerminalIndex::EMPTY) {
+            ret.remove(TerminalIndex::EMPTY);
             return ret;
         }
-        remove_empty(&mut f);
-        ret.extend(f)
     }
-    ret.push(Terminal::empty());
+    ret.insert(TerminalIndex::EMPTY);
     ret
 }
 
+/// FIRST sets by worklist: a production is looked at again only when the FIRST set of a
+/// nonterminal on its right-hand side has grown.
 pub fn first(cfg: &CFG) -> First {
-    let mut first = First::new();
-    for nt in cfg.non_terminals.clone() {
-        first.insert(Token::NT(nt.clone()), vec![]);
+    let index = Rc::new(TerminalIndex::new(cfg));
+    let mut sets = HashMap::new();
+    for nt in &cfg.non_terminals {
+        sets.insert(Token::NT(nt.clone()), BitSet::new());
     }
-    for t in cfg.terminals.clone() {
-        first.insert(Token::T(t.clone()), vec![t]);
+    for (id, t) in index.terminals.iter().enumerate() {
+        sets.insert(Token::T(t.clone()), BitSet::single(id));
     }
-    first.insert(Token::T(Terminal::empty()), vec![Terminal::empty()]);
-    first.insert(Token::T(Terminal::eof()), vec![Terminal::eof()]);
-    let mut updated = true;
+
     let productions: Vec<_> = cfg.productions.values().flatten().collect();
-    while updated {
-        updated = false;
-        for &p in &productions {
-            let mut rhs = vec![];
-            let key = Token::NT(p.non_terminal.clone());
-            let tokens = &p.tokens;     
-            let mut fir = first.get(&tokens[0]).unwrap();
-            let mut f = fir.clone();
-            remove_empty(&mut f);
-            rhs.extend(f);
-            let mut idx = 0;
-            while exist_empty(fir) && idx < tokens.len() - 1 {
-                let mut f = first.get(&tokens[idx + 1]).unwrap().clone();
-                remove_empty(&mut f);
-                rhs.extend(f);
-                idx += 1;
-                fir = first.get(&tokens[idx]).unwrap();
+    let mut users: HashMap<&NoneTerminal, Vec<usize>> = HashMap::new();
+    for (i, p) in productions.iter().enumerate() {
+        for token in &p.tokens {
+            if let Token::NT(nt) = token {
+                users.entry(nt).or_default().push(i);
             }
-            if idx == tokens.len() - 1 && exist_empty(first.get(&tokens[idx]).unwrap()) {
-                rhs.push(Terminal::empty())
-            }
-            if append(first.get_mut(&key).unwrap(), rhs) {
-                updated = true
+        }
+    }
+
+    let mut work = Worklist::new(productions.len());
+    while let Some(i) = work.pop() {
+        let p = productions[i];
+        let rhs = first_of_tokens(&sets, &p.tokens);
+        let key = Token::NT(p.non_terminal.clone());
+        if sets.get_mut(&key).unwrap().union_with(&rhs) {
+            for &j in users.get(&p.non_terminal).into_iter().flatten() {
+                work.push(j);
             }
         }
     }
-    first
+    First { index, sets }
 }
 
+/// FOLLOW sets by worklist: the productions of a nonterminal are looked at again only when
+/// its own FOLLOW set has grown. `$` follows the start symbol through the augmented production.
 pub fn follow(cfg: &CFG, first: &First) -> Follow {
-    let mut follow: HashMap<_, _>= cfg.non_terminals.clone().into_iter().map(|nt| (nt, vec![])).collect();
-    follow.insert(cfg.start.clone(), vec![Terminal::eof()]);
-
-    let mut updated = true;
-    let productions: Vec<_> = cfg.productions.values().flatten().collect();
-
-    while updated {
-        updated = false;
-        for p in &productions {
-            let mut trailer = follow.get(&p.non_terminal).unwrap().clone();
-            for token in p.tokens.iter().rev() {
-                match token {
-                    Token::NT(nt) => {
-                        if append(follow.get_mut(nt).unwrap(), trailer.clone()) {
-                            updated = true
-                        }
-                        let mut trai = first.get(token).unwrap().clone();
-                        if exist_empty(&trai) {
-                            remove_empty(&mut trai);
-                            trailer.extend(trai);
-                        } else {
-                            trailer = trai;
-                        }
+    let augmented = cfg.augmented();
+    let mut sets: HashMap<_, _> = cfg.non_terminals.iter().map(|nt| (nt.clone(), BitSet::new())).collect();
+    sets.insert(augmented.non_terminal.clone(), BitSet::new());
+
+    let productions: Vec<_> = cfg.productions.values().flatten().chain(Some(&augmented)).collect();
+    let mut owned: HashMap<&NoneTerminal, Vec<usize>> = HashMap::new();
+    for (i, p) in productions.iter().enumerate() {
+        owned.entry(&p.non_terminal).or_default().push(i);
+    }
+
+    let mut work = Worklist::new(productions.len());
+    while let Some(i) = work.pop() {
+        let p = productions[i];
+        let mut trailer = sets[&p.non_terminal].clone();
+        for token in p.tokens.iter().rev() {
+            if token.is_empty() {
+                continue;
+            }
+            let f = first.get(token).unwrap();
+            if let Token::NT(nt) = token {
+                if sets.get_mut(nt).unwrap().union_with(&trailer) {
+                    for &j in owned.get(nt).into_iter().flatten() {
+                        work.push(j);
                     }
-                    _ => trailer = first.get(token).unwrap().clone(),
+                }
+                if f.contains(TerminalIndex::EMPTY) {
+                    trailer.union_with(f);
+                    trailer.remove(TerminalIndex::EMPTY);
+                    continue;
                 }
             }
+            trailer = f.clone();
         }
     }
-    follow
-}
-
-fn exist_empty(ts: &Vec<Terminal>) -> bool {
-    for t in ts {
-        if t.is_empty() {
-            return true
-        }
+    sets.remove(&augmented.non_terminal);
+    Follow {
+        index: first.index.clone(),
+        sets,
     }
-    false
 }
 
-fn remove_empty(ts: &mut Vec<Terminal>) {
-    ts.remove_item(&Terminal::empty());
+struct Worklist {
+    queue: VecDeque<usize>,
+    queued: Vec<bool>,
 }
 
-fn append(dst: &mut Vec<Terminal>, src: Vec<Terminal>) -> bool {
-    let mut ret = false;  
-    let mut hashed: HashSet<_> = dst.clone().into_iter().collect();
-    for t in src {
-        if hashed.get(&t).is_none() {
-            dst.push(t.clone());
-            hashed.insert(t);
-            ret = true;
+impl Worklist {
+    // starts out holding every item
+    fn new(n: usize) -> Self {
+        Worklist {
+            queue: (0..n).collect(),
+            queued: vec![true; n],
         }
     }
-    ret
+
+    fn push(&mut self, i: usize) {
+        if !self.queued[i] {
+            self.queued[i] = true;
+            self.queue.push_back(i);
+        }
+    }
+
+    fn pop(&mut self) -> Option<usize> {
+        let i = self.queue.pop_front()?;
+        self.queued[i] = false;
+        Some(i)
+    }
 }
 
 #[cfg(test)]
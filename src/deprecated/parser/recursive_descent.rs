@@ -1,21 +1,26 @@
+use crate::parser::stream::{TokenBuffer, TokenStream};
+use crate::parser::token::Kind;
+
 use super::Terminal;
 
-struct Context{
-    tokens: Vec<Terminal>,
-    idx: usize,
-    eof: Terminal,
+struct Context<S> {
+    tokens: S,
 }
 
 pub struct Error;
 
 type Result = std::result::Result<(), Error>;
 
-pub fn parse(tokens: Vec<Terminal>) -> Result {
-    let mut ctx = Context::new(tokens)   ;
+pub fn parse<T: Kind>(tokens: Vec<T>) -> Result {
+    parse_stream(TokenBuffer::new(tokens))
+}
+
+pub fn parse_stream<T: Kind, S: TokenStream<Token = T>>(tokens: S) -> Result {
+    let mut ctx = Context::new(tokens);
     start(&mut ctx)
 }
 
-fn start(ctx: &mut Context) -> Result {
+fn start<T: Kind, S: TokenStream<Token = T>>(ctx: &mut Context<S>) -> Result {
     expr(ctx)?;
     if ctx.current().is_eof() {
         Ok(())
@@ -24,14 +29,14 @@ fn start(ctx: &mut Context) -> Result {
     }
 }
 
-fn expr(ctx: &mut Context) -> Result {
+fn expr<T: Kind, S: TokenStream<Token = T>>(ctx: &mut Context<S>) -> Result {
     term(ctx)?;
     expr__(ctx)
 }
 
-fn expr__(ctx: &mut Context) -> Result {
-    let t = &ctx.current().0;
-    if t == "+" || t == "-" {
+fn expr__<T: Kind, S: TokenStream<Token = T>>(ctx: &mut Context<S>) -> Result {
+    let t = ctx.current().name();
+    if t == Some("+") || t == Some("-") {
         ctx.forward();
         term(ctx)?;
         expr__(ctx)?;
@@ -39,20 +44,20 @@ fn expr__(ctx: &mut Context) -> Result {
     Ok(())
 }
 
-fn term(ctx: &mut Context) -> Result {
+fn term<T: Kind, S: TokenStream<Token = T>>(ctx: &mut Context<S>) -> Result {
     factor(ctx)?;
     term__(ctx)
 }
 
-fn factor(ctx: &mut Context) -> Result {
-    let t = &ctx.current().0;
-    if t == "num" || t == "name" {
+fn factor<T: Kind, S: TokenStream<Token = T>>(ctx: &mut Context<S>) -> Result {
+    let t = ctx.current().name();
+    if t == Some("num") || t == Some("name") {
         ctx.forward();
         Ok(())
-    }  else if t == "(" {
+    }  else if t == Some("(") {
         ctx.forward();
         expr(ctx)?;
-        if ctx.current().0 == ")" {
+        if ctx.current().name() == Some(")") {
             ctx.forward();
             Ok(())
         } else {
@@ -64,9 +69,9 @@ fn factor(ctx: &mut Context) -> Result {
     }
 }
 
-fn term__(ctx: &mut Context) -> Result {
-    let t = &ctx.current().0;
-    if t == "*" || t == "/" {
+fn term__<T: Kind, S: TokenStream<Token = T>>(ctx: &mut Context<S>) -> Result {
+    let t = ctx.current().name();
+    if t == Some("*") || t == Some("/") {
         ctx.forward();
         factor(ctx)?;
         term__(ctx)?;
@@ -74,26 +79,16 @@ fn term__(ctx: &mut Context) -> Result {
     Ok(())
 }
 
-impl Context {
-    fn new(tokens: Vec<Terminal>) -> Self {
-        Context {
-            tokens: tokens,
-            idx: 0,
-            eof: Terminal::eof(),
-        }
+impl<T: Kind, S: TokenStream<Token = T>> Context<S> {
+    fn new(tokens: S) -> Self {
+        Context { tokens }
     }
 
     fn forward(&mut self) {
-        if self.idx != self.tokens.len() {
-            self.idx += 1;
-        }
+        self.tokens.forward()
     }
 
-    fn current(&self) -> &Terminal {
-        if self.idx == self.tokens.len() {
-            &self.eof
-        } else {
-            &self.tokens[self.idx]
-        }
+    fn current(&mut self) -> Terminal {
+        self.tokens.current().map_or(Terminal::Eof, |t| t.kind().into())
     }
 }
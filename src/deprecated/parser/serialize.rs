@@ -0,0 +1,597 @@
+//! Saving a grammar together with its FIRST/FOLLOW sets and parse tables, as JSON or in a
+//! compact binary form, so that large grammars don't have to be analysed again on every run.
+//!
+//! Both forms carry a hash of the grammar and a checksum of the tables derived from it. Loading
+//! recomputes both, and `Tables::check` compares the grammar hash with the grammar the caller is
+//! about to parse with.
+//!
+//! Symbols are written by name, except for the sentinels `Terminal::Empty` and `Terminal::Eof`,
+//! which are written as the numbers 0 and 1.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::rc::Rc;
+
+use super::*;
+use super::json::{self, Value};
+use super::lr1::{build_action_and_goto_table, Action, ActionTable, GotoTable, State};
+
+const FORMAT: &str = "eac-tables";
+const VERSION: u64 = 3;
+
+// the fields covered by the checksum, in the order they are hashed
+const TABLE_FIELDS: [&str; 4] = ["first", "follow", "ll1", "lr1"];
+
+#[derive(Debug)]
+pub enum Error {
+    /// Not JSON, or not the binary encoding.
+    Syntax(String),
+    /// Well formed, but not a table file this version understands.
+    Format(String),
+    /// The tables were built for a grammar hashing to `found`, but are used with, or stored
+    /// next to, a grammar hashing to `expected`.
+    HashMismatch { expected: u64, found: u64 },
+    /// The stored tables don't match the checksum written with them.
+    ChecksumMismatch { expected: u64, found: u64 },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Error::Syntax(msg) => write!(f, "malformed table file: {}", msg),
+            Error::Format(msg) => write!(f, "invalid table file: {}", msg),
+            Error::HashMismatch { expected, found } => write!(
+                f,
+                "tables were built for grammar {:016x}, not {:016x}",
+                found, expected
+            ),
+            Error::ChecksumMismatch { expected, found } => write!(
+                f,
+                "tables have checksum {:016x}, expected {:016x}",
+                found, expected
+            ),
+        }
+    }
+}
+
+/// A grammar and everything derived from it.
+#[derive(Debug)]
+pub struct Tables {
+    pub cfg: CFG,
+    pub first: First,
+    pub follow: Follow,
+    pub ll1: Option<Predict>,
+    pub lr1: Option<(ActionTable, GotoTable)>,
+}
+
+impl Tables {
+    pub fn new(cfg: CFG) -> Self {
+        let first = first(&cfg);
+        let follow = follow(&cfg, &first);
+        Tables {
+            cfg,
+            first,
+            follow,
+            ll1: None,
+            lr1: None,
+        }
+    }
+
+    /// Adds the LL(1) predict sets, the grammar must not be left recursive.
+    pub fn with_ll1(mut self) -> Self {
+        let cfg = NoneLeftRecursionCFG(self.cfg);
+        self.ll1 = Some(predict(&cfg, &self.first, &self.follow));
+        self.cfg = cfg.0;
+        self
+    }
+
+    pub fn with_lr1(mut self) -> Self {
+        self.lr1 = Some(build_action_and_goto_table(&self.cfg));
+        self
+    }
+
+    /// Fails unless these tables were built from `cfg`.
+    pub fn check(&self, cfg: &CFG) -> Result<(), Error> {
+        let (expected, found) = (grammar_hash(cfg), grammar_hash(&self.cfg));
+        if expected != found {
+            return Err(Error::HashMismatch { expected, found });
+        }
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> String {
+        json::to_string(&encode(self))
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, Error> {
+        decode(&json::from_str(text).map_err(Error::Syntax)?)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        json::to_bytes(&encode(self))
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        decode(&json::from_bytes(bytes).map_err(Error::Syntax)?)
+    }
+}
+
+/// FNV-1a over the grammar by name. The order terminals and nonterminals are listed in doesn't
+/// matter, the order of the alternatives of a nonterminal does.
+pub fn grammar_hash(cfg: &CFG) -> u64 {
+    let mut hash = Fnv::new();
+    hash.write(cfg.start.0.as_str());
+    let mut ts: Vec<_> = cfg.terminals.iter().filter_map(|t| t.name()).collect();
+    ts.sort();
+    ts.dedup();
+    for t in ts {
+        hash.write(t);
+    }
+    for nt in sorted_non_terminals(cfg) {
+        hash.write(nt.0.as_str());
+        for p in cfg.productions.get(nt).into_iter().flatten() {
+            hash.write("->");
+            for token in &p.tokens {
+                match token {
+                    Token::T(Terminal::Named(name)) => hash.write(name.as_str()),
+                    Token::T(t) => hash.sentinel(t),
+                    Token::NT(nt) => {
+                        hash.write("<>");
+                        hash.write(nt.0.as_str());
+                    }
+                }
+            }
+        }
+    }
+    hash.0
+}
+
+// FNV-1a over the encoded FIRST/FOLLOW sets and parse tables
+fn tables_checksum(value: &Value) -> u64 {
+    let mut hash = Fnv::new();
+    for name in TABLE_FIELDS {
+        if let Some(field) = value.get(name) {
+            hash.write(name);
+            hash.write(&json::to_string(field));
+        }
+    }
+    hash.0
+}
+
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Self {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+
+    // every string is terminated by 0xff, which utf-8 never contains
+    fn write(&mut self, s: &str) {
+        for &b in s.as_bytes().iter().chain(&[0xff]) {
+            self.0 ^= u64::from(b);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    // 0xfe can't start a name either
+    fn sentinel(&mut self, t: &Terminal) {
+        let id = if t.is_empty() { TerminalIndex::EMPTY } else { TerminalIndex::EOF };
+        for &b in &[0xfe, id as u8] {
+            self.0 ^= u64::from(b);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+fn sorted_non_terminals(cfg: &CFG) -> Vec<&NoneTerminal> {
+    let mut nts: Vec<_> = cfg.non_terminals.iter().collect();
+    nts.sort_by_key(|nt| nt.0.as_str());
+    nts
+}
+
+// productions in the order they are written out, which is also how reductions refer to them
+fn numbered_productions(cfg: &CFG) -> Vec<&Production> {
+    sorted_non_terminals(cfg)
+        .into_iter()
+        .flat_map(|nt| cfg.productions.get(nt).into_iter().flatten())
+        .collect()
+}
+
+fn stripped(tokens: &[Token]) -> Vec<Token> {
+    tokens.iter().filter(|t| !t.is_empty()).cloned().collect()
+}
+
+// sentinels first, then by name
+fn sort_key(t: &Terminal) -> (usize, &'static str) {
+    match t {
+        Terminal::Named(name) => (2, name.as_str()),
+        Terminal::Empty => (TerminalIndex::EMPTY, ""),
+        Terminal::Eof => (TerminalIndex::EOF, ""),
+    }
+}
+
+fn terminal_value(t: &Terminal) -> Value {
+    match t {
+        Terminal::Named(name) => Value::str(name.as_str()),
+        t => Value::Int(sort_key(t).0 as u64),
+    }
+}
+
+///////////////////////// encoding /////////////////////////////////////////////////////////////////
+
+// Everything is written sorted by name and LR states are renumbered canonically, so the same
+// grammar always gives the same file.
+fn encode(tables: &Tables) -> Value {
+    let cfg = &tables.cfg;
+    let nts = sorted_non_terminals(cfg);
+    let prods = numbered_productions(cfg);
+    let mut terminals: Vec<_> = cfg.terminals.iter().filter_map(|t| t.name()).collect();
+    terminals.sort();
+    terminals.dedup();
+
+    let grammar = Value::Object(vec![
+        ("start".to_string(), Value::str(cfg.start.0.as_str())),
+        ("terminals".to_string(), Value::Array(terminals.into_iter().map(Value::str).collect())),
+        (
+            "productions".to_string(),
+            Value::Array(
+                prods
+                    .iter()
+                    .map(|p| {
+                        let right = p
+                            .tokens
+                            .iter()
+                            .map(|t| match t {
+                                Token::T(t) => terminal_value(t),
+                                Token::NT(nt) => Value::str(nt.0.as_str()),
+                            })
+                            .collect();
+                        Value::Array(vec![Value::str(p.non_terminal.0.as_str()), Value::Array(right)])
+                    })
+                    .collect(),
+            ),
+        ),
+    ]);
+    let index = tables.first.index();
+    let first = nts
+        .iter()
+        .map(|nt| (nt.to_string(), terminal_set(index, tables.first.get(&Token::NT((*nt).clone())))))
+        .collect();
+    let follow = nts
+        .iter()
+        .map(|nt| (nt.to_string(), terminal_set(index, tables.follow.get(nt))))
+        .collect();
+
+    let mut fields = vec![
+        ("format".to_string(), Value::str(FORMAT)),
+        ("version".to_string(), Value::Int(VERSION)),
+        ("hash".to_string(), Value::Str(format!("{:016x}", grammar_hash(cfg)))),
+        ("grammar".to_string(), grammar),
+        ("first".to_string(), Value::Object(first)),
+        ("follow".to_string(), Value::Object(follow)),
+    ];
+    if let Some(predict) = &tables.ll1 {
+        let mut rows: Vec<_> = predict.iter().collect();
+        rows.sort_by_key(|((nt, i), _)| (nt.0.as_str(), *i));
+        let rows = rows
+            .into_iter()
+            .map(|((nt, i), set)| {
+                Value::Array(vec![Value::str(nt.0.as_str()), Value::Int(*i as u64), terminal_set(index, Some(set))])
+            })
+            .collect();
+        fields.push(("ll1".to_string(), Value::Array(rows)));
+    }
+    if let Some((action, goto)) = &tables.lr1 {
+        fields.push(("lr1".to_string(), encode_lr1(action, goto, &prods)));
+    }
+    let checksum = tables_checksum(&Value::Object(fields.clone()));
+    fields.insert(3, ("checksum".to_string(), Value::Str(format!("{:016x}", checksum))));
+    Value::Object(fields)
+}
+
+fn terminal_set(index: &TerminalIndex, set: Option<&BitSet>) -> Value {
+    let mut ts: Vec<_> = set.into_iter().flat_map(|s| index.terminals(s)).collect();
+    ts.sort_by_key(|t| sort_key(t));
+    Value::Array(ts.into_iter().map(terminal_value).collect())
+}
+
+fn encode_lr1(action: &ActionTable, goto: &GotoTable, prods: &[&Production]) -> Value {
+    let numbers: HashMap<_, _> = prods
+        .iter()
+        .enumerate()
+        .rev()
+        .map(|(i, p)| ((&p.non_terminal, stripped(&p.tokens)), i))
+        .collect();
+    let states = canonical_states(action, goto);
+
+    let mut actions: Vec<_> = action.iter().map(|((s, t), a)| (states[s], t, a)).collect();
+    actions.sort_by_key(|&(s, t, _)| (s, sort_key(t)));
+    let actions = actions
+        .into_iter()
+        .map(|(s, t, a)| {
+            let mut row = vec![Value::Int(s as u64), terminal_value(t)];
+            match a {
+                Action::Shift(to) => {
+                    row.push(Value::str("shift"));
+                    row.push(Value::Int(states[to] as u64));
+                }
+                Action::Reduce(p) => {
+                    row.push(Value::str("reduce"));
+                    row.push(Value::Int(numbers[&(&p.non_terminal, p.tokens.clone())] as u64));
+                }
+                Action::Accept => row.push(Value::str("accept")),
+            }
+            Value::Array(row)
+        })
+        .collect();
+
+    let mut gotos: Vec<_> = goto.iter().map(|((s, nt), to)| (states[s], nt.0.as_str(), states[to])).collect();
+    gotos.sort();
+    let gotos = gotos
+        .into_iter()
+        .map(|(s, nt, to)| Value::Array(vec![Value::Int(s as u64), Value::str(nt), Value::Int(to as u64)]))
+        .collect();
+
+    Value::Object(vec![
+        ("states".to_string(), Value::Int(states.len() as u64)),
+        ("action".to_string(), Value::Array(actions)),
+        ("goto".to_string(), Value::Array(gotos)),
+    ])
+}
+
+// Outgoing transitions of a state: whether the symbol is a nonterminal, its sort key, and the
+// target state.
+type Edges<'a> = HashMap<State, Vec<(bool, (usize, &'a str), State)>>;
+
+// Numbers states in the order a breadth first walk from state 0 reaches them, taking the
+// transitions of a state in order of symbol name.
+fn canonical_states(action: &ActionTable, goto: &GotoTable) -> HashMap<State, State> {
+    let mut edges: Edges = HashMap::new();
+    for ((from, t), a) in action {
+        if let Action::Shift(to) = a {
+            edges.entry(*from).or_default().push((false, sort_key(t), *to));
+        }
+    }
+    for ((from, nt), to) in goto {
+        edges.entry(*from).or_default().push((true, (0, nt.0.as_str()), *to));
+    }
+
+    let mut order = HashMap::new();
+    order.insert(0, 0);
+    let mut queue = VecDeque::new();
+    queue.push_back(0);
+    while let Some(s) = queue.pop_front() {
+        let mut out = edges.remove(&s).unwrap_or_default();
+        out.sort();
+        for (_, _, to) in out {
+            if !order.contains_key(&to) {
+                order.insert(to, order.len());
+                queue.push_back(to);
+            }
+        }
+    }
+    order
+}
+
+///////////////////////// decoding /////////////////////////////////////////////////////////////////
+
+fn decode(value: &Value) -> Result<Tables, Error> {
+    match field(value, "format")?.as_str() {
+        Some(FORMAT) => {}
+        _ => return Err(format_error("not a grammar table file")),
+    }
+    let version = int(field(value, "version")?, "version")?;
+    if version != VERSION {
+        return Err(format_error(format!("unsupported version {}", version)));
+    }
+
+    let cfg = decode_grammar(field(value, "grammar")?)?;
+    let stored = hex(field(value, "hash")?, "hash")?;
+    let expected = grammar_hash(&cfg);
+    if stored != expected {
+        return Err(Error::HashMismatch { expected, found: stored });
+    }
+    let stored = hex(field(value, "checksum")?, "checksum")?;
+    let found = tables_checksum(value);
+    if stored != found {
+        return Err(Error::ChecksumMismatch { expected: stored, found });
+    }
+
+    let index = Rc::new(TerminalIndex::new(&cfg));
+    let mut first_sets: HashMap<_, _> = index
+        .terminals
+        .iter()
+        .enumerate()
+        .map(|(id, t)| (Token::T(t.clone()), BitSet::single(id)))
+        .collect();
+    let mut follow_sets = HashMap::new();
+    let (first_value, follow_value) = (field(value, "first")?, field(value, "follow")?);
+    for nt in &cfg.non_terminals {
+        let name = nt.0.as_str();
+        let set = decode_set(&index, field(first_value, name)?, "first")?;
+        first_sets.insert(Token::NT(nt.clone()), set);
+        follow_sets.insert(nt.clone(), decode_set(&index, field(follow_value, name)?, "follow")?);
+    }
+    let first = First {
+        index: index.clone(),
+        sets: first_sets,
+    };
+    let follow = Follow { index, sets: follow_sets };
+
+    let ll1 = match value.get("ll1") {
+        Some(rows) => Some(decode_ll1(&cfg, first.index(), rows)?),
+        None => None,
+    };
+    let lr1 = match value.get("lr1") {
+        Some(tables) => Some(decode_lr1(&cfg, first.index(), tables)?),
+        None => None,
+    };
+    Ok(Tables {
+        cfg,
+        first,
+        follow,
+        ll1,
+        lr1,
+    })
+}
+
+fn decode_grammar(value: &Value) -> Result<CFG, Error> {
+    let start = NoneTerminal::new(string(field(value, "start")?, "start")?);
+    let rows = array(field(value, "productions")?, "productions")?;
+    let mut non_terminals: Vec<NoneTerminal> = vec![];
+    for row in rows {
+        let left = NoneTerminal::new(string(array(row, "production")?.first().unwrap_or(&Value::Null), "production")?);
+        if !non_terminals.contains(&left) {
+            non_terminals.push(left);
+        }
+    }
+    let is_nt = |name: &str| non_terminals.iter().any(|nt| nt.0.as_str() == name);
+
+    let mut productions: HashMap<NoneTerminal, Vec<Production>> = HashMap::new();
+    for row in rows {
+        let row = array(row, "production")?;
+        let (left, right) = match row {
+            [left, right] => (NoneTerminal::new(string(left, "production")?), array(right, "production")?),
+            _ => return Err(format_error("a production is a left side and a list of names")),
+        };
+        let tokens = right
+            .iter()
+            .map(|v| {
+                Ok(match v.as_str() {
+                    Some(name) if is_nt(name) => Token::NT(NoneTerminal::new(name)),
+                    _ => Token::T(decode_terminal(v, "production")?),
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        if tokens.is_empty() {
+            return Err(format_error(format!("production of `{}` has no right side", left)));
+        }
+        productions.entry(left.clone()).or_default().push(Production::new(left, tokens));
+    }
+    let terminals = array(field(value, "terminals")?, "terminals")?
+        .iter()
+        .map(|t| string(t, "terminals").map(Terminal::new))
+        .collect::<Result<_, _>>()?;
+    if !non_terminals.contains(&start) {
+        return Err(format_error(format!("start symbol `{}` has no productions", start)));
+    }
+    Ok(CFG {
+        terminals,
+        non_terminals,
+        productions,
+        start,
+    })
+}
+
+fn decode_terminal(value: &Value, what: &str) -> Result<Terminal, Error> {
+    match value {
+        Value::Str(name) => Ok(Terminal::new(name)),
+        Value::Int(0) => Ok(Terminal::Empty),
+        Value::Int(1) => Ok(Terminal::Eof),
+        _ => Err(format_error(format!("expected a terminal in {}", what))),
+    }
+}
+
+fn decode_set(index: &TerminalIndex, value: &Value, what: &str) -> Result<BitSet, Error> {
+    let mut ret = BitSet::new();
+    for t in array(value, what)? {
+        ret.insert(terminal_id(index, &decode_terminal(t, what)?, what)?);
+    }
+    Ok(ret)
+}
+
+fn terminal_id(index: &TerminalIndex, t: &Terminal, what: &str) -> Result<usize, Error> {
+    index
+        .id(t)
+        .ok_or_else(|| format_error(format!("unknown terminal `{}` in {}", t, what)))
+}
+
+fn decode_ll1(cfg: &CFG, index: &TerminalIndex, rows: &Value) -> Result<Predict, Error> {
+    let mut predict = Predict::new();
+    for row in array(rows, "ll1")? {
+        match array(row, "ll1")? {
+            [nt, i, set] => {
+                let nt = NoneTerminal::new(string(nt, "ll1")?);
+                let i = int(i, "ll1")? as usize;
+                if cfg.productions.get(&nt).map_or(0, |ps| ps.len()) <= i {
+                    return Err(format_error(format!("`{}` has no alternative {}", nt, i)));
+                }
+                predict.insert((nt, i), decode_set(index, set, "ll1")?);
+            }
+            _ => return Err(format_error("an ll1 row is a nonterminal, an alternative and a set")),
+        }
+    }
+    Ok(predict)
+}
+
+fn decode_lr1(cfg: &CFG, index: &TerminalIndex, value: &Value) -> Result<(ActionTable, GotoTable), Error> {
+    let prods = numbered_productions(cfg);
+    let states = int(field(value, "states")?, "states")? as usize;
+    let state = |v: &Value| match int(v, "lr1") {
+        Ok(s) if (s as usize) < states => Ok(s as State),
+        Ok(s) => Err(format_error(format!("state {} out of range", s))),
+        Err(e) => Err(e),
+    };
+
+    let mut action = ActionTable::new();
+    for row in array(field(value, "action")?, "action")? {
+        let row = array(row, "action")?;
+        if row.len() < 3 {
+            return Err(format_error("an action row is a state, a terminal and an action"));
+        }
+        let from = state(&row[0])?;
+        let t = decode_terminal(&row[1], "action")?;
+        terminal_id(index, &t, "action")?;
+        let act = match (string(&row[2], "action")?, row.get(3)) {
+            ("shift", Some(to)) => Action::Shift(state(to)?),
+            ("reduce", Some(p)) => {
+                let p = int(p, "action")? as usize;
+                let p = prods.get(p).ok_or_else(|| format_error(format!("production {} out of range", p)))?;
+                Action::Reduce(Production::new(p.non_terminal.clone(), stripped(&p.tokens)))
+            }
+            ("accept", None) => Action::Accept,
+            (other, _) => return Err(format_error(format!("bad action `{}`", other))),
+        };
+        action.insert((from, t), act);
+    }
+
+    let mut goto = GotoTable::new();
+    for row in array(field(value, "goto")?, "goto")? {
+        match array(row, "goto")? {
+            [from, nt, to] => {
+                let nt = NoneTerminal::new(string(nt, "goto")?);
+                if !cfg.non_terminals.contains(&nt) {
+                    return Err(format_error(format!("unknown nonterminal `{}` in goto", nt)));
+                }
+                goto.insert((state(from)?, nt), state(to)?);
+            }
+            _ => return Err(format_error("a goto row is a state, a nonterminal and a state")),
+        }
+    }
+    Ok((action, goto))
+}
+
+fn format_error<S: Into<String>>(msg: S) -> Error {
+    Error::Format(msg.into())
+}
+
+fn field<'a>(value: &'a Value, key: &str) -> Result<&'a Value, Error> {
+    value.get(key).ok_or_else(|| format_error(format!("missing `{}`", key)))
+}
+
+fn string<'a>(value: &'a Value, what: &str) -> Result<&'a str, Error> {
+    value.as_str().ok_or_else(|| format_error(format!("expected a string in {}", what)))
+}
+
+fn int(value: &Value, what: &str) -> Result<u64, Error> {
+    value.as_int().ok_or_else(|| format_error(format!("expected a number in {}", what)))
+}
+
+fn hex(value: &Value, what: &str) -> Result<u64, Error> {
+    let s = string(value, what)?;
+    u64::from_str_radix(s, 16).map_err(|_| format_error(format!("bad {} `{}`", what, s)))
+}
+
+fn array<'a>(value: &'a Value, what: &str) -> Result<&'a [Value], Error> {
+    value.as_array().ok_or_else(|| format_error(format!("expected a list in {}", what)))
+}
@@ -27,12 +27,12 @@ lazy_static! {
         ("Expr", vec!["Term", "Expr@"]),
         ("Expr@", vec!["+", "Term", "Expr@"]),
         ("Expr@", vec!["-", "Term", "Expr@"]),
-        ("Expr@", vec!["empty@@"]),
+        ("Expr@", vec!["ε"]),
 
         ("Term", vec!["Factor", "Term@"]),
         ("Term@", vec!["*", "Factor", "Term@"]),
         ("Term@", vec!["/", "Factor", "Term@"]),
-        ("Term@", vec!["empty@@"]),
+        ("Term@", vec!["ε"]),
 
         ("Factor", vec!["(", "Expr", ")"]),
         ("Factor", vec!["num"]),
@@ -42,23 +42,23 @@ lazy_static! {
     static ref FIRST: Vec<(&'static str, Vec<&'static str>)> = vec! {
         ("Goal", vec!["(", "name", "num"]),
         ("Expr", vec!["(", "name", "num"]),
-        ("Expr@", vec!["+", "-", "empty@@"]),
+        ("Expr@", vec!["+", "-", "ε"]),
         ("Term", vec!["(", "name", "num"]),
-        ("Term@", vec!["*", "/", "empty@@"]),
+        ("Term@", vec!["*", "/", "ε"]),
         ("Factor", vec!["(", "name", "num"]),
     };
 
     static ref FOLLOW: Vec<(&'static str, Vec<&'static str>)> = vec! {
-        ("Goal", vec!["eof@@"]),
-        ("Expr", vec!["eof@@", ")"]),
-        ("Expr@", vec!["eof@@", ")"]),
-        ("Term", vec!["eof@@", "+", "-", ")"]),
-        ("Term@", vec!["eof@@", "+", "-", ")"]),
-        ("Factor", vec!["eof@@", "+", "-", "*", "/", ")"]),
+        ("Goal", vec!["$"]),
+        ("Expr", vec!["$", ")"]),
+        ("Expr@", vec!["$", ")"]),
+        ("Term", vec!["$", "+", "-", ")"]),
+        ("Term@", vec!["$", "+", "-", ")"]),
+        ("Factor", vec!["$", "+", "-", "*", "/", ")"]),
     };
 }
 
-fn gen_cfg(grammer: &Vec<(&'static str, Vec<&'static str>)>) -> CFG {
+fn gen_cfg(grammer: &[(&str, Vec<&str>)]) -> CFG {
     let mut nt_str = HashSet::new();
     let mut nts = Vec::new();
     let mut ts = HashSet::new();
@@ -71,12 +71,14 @@ fn gen_cfg(grammer: &Vec<(&'static str, Vec<&'static str>)>) -> CFG {
     for &(nt, ref production) in grammer {
         let entry = ps.entry(NoneTerminal::new(nt)).or_insert_with(Vec::new);
         let tokens = production.clone().into_iter().map(|s|{
-            if nt_str.get(s).is_some() {
+            if nt_str.contains(s) {
                 Token::NT(NoneTerminal::new(s))
             } else {
-                let t = Terminal::new(s);
-                ts.insert(t.clone());
-                Token::T(t.clone())
+                let t = sym(s);
+                if t.name().is_some() {
+                    ts.insert(t.clone());
+                }
+                Token::T(t)
             }
         }).collect();
         entry.push(Production::new(NoneTerminal::new(nt), tokens));
@@ -85,7 +87,16 @@ fn gen_cfg(grammer: &Vec<(&'static str, Vec<&'static str>)>) -> CFG {
         terminals: ts.into_iter().collect(),
         non_terminals: nts,
         productions: ps,
-        start: NoneTerminal::start(),
+        start: NoneTerminal::new("Goal"),
+    }
+}
+
+// `ε` and `$` stand for the sentinels
+fn sym(s: &str) -> Terminal {
+    match s {
+        "ε" => Terminal::Empty,
+        "$" => Terminal::Eof,
+        s => Terminal::new(s),
     }
 }
 
@@ -113,14 +124,14 @@ fn test_eleminate_left_recursion() {
 #[test]
 fn test_first() {
     let cfg = gen_cfg(&GRAMMER).into_non_left_recursion();
-    let mut first: HashMap<_, _>= first(&cfg.0).into_iter().filter(|(ref t, _)|t.is_non_terminal()).collect();
-    assert_eq!(first.len(), FIRST.len());
+    let first = first(&cfg.0);
+    assert_eq!(cfg.0.non_terminals.len(), FIRST.len());
     for (nt, expect) in FIRST.clone() {
-        let f = first.get_mut(&Token::NT(NoneTerminal::new(nt))).unwrap();
+        let mut f = first.terminals(&Token::NT(NoneTerminal::new(nt))).unwrap();
         f.sort();
-        let mut expect: Vec<_> = expect.into_iter().map(|s| Terminal::new(s)).collect();
+        let mut expect: Vec<_> = expect.into_iter().map(sym).collect();
         expect.sort();
-        assert_eq!(f, &mut expect);
+        assert_eq!(f, expect);
     }
 }
 
@@ -128,12 +139,367 @@ fn test_first() {
 fn test_follow() {
     let cfg = gen_cfg(&GRAMMER).into_non_left_recursion();
     let first = first(&cfg.0);
-    let mut follow = follow(&cfg.0, &first);
+    let follow = follow(&cfg.0, &first);
     for (nt, expect) in FOLLOW.clone() {
-        let f = follow.get_mut(&NoneTerminal::new(nt)).unwrap();
+        let mut f = follow.terminals(&NoneTerminal::new(nt)).unwrap();
         f.sort();
-        let mut expect: Vec<_> = expect.into_iter().map(|s| Terminal::new(s)).collect();
+        let mut expect: Vec<_> = expect.into_iter().map(sym).collect();
         expect.sort();
-        assert_eq!(f, &mut expect);
+        assert_eq!(f, expect);
+    }
+}
+
+#[test]
+fn test_bitset() {
+    use super::bitset::BitSet;
+
+    let mut a = BitSet::new();
+    assert!(a.is_empty());
+    assert!(a.insert(3));
+    assert!(!a.insert(3));
+    assert!(a.insert(130));
+    assert_eq!(a.iter().collect::<Vec<_>>(), vec![3, 130]);
+    assert_eq!(a.len(), 2);
+
+    let mut b = BitSet::single(3);
+    assert!(!b.union_with(&BitSet::single(3)));
+    assert!(b.union_with(&a));
+    assert_eq!(a, b);
+    assert!(b.remove(130));
+    assert!(!b.remove(130));
+    assert_eq!(b, BitSet::single(3));
+    assert!(b.contains(3) && !b.contains(130) && !b.contains(1000));
+}
+
+fn terminals(ts: &[&str]) -> Vec<Terminal> {
+    ts.iter().map(|&s| sym(s)).collect()
+}
+
+// input for the drivers, which take tokens of the generic parser
+fn words(ts: &[&str]) -> Vec<crate::parser::Terminal> {
+    ts.iter().map(|&s| crate::parser::Terminal::new(s)).collect()
+}
+
+#[test]
+fn test_ll1_trace() {
+    use super::ll1::{parse_with_trace, Move};
+
+    let cfg = gen_cfg(&GRAMMER).into_non_left_recursion();
+    let mut steps = vec![];
+    assert!(parse_with_trace(&words(&["num"]), &cfg, |s| steps.push(s)).is_ok());
+
+    let actions: Vec<_> = steps.iter().map(|s| s.action.to_string()).collect();
+    assert_eq!(actions, vec![
+        "expand Goal -> Expr",
+        "expand Expr -> Term Expr@",
+        "expand Term -> Factor Term@",
+        "expand Factor -> num",
+        "match num",
+        "expand Term@ -> ε",
+        "expand Expr@ -> ε",
+        "accept",
+    ]);
+    let last = steps.last().unwrap();
+    assert_eq!(last.stack, vec![Token::T(Terminal::Eof)]);
+    assert_eq!(last.input, vec![Terminal::Eof]);
+    assert_eq!(steps[4].input, terminals(&["num", "$"]));
+
+    let table = super::trace::table(&steps);
+    assert_eq!(table.lines().count(), steps.len() + 2);
+    assert!(table.starts_with("Stack"));
+
+    let mut steps = vec![];
+    assert!(parse_with_trace(&words(&["num", "num"]), &cfg, |s| steps.push(s)).is_err());
+    match steps.last().unwrap().action {
+        Move::Error => {}
+        ref m => panic!("unexpected last move: {}", m),
+    }
+}
+
+#[test]
+fn test_lr1_trace() {
+    use super::lr1::{parse_with_trace, Move, Step};
+
+    let cfg = gen_cfg(&GRAMMER);
+    let mut steps = vec![];
+    assert!(parse_with_trace(&words(&["num", "+", "name"]), &cfg, |s| steps.push(s)).is_ok());
+
+    // state numbers depend on construction order, so only compare the symbols
+    let actions: Vec<_> = steps.iter().map(|s| match &s.action {
+        Move::Shift(_) => "shift".to_string(),
+        m => m.to_string(),
+    }).collect();
+    assert_eq!(actions, vec![
+        "shift",
+        "reduce Factor -> num",
+        "reduce Term -> Factor",
+        "reduce Expr -> Term",
+        "shift",
+        "shift",
+        "reduce Factor -> name",
+        "reduce Term -> Factor",
+        "reduce Expr -> Expr + Term",
+        "reduce Goal -> Expr",
+        "accept",
+    ]);
+    let symbols: Vec<_> = steps[8].stack.iter().filter_map(|s| match s {
+        Step::Token(t) => Some(t.to_string()),
+        Step::State(_) => None,
+    }).collect();
+    assert_eq!(symbols, vec!["Expr", "+", "Term"]);
+    assert_eq!(steps[8].input, vec![Terminal::Eof]);
+
+    let mut steps = vec![];
+    assert!(parse_with_trace(&words(&["num", "+"]), &cfg, |s| steps.push(s)).is_err());
+    match steps.last().unwrap().action {
+        Move::Error => {}
+        ref m => panic!("unexpected last move: {}", m),
+    }
+}
+
+#[test]
+fn test_parse_stream() {
+    use crate::parser::stream::TokenBuffer;
+
+    // the drivers stop pulling tokens as soon as they fail
+    let mut pulled = 0;
+    let input = ["num", "num"].iter().cycle().take(1000).map(|&s| crate::parser::Terminal::new(s));
+    let cfg = gen_cfg(&GRAMMER).into_non_left_recursion();
+    let tokens = TokenBuffer::new(input.inspect(|_| pulled += 1));
+    assert!(super::ll1::parse_stream(tokens, &cfg).is_err());
+    assert_eq!(pulled, 2);
+
+    let input = words(&["(", "num", "+", "name", ")", "/", "num"]);
+    let cfg = gen_cfg(&GRAMMER);
+    assert!(super::lr1::parse_stream(TokenBuffer::new(input.clone()), &cfg).is_ok());
+    assert!(super::recursive_descent::parse_stream(TokenBuffer::new(input)).is_ok());
+    assert!(super::recursive_descent::parse(words(&["num", ")"])).is_err());
+}
+
+#[test]
+fn test_parse_tree_tokens() {
+    use crate::parser::stream::TokenBuffer;
+    use crate::parser::token::{Span, Token};
+
+    // `num` 3 and `num` 42 come out of both drivers as they went in
+    let token = |kind: &str, lexeme: &str, start: usize| {
+        let span = Span { start, end: start + lexeme.len(), line: 1, column: start + 1 };
+        Token::new(crate::parser::Terminal::new(kind), lexeme, span)
+    };
+    let input = vec![token("num", "3", 0), token("+", "+", 2), token("num", "42", 4)];
+    let cfg = gen_cfg(&GRAMMER);
+    let tree = super::lr1::parse_tree(&input, &cfg).ok().unwrap();
+    assert_eq!(tree.leaves(), input.iter().collect::<Vec<_>>());
+    let tree = super::ll1::parse_tree(&input, &cfg.clone().into_non_left_recursion()).ok().unwrap();
+    assert_eq!(tree.leaves(), input.iter().collect::<Vec<_>>());
+
+    // the recognizers and the incremental parser take the same tokens
+    let ll1_cfg = cfg.clone().into_non_left_recursion();
+    assert!(super::lr1::parse(&input, &cfg).is_ok());
+    assert!(super::ll1::parse(&input, &ll1_cfg).is_ok());
+    assert!(super::ll1::parse_stream(TokenBuffer::new(input.clone()), &ll1_cfg).is_ok());
+    assert!(super::recursive_descent::parse(input.clone()).is_ok());
+    let parser = super::incremental::IncrementalParser::new(&cfg);
+    let old = parser.parse(&input).ok().unwrap();
+    let edit = super::incremental::Edit { range: 2..3, insert: vec![token("name", "x", 4)] };
+    let (new, _) = parser.reparse(&old, &edit).ok().unwrap();
+    assert_eq!(new.tree().leaves(), vec![&input[0], &input[1], &edit.insert[0]]);
+
+    let input = vec![token("num", "3", 0), token("num", "42", 2), token("+", "+", 5)];
+    let err = super::lr1::parse_tree(&input, &cfg).err().unwrap();
+    assert_eq!((err.position, err.found.as_ref()), (1, Some(&input[1])));
+    assert_eq!(err.to_string(), "at 1:3: unexpected num `42`");
+    let input = [input[0].clone(), input[2].clone()];
+    let err = super::ll1::parse_tree(&input, &cfg.into_non_left_recursion()).err().unwrap();
+    assert_eq!((err.position, err.found), (2, None));
+}
+
+#[test]
+fn test_incremental_reparse() {
+    use super::incremental::{Edit, IncrementalParser};
+    use crate::parser::render::{to_sexpr, RenderOptions};
+
+    let cfg = gen_cfg(&GRAMMER);
+    let parser = IncrementalParser::new(&cfg);
+    let old = parser.parse(&words(&["num", "+", "name", "*", "num", "-", "num"])).ok().unwrap();
+
+    // `name` becomes `( name )`
+    let edit = Edit { range: 2..3, insert: words(&["(", "name", ")"]) };
+    let (new, changed) = parser.reparse(&old, &edit).ok().unwrap();
+    let fresh = parser.parse(new.tokens()).ok().unwrap();
+    assert_eq!(new.tree(), fresh.tree());
+    assert_eq!(
+        to_sexpr(&new.tree(), &RenderOptions::new().collapse_unit(true)),
+        "(Goal/Expr (Expr (Expr/Term/Factor num) + (Term (Term/Factor \"(\" (Expr/Term/Factor name) \")\") * (Factor num))) - (Term/Factor num))"
+    );
+    // `num +` on the left is reused, so is the trailing `num` once the parser is back in step
+    assert!(!changed.contains(&vec![0, 0, 0]));
+    assert!(!changed.contains(&vec![0, 2]));
+    assert!(changed.contains(&vec![0, 0, 2]));
+    assert!(changed.contains(&vec![]));
+    assert_eq!(changed[0], vec![]);
+
+    // a deletion that reuses nothing before the edit
+    let edit = Edit { range: 0..2, insert: vec![] };
+    let (new, _) = parser.reparse(&old, &edit).ok().unwrap();
+    assert_eq!(new.tokens(), &words(&["name", "*", "num", "-", "num"])[..]);
+    assert_eq!(new.tree(), parser.parse(new.tokens()).ok().unwrap().tree());
+
+    // edits that break the input are reported as errors
+    let edit = Edit { range: 6..7, insert: vec![] };
+    assert!(parser.reparse(&old, &edit).is_err());
+    // and so are ranges that aren't within the old tokens
+    let len = old.tokens().len();
+    for range in [len..len + 1, len + 1..len + 1, std::ops::Range { start: 3, end: 2 }] {
+        let edit = Edit { range, insert: words(&["num"]) };
+        assert!(parser.reparse(&old, &edit).is_err());
+    }
+}
+
+#[test]
+fn test_lr1_large_grammar() {
+    // E0 -> E0 op0 E1 | E1 ; ... ; E99 -> ( E0 ) | num, i.e. a hundred precedence levels
+    let levels = 100;
+    let e = |i: usize| format!("E{}", i);
+    let mut grammer = vec![("Goal".to_string(), vec![e(0)])];
+    for i in 0..levels {
+        grammer.push((e(i), vec![e(i), format!("op{}", i), e(i + 1)]));
+        grammer.push((e(i), vec![e(i + 1)]));
+    }
+    grammer.push((e(levels), vec!["(".to_string(), e(0), ")".to_string()]));
+    grammer.push((e(levels), vec!["num".to_string()]));
+    let grammer: Vec<(&str, Vec<&str>)> = grammer
+        .iter()
+        .map(|(nt, right)| (nt.as_str(), right.iter().map(|s| s.as_str()).collect()))
+        .collect();
+    let cfg = gen_cfg(&grammer);
+
+    let input = words(&["num", "op3", "(", "num", "op99", "num", "op0", "num", ")", "op42", "num"]);
+    assert!(super::lr1::parse(&input, &cfg).is_ok());
+    assert!(super::lr1::parse(&input[..input.len() - 1], &cfg).is_err());
+}
+
+#[test]
+fn test_serialize_tables() {
+    use super::serialize::{grammar_hash, Error, Tables};
+
+    let input = words(&["(", "num", "+", "name", ")", "*", "num"]);
+    let tables = Tables::new(gen_cfg(&GRAMMER)).with_lr1();
+    let text = tables.to_json();
+    let loaded = Tables::from_json(&text).unwrap();
+    assert_eq!(grammar_hash(&loaded.cfg), grammar_hash(&tables.cfg));
+    assert!(loaded.check(&gen_cfg(&GRAMMER)).is_ok());
+    assert_eq!(loaded.first.terminals(&Token::NT(NoneTerminal::new("Expr"))).map(|ts| ts.len()), Some(3));
+    assert_eq!(loaded.to_json(), text);
+    // the file doesn't depend on the order the grammar was built in
+    assert_eq!(Tables::new(gen_cfg(&GRAMMER)).with_lr1().to_json(), text);
+
+    let (action, goto) = loaded.lr1.as_ref().unwrap();
+    assert_eq!(action.len(), tables.lr1.as_ref().unwrap().0.len());
+    assert!(super::lr1::parse_with_tables(&input, action, goto).is_ok());
+    assert!(super::lr1::parse_with_tables(&input[1..], action, goto).is_err());
+
+    let bytes = tables.to_bytes();
+    assert!(bytes.len() < text.len() / 2);
+    let loaded = Tables::from_bytes(&bytes).unwrap();
+    assert_eq!(loaded.to_json(), text);
+
+    let cfg = gen_cfg(&RIGHT_RECURSIVE_GRAMMER);
+    let tables = Tables::new(gen_cfg(&RIGHT_RECURSIVE_GRAMMER)).with_ll1();
+    let loaded = Tables::from_bytes(&tables.to_bytes()).unwrap();
+    let predict = loaded.ll1.as_ref().unwrap();
+    assert!(super::ll1::parse_with_predict(&input, &loaded.cfg, &loaded.first, predict).is_ok());
+    assert!(super::ll1::parse_with_predict(&input[1..], &loaded.cfg, &loaded.first, predict).is_err());
+
+    // tables for another grammar, or a grammar edited by hand, are refused
+    match loaded.check(&gen_cfg(&GRAMMER)) {
+        Err(Error::HashMismatch { expected, found }) => {
+            assert_eq!(expected, grammar_hash(&gen_cfg(&GRAMMER)));
+            assert_eq!(found, grammar_hash(&cfg));
+        }
+        _ => panic!("loaded tables accepted for another grammar"),
     }
+    let edited = tables.to_json().replacen("\"name\"", "\"ident\"", 1);
+    match Tables::from_json(&edited) {
+        Err(Error::HashMismatch { expected, found }) => {
+            assert_eq!(found, grammar_hash(&cfg));
+            assert_ne!(expected, found);
+        }
+        _ => panic!("edited grammar accepted"),
+    }
+    // and so are tables edited by hand
+    let edited = tables.to_json().replacen("[\"Expr@\", 0, [\"+\"]]", "[\"Expr@\", 0, [\"-\"]]", 1);
+    assert_ne!(edited, tables.to_json());
+    assert!(matches!(Tables::from_json(&edited), Err(Error::ChecksumMismatch { .. })));
+    assert!(matches!(Tables::from_json("{\"format\": 1"), Err(Error::Syntax(_))));
+    assert!(matches!(Tables::from_bytes(b"EAC2\x00\x06\x00"), Err(Error::Format(_))));
+}
+
+#[test]
+fn test_start_symbol_and_sentinels() {
+    use crate::parser::stream::TokenBuffer;
+
+    // `Goal` is an ordinary nonterminal here, `$`, `eof@@` and `empty@@` ordinary terminals
+    let grammer = vec![
+        ("Program", vec!["Stmt", "Program"]),
+        ("Program", vec!["ε"]),
+        ("Stmt", vec!["Goal", "eof@@"]),
+        ("Goal", vec!["empty@@"]),
+        ("Goal", vec!["dollar"]),
+    ];
+    let mut cfg = gen_cfg(&grammer);
+    cfg.start = NoneTerminal::new("Program");
+    assert_eq!(cfg.augmented().to_string(), "Program@ -> Program $ ");
+
+    let first = first(&cfg);
+    let follow = follow(&cfg, &first);
+    let mut f = follow.terminals(&NoneTerminal::new("Program")).unwrap();
+    f.sort();
+    assert_eq!(f, vec![Terminal::Eof]);
+    let f: HashSet<_> = first.terminals(&Token::NT(NoneTerminal::new("Program"))).unwrap().into_iter().collect();
+    assert_eq!(f, terminals(&["dollar", "empty@@", "ε"]).into_iter().collect());
+
+    let input = words(&["empty@@", "eof@@", "dollar", "eof@@"]);
+    let cfg = NoneLeftRecursionCFG(cfg);
+    assert!(super::ll1::parse(&input, &cfg).is_ok());
+    assert!(super::ll1::parse(&input[..0], &cfg).is_ok());
+    assert!(super::ll1::parse(&input[..3], &cfg).is_err());
+    assert!(super::lr1::parse(&input, &cfg.0).is_ok());
+    assert!(super::lr1::parse(&input[..0], &cfg.0).is_ok());
+    assert!(super::lr1::parse_stream(TokenBuffer::new(input[..3].to_vec()), &cfg.0).is_err());
+}
+
+#[test]
+fn test_differential() {
+    use super::differential::{Harness, Outcome};
+    use super::recursive_descent;
+
+    let left = gen_cfg(&GRAMMER);
+    let harness = Harness::new(&left);
+    assert_eq!(harness.parsers(), vec!["backtrack", "lr1"]);
+    assert!(harness.run(1, 30).unwrap() > 100);
+
+    let right = gen_cfg(&RIGHT_RECURSIVE_GRAMMER);
+    let mut harness = Harness::new(&right);
+    harness.add("recursive descent", |tokens| match recursive_descent::parse(tokens.to_vec()) {
+        Ok(()) => Outcome::Accept(None),
+        Err(_) => Outcome::Reject,
+    });
+    assert_eq!(harness.parsers(), vec!["backtrack", "lr1", "ll1", "recursive descent"]);
+    assert!(harness.run(2, 30).is_ok());
+
+    // a parser that wrongly rejects division is caught on the smallest such sentence
+    harness.add("no division", |tokens| match recursive_descent::parse(tokens.to_vec()) {
+        Ok(()) if !tokens.contains(&crate::parser::Terminal::new("/")) => Outcome::Accept(None),
+        _ => Outcome::Reject,
+    });
+    let found = harness.run(3, 30).unwrap_err();
+    assert_eq!(found.input.len(), 3);
+    assert!(found.input.contains(&crate::parser::Terminal::new("/")));
+    assert!(found.to_string().contains("\n  no division rejects"));
+    assert!(found.to_string().contains("\n  lr1 accepts (Goal (Expr (Term (Factor "));
+
+    // ambiguous, so there is no LR(1) or LL(1) parser to compare
+    let ambiguous = gen_cfg(&[("Goal", vec!["Goal", "Goal"]), ("Goal", vec!["a"])]);
+    assert_eq!(Harness::new(&ambiguous).parsers(), vec!["backtrack"]);
 }
@@ -0,0 +1,50 @@
+use std::fmt;
+
+use super::Terminal;
+
+/// One configuration of a table-driven parser, together with the move taken from it.
+#[derive(Clone, Debug)]
+pub struct Trace<S, A> {
+    pub stack: Vec<S>,
+    pub input: Vec<Terminal>,
+    pub action: A,
+}
+
+/// Renders a trace as the usual three column table: stack (bottom on the left),
+/// remaining input and the action.
+pub fn table<S: fmt::Display, A: fmt::Display>(trace: &[Trace<S, A>]) -> String {
+    let rows: Vec<_> = trace
+        .iter()
+        .map(|t| [join(&t.stack), join(&t.input), t.action.to_string()])
+        .collect();
+    let header = ["Stack", "Input", "Action"];
+    let mut widths: Vec<_> = header.iter().map(|h| h.chars().count()).collect();
+    for row in &rows {
+        for (w, col) in widths.iter_mut().zip(row.iter()) {
+            *w = (*w).max(col.chars().count());
+        }
+    }
+
+    let mut ret = String::new();
+    push_row(&mut ret, &header, &widths);
+    let rule: Vec<_> = widths.iter().map(|w| "-".repeat(*w)).collect();
+    push_row(&mut ret, &rule, &widths);
+    for row in &rows {
+        push_row(&mut ret, row, &widths);
+    }
+    ret
+}
+
+fn join<T: fmt::Display>(xs: &[T]) -> String {
+    xs.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(" ")
+}
+
+fn push_row<S: AsRef<str>>(out: &mut String, row: &[S], widths: &[usize]) {
+    let cols: Vec<_> = row
+        .iter()
+        .zip(widths)
+        .map(|(c, w)| format!("{:w$}", c.as_ref(), w = *w))
+        .collect();
+    out.push_str(cols.join(" | ").trim_end());
+    out.push('\n');
+}
@@ -1,6 +1,7 @@
-#![feature(vec_remove_item)]
-
+pub mod deprecated;
 pub mod parser;
+pub mod regex;
+pub mod scanner;
 
 fn main() {
     println!("Hello, world!");
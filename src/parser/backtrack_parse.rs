@@ -1,5 +1,274 @@
-use crate::parser::{Error, ParseTree, Terminal, CFG};
+use std::collections::HashMap;
 
-pub fn backtrack_parse(_cfg: &CFG, _tokens: &[Terminal]) -> Result<ParseTree, Error> {
-    unimplemented!()
+use crate::parser::stream::{TokenBuffer, TokenStream};
+use crate::parser::token::Kind;
+use crate::parser::{Element, Error, NonTerminal, ParseTree, CFG};
+
+pub fn backtrack_parse<T: Kind + Clone>(cfg: &CFG, tokens: &[T]) -> Result<ParseTree<T>, Error<T>> {
+    backtrack_parse_stream(cfg, TokenBuffer::new(tokens.iter().cloned()))
+}
+
+/// Top-down parse that tries the productions of each nonterminal in order and backs up
+/// whenever the rest of the input cannot be derived. An expansion is abandoned when the
+/// symbols still to be matched need more tokens than are left, which also stops left
+/// recursion; a nonterminal nested at the same position more often than there are tokens
+/// left is cut off as well, for left recursion through nullable symbols.
+///
+/// On failure the input is cut down to its longest prefix that some sentence starts with,
+/// and the error points at the token right after it.
+pub fn backtrack_parse_stream<S>(cfg: &CFG, tokens: S) -> Result<ParseTree<S::Token>, Error<S::Token>>
+where
+    S: TokenStream,
+    S::Token: Kind + Clone,
+{
+    let mut parser = Parser {
+        cfg,
+        tokens,
+        out: vec![],
+        path: vec![],
+        min_len: min_len(cfg),
+        need: 0,
+    };
+    let start = Element::NT(cfg.start.clone());
+    if parser.symbol(&start, &mut |p| p.tokens.current().is_none()) {
+        return Ok(parser.out.pop().unwrap());
+    }
+    let tokens = parser.tokens.remaining();
+    let position = viable_prefix(cfg, &parser.min_len, &tokens);
+    Err(Error {
+        position,
+        found: tokens.get(position).cloned(),
+    })
+}
+
+struct Parser<'c, S: TokenStream> {
+    cfg: &'c CFG,
+    tokens: S,
+    // finished subtrees, children of the nonterminals being expanded
+    out: Vec<ParseTree<S::Token>>,
+    // nonterminals being expanded and the position they started at
+    path: Vec<(&'c NonTerminal, usize)>,
+    // fewest tokens each nonterminal derives
+    min_len: HashMap<&'c NonTerminal, usize>,
+    // fewest tokens the pending continuations still have to match
+    need: usize,
+}
+
+fn min_len(cfg: &CFG) -> HashMap<&NonTerminal, usize> {
+    let mut ret = HashMap::new();
+    let mut updated = true;
+    while updated {
+        updated = false;
+        for block in &cfg.productions {
+            for prod in &block.productions {
+                let len = prod.right.iter().try_fold(0, |acc, e| match e {
+                    Element::T(_) => Some(acc + 1),
+                    Element::Empty => Some(acc),
+                    Element::NT(nt) => ret.get(nt).map(|l| acc + l),
+                });
+                if let Some(len) = len {
+                    let entry = ret.entry(&block.left).or_insert(usize::MAX);
+                    if len < *entry {
+                        *entry = len;
+                        updated = true;
+                    }
+                }
+            }
+        }
+    }
+    ret
+}
+
+// block, production, dot and the position the production started at
+type Item = (usize, usize, usize, usize);
+
+// length of the longest prefix of `tokens` that some sentence starts with, i.e. how far an
+// Earley recognizer gets before its item set runs empty; the backtracking parser can't tell,
+// as it cuts off expansions long before they would have reached the offending token
+fn viable_prefix<T: Kind>(cfg: &CFG, min_len: &HashMap<&NonTerminal, usize>, tokens: &[T]) -> usize {
+    let block_of = |nt: &NonTerminal| cfg.productions.iter().position(|b| &b.left == nt);
+    // the symbol after the dot, skipping `Element::Empty`
+    let next = |&(b, p, dot, _): &Item| {
+        let right = &cfg.productions[b].productions[p].right;
+        right[dot..].iter().position(|e| *e != Element::Empty).map(|i| (dot + i, &right[dot + i]))
+    };
+    let productive = |b: usize, p: usize| {
+        cfg.productions[b].productions[p].right.iter().all(|e| match e {
+            Element::NT(nt) => min_len.contains_key(nt),
+            _ => true,
+        })
+    };
+
+    let mut sets: Vec<Vec<Item>> = vec![vec![]; tokens.len() + 1];
+    if let Some(b) = block_of(&cfg.start) {
+        let prods = 0..cfg.productions[b].productions.len();
+        sets[0] = prods.filter(|&p| productive(b, p)).map(|p| (b, p, 0, 0)).collect();
+    }
+    for i in 0..=tokens.len() {
+        // nonterminals that derive the empty string here, for items predicting them later
+        let mut nulled: Vec<&NonTerminal> = vec![];
+        let mut j = 0;
+        while j < sets[i].len() {
+            let item = sets[i][j];
+            j += 1;
+            let (b, p, _, origin) = item;
+            match next(&item) {
+                None => {
+                    let left = &cfg.productions[b].left;
+                    if origin == i {
+                        nulled.push(left);
+                    }
+                    let waiting: Vec<_> = sets[origin]
+                        .iter()
+                        .filter_map(|w| match next(w) {
+                            Some((dot, Element::NT(nt))) if nt == left => Some((w.0, w.1, dot + 1, w.3)),
+                            _ => None,
+                        })
+                        .collect();
+                    for w in waiting {
+                        add(&mut sets[i], w);
+                    }
+                }
+                Some((dot, Element::NT(nt))) => {
+                    if let Some(nb) = block_of(nt) {
+                        for np in 0..cfg.productions[nb].productions.len() {
+                            if productive(nb, np) {
+                                add(&mut sets[i], (nb, np, 0, i));
+                            }
+                        }
+                    }
+                    if nulled.contains(&nt) {
+                        add(&mut sets[i], (b, p, dot + 1, origin));
+                    }
+                }
+                Some((dot, Element::T(t))) => {
+                    if i < tokens.len() && tokens[i].kind() == t {
+                        add(&mut sets[i + 1], (b, p, dot + 1, origin));
+                    }
+                }
+                Some((_, Element::Empty)) => unreachable!(),
+            }
+        }
+        if i < tokens.len() && sets[i + 1].is_empty() {
+            return i;
+        }
+    }
+    tokens.len()
+}
+
+fn add(set: &mut Vec<Item>, item: Item) {
+    if !set.contains(&item) {
+        set.push(item);
+    }
+}
+
+type Cont<'k, 'c, S> = dyn FnMut(&mut Parser<'c, S>) -> bool + 'k;
+
+impl<'c, S> Parser<'c, S>
+where
+    S: TokenStream,
+    S::Token: Kind + Clone,
+{
+    fn sequence(&mut self, elements: &'c [Element], k: &mut Cont<'_, 'c, S>) -> bool {
+        match elements.split_first() {
+            None => k(self),
+            Some((first, rest)) => {
+                let rest_len = self.len_of(rest);
+                self.need += rest_len;
+                let done = self.symbol(first, &mut |p| {
+                    p.need -= rest_len;
+                    if p.sequence(rest, k) {
+                        return true;
+                    }
+                    p.need += rest_len;
+                    false
+                });
+                if !done {
+                    self.need -= rest_len;
+                }
+                done
+            }
+        }
+    }
+
+    fn len_of(&self, elements: &[Element]) -> usize {
+        elements
+            .iter()
+            .map(|e| match e {
+                Element::T(_) => 1,
+                Element::Empty => 0,
+                Element::NT(nt) => self.min_len.get(nt).cloned().unwrap_or(0),
+            })
+            .sum()
+    }
+
+    fn symbol(&mut self, element: &'c Element, k: &mut Cont<'_, 'c, S>) -> bool {
+        match element {
+            Element::T(t) => {
+                let token = match self.tokens.current() {
+                    Some(token) if token.kind() == t => token.clone(),
+                    _ => return false,
+                };
+                let mark = self.tokens.mark();
+                self.tokens.forward();
+                self.out.push(ParseTree::Leaf(token));
+                if k(self) {
+                    self.tokens.release(mark);
+                    return true;
+                }
+                self.out.pop();
+                self.tokens.reset(mark);
+                false
+            }
+            Element::Empty => {
+                self.out.push(ParseTree::Empty);
+                if k(self) {
+                    return true;
+                }
+                self.out.pop();
+                false
+            }
+            Element::NT(nt) => self.expand(nt, k),
+        }
+    }
+
+    fn expand(&mut self, nt: &'c NonTerminal, k: &mut Cont<'_, 'c, S>) -> bool {
+        let block = match (self.cfg.productions.iter().find(|b| &b.left == nt), self.min_len.get(nt)) {
+            (Some(block), Some(len)) => {
+                let need = self.need + len;
+                if need > 0 && self.tokens.peek(need - 1).is_none() {
+                    return false;
+                }
+                block
+            }
+            _ => return false,
+        };
+        let pos = self.tokens.position();
+        let depth = self.path.iter().filter(|&&(n, p)| n == nt && p == pos).count();
+        if depth > 0 && self.tokens.peek(depth - 1).is_none() {
+            return false;
+        }
+        for prod in &block.productions {
+            let base = self.out.len();
+            self.path.push((nt, pos));
+            let done = self.sequence(&prod.right, &mut |p| {
+                let children = p.out.split_off(base);
+                let entry = p.path.pop().unwrap();
+                p.out.push(ParseTree::Node(nt.clone(), children));
+                if k(p) {
+                    return true;
+                }
+                if let Some(ParseTree::Node(_, children)) = p.out.pop() {
+                    p.out.extend(children);
+                }
+                p.path.push(entry);
+                false
+            });
+            if done {
+                return true;
+            }
+            self.path.pop();
+        }
+        false
+    }
 }
@@ -0,0 +1,145 @@
+use crate::parser::validate::Diagnostic;
+use crate::parser::{Element, NonTerminal, ProdBlock, Production, Terminal, CFG};
+
+/// Collects productions one at a time and checks the grammar once it is complete.
+///
+/// Blocks are created in the order their nonterminals first appear on a left side, terminals
+/// are declared in the order they are first used. Without an explicit `start`, the first
+/// block is the start symbol.
+#[derive(Debug, Clone, Default)]
+pub struct CFGBuilder {
+    start: Option<NonTerminal>,
+    terminals: Vec<Terminal>,
+    productions: Vec<ProdBlock>,
+}
+
+impl CFG {
+    pub fn builder() -> CFGBuilder {
+        CFGBuilder::default()
+    }
+}
+
+impl CFGBuilder {
+    pub fn start(mut self, start: NonTerminal) -> Self {
+        self.start = Some(start);
+        self
+    }
+
+    /// Declares a terminal no production uses.
+    pub fn terminal(mut self, t: Terminal) -> Self {
+        self.declare(t);
+        self
+    }
+
+    pub fn production(mut self, left: NonTerminal, right: Vec<Element>) -> Self {
+        for e in &right {
+            if let Element::T(t) = e {
+                self.declare(t.clone());
+            }
+        }
+        let prod = Production::new(left.clone(), right);
+        match self.productions.iter_mut().find(|b| b.left == left) {
+            Some(block) => block.productions.push(prod),
+            None => self.productions.push(ProdBlock::new(left, vec![prod])),
+        }
+        self
+    }
+
+    /// One production per alternative.
+    pub fn rule(self, left: NonTerminal, alternatives: Vec<Vec<Element>>) -> Self {
+        alternatives
+            .into_iter()
+            .fold(self, |builder, right| builder.production(left.clone(), right))
+    }
+
+    /// The grammar, or everything `CFG::validate` finds wrong with it.
+    pub fn build(self) -> Result<CFG, Vec<Diagnostic>> {
+        let start = match (self.start, self.productions.first()) {
+            (Some(start), _) => start,
+            (None, Some(block)) => block.left.clone(),
+            (None, None) => return Err(vec![Diagnostic::NoProductions]),
+        };
+        let cfg = CFG {
+            start,
+            non_terminals: self.productions.iter().map(|b| b.left.clone()).collect(),
+            terminals: self.terminals,
+            productions: self.productions,
+        };
+        cfg.validate().map(|_| cfg)
+    }
+
+    fn declare(&mut self, t: Terminal) {
+        if !self.terminals.contains(&t) {
+            self.terminals.push(t);
+        }
+    }
+}
+
+/// Declares a grammar in BNF-like syntax. Nonterminals are identifiers, terminals are string
+/// literals, `()` is the empty alternative and the first rule's left side is the start symbol:
+///
+/// ```ignore
+/// let cfg = grammar! {
+///     Expr -> Expr "+" Term | Term;
+///     Term -> "(" Expr ")" | "num";
+/// };
+/// ```
+///
+/// # Panics
+///
+/// If the grammar is malformed, with every diagnostic in the message.
+#[macro_export]
+macro_rules! grammar {
+    ($($body:tt)*) => {{
+        #[allow(unused_mut)]
+        let mut builder = $crate::parser::CFG::builder();
+        $crate::__grammar_rules!(builder; $($body)*);
+        match builder.build() {
+            Ok(cfg) => cfg,
+            Err(diagnostics) => {
+                let messages: Vec<_> = diagnostics.iter().map(|d| d.to_string()).collect();
+                panic!("malformed grammar:\n    {}", messages.join("\n    "))
+            }
+        }
+    }};
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __grammar_rules {
+    ($b:ident;) => {};
+    ($b:ident; $left:ident -> $($rest:tt)*) => {
+        $crate::__grammar_alternative!($b; $left; []; $($rest)*);
+    };
+}
+
+// munches one symbol at a time, the elements so far are kept in the brackets
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __grammar_alternative {
+    ($b:ident; $left:ident; [$($e:expr),*];) => {
+        $crate::__grammar_alternative!($b; $left; [$($e),*]; ;);
+    };
+    ($b:ident; $left:ident; [$($e:expr),*]; ; $($rest:tt)*) => {
+        $b = $b.production($crate::parser::NonTerminal::new(stringify!($left)), vec![$($e),*]);
+        $crate::__grammar_rules!($b; $($rest)*);
+    };
+    ($b:ident; $left:ident; [$($e:expr),*]; | $($rest:tt)*) => {
+        $b = $b.production($crate::parser::NonTerminal::new(stringify!($left)), vec![$($e),*]);
+        $crate::__grammar_alternative!($b; $left; []; $($rest)*);
+    };
+    ($b:ident; $left:ident; [$($e:expr),*]; () $($rest:tt)*) => {
+        $crate::__grammar_alternative!($b; $left; [$($e,)* $crate::parser::Element::Empty]; $($rest)*);
+    };
+    ($b:ident; $left:ident; [$($e:expr),*]; $t:literal $($rest:tt)*) => {
+        $crate::__grammar_alternative!(
+            $b; $left; [$($e,)* $crate::parser::Element::T($crate::parser::Terminal::new($t))]; $($rest)*
+        );
+    };
+    ($b:ident; $left:ident; [$($e:expr),*]; $nt:ident $($rest:tt)*) => {
+        $crate::__grammar_alternative!(
+            $b; $left; [$($e,)* $crate::parser::Element::NT($crate::parser::NonTerminal::new(stringify!($nt)))];
+            $($rest)*
+        );
+    };
+}
@@ -0,0 +1,225 @@
+use crate::parser::{Element, NonTerminal, ProdBlock, Production, Terminal, CFG};
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Ebnf {
+    Element(Element),
+    /// `( a b | c )`, one inner vector per alternative.
+    Group(Vec<Vec<Ebnf>>),
+    /// `x*`
+    Star(Box<Ebnf>),
+    /// `x+`
+    Plus(Box<Ebnf>),
+    /// `x?`
+    Optional(Box<Ebnf>),
+}
+
+impl Ebnf {
+    pub fn t<S: AsRef<str>>(name: S) -> Self {
+        Ebnf::Element(Element::T(Terminal::new(name)))
+    }
+
+    pub fn nt<S: AsRef<str>>(name: S) -> Self {
+        Ebnf::Element(Element::NT(NonTerminal::new(name)))
+    }
+
+    pub fn star(self) -> Self {
+        Ebnf::Star(Box::new(self))
+    }
+
+    pub fn plus(self) -> Self {
+        Ebnf::Plus(Box::new(self))
+    }
+
+    pub fn optional(self) -> Self {
+        Ebnf::Optional(Box::new(self))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EbnfBlock {
+    pub left: NonTerminal,
+    pub alternatives: Vec<Vec<Ebnf>>,
+}
+
+impl EbnfBlock {
+    pub fn new(left: NonTerminal, alternatives: Vec<Vec<Ebnf>>) -> Self {
+        EbnfBlock { left, alternatives }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EbnfGrammar {
+    pub start: NonTerminal,
+    pub blocks: Vec<EbnfBlock>,
+}
+
+/// Which kind of parser the desugared grammar is meant for. Repetitions become right
+/// recursive for top-down parsers and left recursive for bottom-up ones, which keeps the
+/// LR stack shallow.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Family {
+    TopDown,
+    BottomUp,
+}
+
+/// Rewrites every EBNF operator into plain productions over generated nonterminals, named
+/// by forking the nonterminal whose block they appear in.
+pub fn desugar(grammar: &EbnfGrammar, family: Family) -> CFG {
+    let mut ctx = Desugar {
+        family,
+        names: grammar.blocks.iter().map(|b| b.left.clone()).collect(),
+        generated: vec![],
+    };
+    let mut productions = vec![];
+    for block in &grammar.blocks {
+        let prods = block
+            .alternatives
+            .iter()
+            .map(|alt| Production::new(block.left.clone(), ctx.sequence(alt, &block.left)))
+            .collect();
+        productions.push(ProdBlock::new(block.left.clone(), prods));
+        productions.append(&mut ctx.generated);
+    }
+
+    let non_terminals = productions.iter().map(|b| b.left.clone()).collect();
+    let mut terminals = vec![];
+    for prod in productions.iter().flat_map(|b| &b.productions) {
+        for e in &prod.right {
+            if let Element::T(t) = e {
+                if !terminals.contains(t) {
+                    terminals.push(t.clone())
+                }
+            }
+        }
+    }
+    CFG {
+        start: grammar.start.clone(),
+        non_terminals,
+        terminals,
+        productions,
+    }
+}
+
+struct Desugar {
+    family: Family,
+    names: Vec<NonTerminal>,
+    generated: Vec<ProdBlock>,
+}
+
+impl Desugar {
+    fn sequence(&mut self, items: &[Ebnf], owner: &NonTerminal) -> Vec<Element> {
+        let ret: Vec<_> = items.iter().flat_map(|e| self.lower(e, owner)).collect();
+        if ret.is_empty() {
+            vec![Element::Empty]
+        } else {
+            ret
+        }
+    }
+
+    fn lower(&mut self, e: &Ebnf, owner: &NonTerminal) -> Vec<Element> {
+        match e {
+            Ebnf::Element(el) => vec![el.clone()],
+            Ebnf::Group(alts) if alts.len() == 1 => {
+                alts[0].iter().flat_map(|e| self.lower(e, owner)).collect()
+            }
+            Ebnf::Group(_) => {
+                let alts = self.alternatives(e, owner);
+                let nt = self.fresh(owner);
+                self.add(&nt, alts);
+                vec![Element::NT(nt)]
+            }
+            Ebnf::Optional(inner) => {
+                let mut alts = self.alternatives(inner, owner);
+                alts.push(vec![Element::Empty]);
+                let nt = self.fresh(owner);
+                self.add(&nt, alts);
+                vec![Element::NT(nt)]
+            }
+            Ebnf::Star(inner) => {
+                let alts = self.alternatives(inner, owner);
+                let nt = self.fresh(owner);
+                let rep = self.repeat(&nt, alts, vec![Element::Empty]);
+                self.add(&nt, rep);
+                vec![Element::NT(nt)]
+            }
+            Ebnf::Plus(inner) => {
+                let alts = self.alternatives(inner, owner);
+                let nt = self.fresh(owner);
+                match self.family {
+                    // x+ => N -> N x | x
+                    Family::BottomUp => {
+                        let rep = self.repeat(&nt, alts.clone(), vec![]);
+                        let mut prods = rep;
+                        prods.extend(alts);
+                        self.add(&nt, prods);
+                    }
+                    // x+ => N -> x N@ ; N@ -> x N@ | empty, so no two alternatives share a prefix
+                    Family::TopDown => {
+                        let tail = self.fresh(owner);
+                        let prods = alts
+                            .iter()
+                            .map(|alt| {
+                                let mut alt = alt.clone();
+                                alt.retain(|e| e != &Element::Empty);
+                                alt.push(Element::NT(tail.clone()));
+                                alt
+                            })
+                            .collect();
+                        self.add(&nt, prods);
+                        let rep = self.repeat(&tail, alts, vec![Element::Empty]);
+                        self.add(&tail, rep);
+                    }
+                }
+                vec![Element::NT(nt)]
+            }
+        }
+    }
+
+    // the alternatives an operand stands for, a group contributes all of its own
+    fn alternatives(&mut self, e: &Ebnf, owner: &NonTerminal) -> Vec<Vec<Element>> {
+        match e {
+            Ebnf::Group(alts) => alts.iter().map(|alt| self.sequence(alt, owner)).collect(),
+            e => vec![self.lower(e, owner)],
+        }
+    }
+
+    // `nt -> nt alt` or `nt -> alt nt` for every non-empty alternative, plus `base` if not empty.
+    // An empty alternative would give the cycle `nt -> nt`, the empty string is matched anyway.
+    fn repeat(&self, nt: &NonTerminal, alts: Vec<Vec<Element>>, base: Vec<Element>) -> Vec<Vec<Element>> {
+        let mut ret: Vec<_> = alts
+            .into_iter()
+            .filter_map(|mut alt| {
+                alt.retain(|e| e != &Element::Empty);
+                if alt.is_empty() {
+                    return None;
+                }
+                match self.family {
+                    Family::BottomUp => alt.insert(0, Element::NT(nt.clone())),
+                    Family::TopDown => alt.push(Element::NT(nt.clone())),
+                }
+                Some(alt)
+            })
+            .collect();
+        if !base.is_empty() {
+            ret.push(base);
+        }
+        ret
+    }
+
+    fn fresh(&mut self, owner: &NonTerminal) -> NonTerminal {
+        let mut nt = owner.fork();
+        while self.names.contains(&nt) {
+            nt = nt.fork();
+        }
+        self.names.push(nt.clone());
+        nt
+    }
+
+    fn add(&mut self, nt: &NonTerminal, alts: Vec<Vec<Element>>) {
+        let prods = alts
+            .into_iter()
+            .map(|right| Production::new(nt.clone(), right))
+            .collect();
+        self.generated.push(ProdBlock::new(nt.clone(), prods));
+    }
+}
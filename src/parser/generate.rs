@@ -0,0 +1,277 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::parser::{Element, NonTerminal, Production, Terminal, CFG};
+
+/// Every sentence of at most `max_len` terminals the grammar derives, shortest first and
+/// sentences of the same length in order of terminal names.
+pub fn sentences(cfg: &CFG, max_len: usize) -> Vec<Vec<Terminal>> {
+    // the sentences of each nonterminal found so far, grown until nothing changes
+    let mut lang: HashMap<&NonTerminal, HashSet<Vec<Terminal>>> = HashMap::new();
+    let mut updated = true;
+    while updated {
+        updated = false;
+        for block in &cfg.productions {
+            for prod in &block.productions {
+                let mut acc: HashSet<Vec<Terminal>> = Some(vec![]).into_iter().collect();
+                for e in &prod.right {
+                    let next: Vec<Vec<Terminal>> = match e {
+                        Element::T(t) => vec![vec![t.clone()]],
+                        Element::NT(nt) => lang.get(nt).into_iter().flatten().cloned().collect(),
+                        Element::Empty => continue,
+                    };
+                    acc = acc
+                        .iter()
+                        .flat_map(|a| {
+                            next.iter()
+                                .filter(move |b| a.len() + b.len() <= max_len)
+                                .map(move |b| [&a[..], &b[..]].concat())
+                        })
+                        .collect();
+                }
+                let known = lang.entry(&block.left).or_default();
+                for s in acc {
+                    updated |= known.insert(s);
+                }
+            }
+        }
+    }
+    let mut ret: Vec<_> = lang.remove(&cfg.start).into_iter().flatten().collect();
+    ret.sort_by_cached_key(|s| (s.len(), s.iter().map(|t| t.symbol().as_str()).collect::<Vec<_>>()));
+    ret
+}
+
+/// Random sentences, reproducible from a seed.
+pub struct Generator<'c> {
+    cfg: &'c CFG,
+    blocks: HashMap<&'c NonTerminal, &'c [Production]>,
+    lowest: HashMap<&'c NonTerminal, (usize, &'c Production)>,
+    rng: Rng,
+}
+
+impl<'c> Generator<'c> {
+    pub fn new(cfg: &'c CFG, seed: u64) -> Self {
+        let mut blocks = HashMap::new();
+        for block in &cfg.productions {
+            blocks.entry(&block.left).or_insert(&block.productions[..]);
+        }
+        Generator {
+            cfg,
+            blocks,
+            lowest: lowest(cfg),
+            rng: Rng::new(seed),
+        }
+    }
+
+    /// A sentence whose derivation tree is at most `max_depth` high, or as low as the grammar
+    /// allows if that is higher. Every step picks uniformly among the productions that still
+    /// fit, so this always terminates. `None` if the start symbol derives no sentence at all.
+    pub fn random(&mut self, max_depth: usize) -> Option<Vec<Terminal>> {
+        let cfg = self.cfg;
+        if !self.lowest.contains_key(&cfg.start) {
+            return None;
+        }
+        let mut out = vec![];
+        self.expand(&cfg.start, max_depth, &mut out);
+        Some(out)
+    }
+
+    fn expand(&mut self, nt: &NonTerminal, depth: usize, out: &mut Vec<Terminal>) {
+        let lowest = &self.lowest;
+        let fits: Vec<_> = self.blocks[nt]
+            .iter()
+            .filter(|p| height(p, lowest).is_some_and(|h| h <= depth))
+            .collect();
+        let prod = if fits.is_empty() {
+            self.lowest[nt].1
+        } else {
+            fits[self.rng.below(fits.len())]
+        };
+        for e in &prod.right {
+            match e {
+                Element::T(t) => out.push(t.clone()),
+                Element::NT(nt) => self.expand(nt, depth.saturating_sub(1), out),
+                Element::Empty => {}
+            }
+        }
+    }
+}
+
+/// Sentences that together use every production at least once, leaving out those that can't
+/// take part in any derivation. Each sentence is built for the first production not used yet:
+/// the shortest context the start symbol derives around its left side, with every other
+/// nonterminal expanded by its lowest derivation.
+pub fn covering(cfg: &CFG) -> Vec<Vec<Terminal>> {
+    let lowest = lowest(cfg);
+    let productive = |p: &Production| height(p, &lowest).is_some();
+    let mut yields = HashMap::new();
+    for &nt in lowest.keys() {
+        yield_len(nt, &lowest, &mut yields);
+    }
+
+    // the production and position each nonterminal is reached through with the fewest
+    // terminals around it, and how many that is
+    let mut parent: HashMap<&NonTerminal, (&Production, usize)> = HashMap::new();
+    let mut context: HashMap<&NonTerminal, usize> = HashMap::new();
+    if lowest.contains_key(&cfg.start) {
+        context.insert(&cfg.start, 0);
+    }
+    let mut updated = true;
+    while updated {
+        updated = false;
+        for prod in cfg.productions.iter().flat_map(|b| &b.productions).filter(|p| productive(p)) {
+            let outer = match context.get(&prod.left) {
+                Some(&n) => n,
+                None => continue,
+            };
+            let inner: usize = prod.right.iter().map(|e| element_len(e, &yields)).sum();
+            for (i, e) in prod.right.iter().enumerate() {
+                if let Element::NT(child) = e {
+                    let n = outer + inner - yields[child];
+                    if context.get(child).is_none_or(|&old| n < old) {
+                        context.insert(child, n);
+                        parent.insert(child, (prod, i));
+                        updated = true;
+                    }
+                }
+            }
+        }
+    }
+
+    let mut used = HashSet::new();
+    let mut ret = vec![];
+    for prod in cfg.productions.iter().flat_map(|b| &b.productions) {
+        if used.contains(&(prod as *const Production)) || !productive(prod) || !context.contains_key(&prod.left) {
+            continue;
+        }
+        let mut chain = vec![];
+        let mut nt = &prod.left;
+        while let Some(&(p, i)) = parent.get(nt) {
+            chain.push((p, i));
+            nt = &p.left;
+        }
+        chain.reverse();
+        let mut out = vec![];
+        derive_through(&chain, prod, &lowest, &mut used, &mut out);
+        ret.push(out);
+    }
+    ret
+}
+
+// expands `chain[0]`, following the chain at its marked position and ending with `target`
+fn derive_through(
+    chain: &[(&Production, usize)],
+    target: &Production,
+    lowest: &HashMap<&NonTerminal, (usize, &Production)>,
+    used: &mut HashSet<*const Production>,
+    out: &mut Vec<Terminal>,
+) {
+    let (prod, next) = match chain.first() {
+        Some(&(p, i)) => (p, Some(i)),
+        None => (target, None),
+    };
+    used.insert(prod);
+    for (i, e) in prod.right.iter().enumerate() {
+        match e {
+            Element::T(t) => out.push(t.clone()),
+            Element::NT(_) if next == Some(i) => derive_through(&chain[1..], target, lowest, used, out),
+            Element::NT(nt) => derive_lowest(nt, lowest, used, out),
+            Element::Empty => {}
+        }
+    }
+}
+
+fn derive_lowest(
+    nt: &NonTerminal,
+    lowest: &HashMap<&NonTerminal, (usize, &Production)>,
+    used: &mut HashSet<*const Production>,
+    out: &mut Vec<Terminal>,
+) {
+    let prod = lowest[nt].1;
+    used.insert(prod);
+    for e in &prod.right {
+        match e {
+            Element::T(t) => out.push(t.clone()),
+            Element::NT(nt) => derive_lowest(nt, lowest, used, out),
+            Element::Empty => {}
+        }
+    }
+}
+
+// height of the lowest derivation tree of each nonterminal that derives a sentence at all, and
+// the first production giving it
+fn lowest(cfg: &CFG) -> HashMap<&NonTerminal, (usize, &Production)> {
+    let mut ret: HashMap<&NonTerminal, (usize, &Production)> = HashMap::new();
+    let mut updated = true;
+    while updated {
+        updated = false;
+        for block in &cfg.productions {
+            for prod in &block.productions {
+                let h = match height(prod, &ret) {
+                    Some(h) => h,
+                    None => continue,
+                };
+                if ret.get(&block.left).is_none_or(|&(old, _)| h < old) {
+                    ret.insert(&block.left, (h, prod));
+                    updated = true;
+                }
+            }
+        }
+    }
+    ret
+}
+
+// length of the sentence `derive_lowest` gives for `nt`
+fn yield_len<'c>(
+    nt: &'c NonTerminal,
+    lowest: &HashMap<&'c NonTerminal, (usize, &'c Production)>,
+    yields: &mut HashMap<&'c NonTerminal, usize>,
+) -> usize {
+    if let Some(&n) = yields.get(nt) {
+        return n;
+    }
+    let mut n = 0;
+    for e in &lowest[nt].1.right {
+        n += match e {
+            Element::T(_) => 1,
+            Element::NT(nt) => yield_len(nt, lowest, yields),
+            Element::Empty => 0,
+        };
+    }
+    yields.insert(nt, n);
+    n
+}
+
+fn element_len(e: &Element, yields: &HashMap<&NonTerminal, usize>) -> usize {
+    match e {
+        Element::T(_) => 1,
+        Element::NT(nt) => yields[nt],
+        Element::Empty => 0,
+    }
+}
+
+fn height(prod: &Production, lowest: &HashMap<&NonTerminal, (usize, &Production)>) -> Option<usize> {
+    prod.right.iter().try_fold(1, |acc, e| match e {
+        Element::NT(nt) => lowest.get(nt).map(|&(h, _)| acc.max(h + 1)),
+        _ => Some(acc),
+    })
+}
+
+// xorshift64*, plenty for picking productions
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Rng((seed ^ 0x9e37_79b9_7f4a_7c15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
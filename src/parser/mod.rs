@@ -1,33 +1,63 @@
 pub mod backtrack_parse;
+pub mod builder;
+pub mod ebnf;
+pub mod generate;
+pub mod render;
+pub mod stream;
+pub mod symbol;
+pub mod token;
+pub mod validate;
+
+use std::fmt;
+
+use self::symbol::Symbol;
 
 #[cfg(test)]
 mod test;
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct Terminal {
-    name: String,
+    name: Symbol,
 }
 
 impl Terminal {
-    pub fn new<S: Into<String>>(name: S) -> Self {
-        Terminal {name: name.into()}
+    pub fn new<S: AsRef<str>>(name: S) -> Self {
+        Terminal {name: Symbol::intern(name.as_ref())}
+    }
+
+    pub fn symbol(&self) -> Symbol {
+        self.name
     }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct NonTerminal {
-    name: String,
+    name: Symbol,
 }
 
 impl NonTerminal {
-    pub fn new<S: Into<String>>(name: S) -> Self {
-        NonTerminal {name: name.into()}
+    pub fn new<S: AsRef<str>>(name: S) -> Self {
+        NonTerminal {name: Symbol::intern(name.as_ref())}
+    }
+
+    pub fn symbol(&self) -> Symbol {
+        self.name
     }
 
     pub fn fork(&self) -> NonTerminal {
-        NonTerminal {
-            name: self.name.clone() + "@",
-        }
+        NonTerminal::new(self.name.to_string() + "@")
+    }
+}
+
+impl From<Symbol> for Terminal {
+    fn from(name: Symbol) -> Self {
+        Terminal { name }
+    }
+}
+
+impl From<Symbol> for NonTerminal {
+    fn from(name: Symbol) -> Self {
+        NonTerminal { name }
     }
 }
 
@@ -75,8 +105,56 @@ pub struct CFGWithoutLeftRecursion {
     cfg: CFG,
 }
 
-pub struct ParseTree;
-pub struct Error;
+impl CFGWithoutLeftRecursion {
+    pub fn cfg(&self) -> &CFG {
+        &self.cfg
+    }
+}
+
+/// A parse tree whose leaves are the input tokens, `Terminal`s or `token::Token`s.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ParseTree<T = Terminal> {
+    Leaf(T),
+    Empty,
+    Node(NonTerminal, Vec<ParseTree<T>>),
+}
+
+impl<T> ParseTree<T> {
+    pub fn leaf(t: T) -> Self {
+        ParseTree::Leaf(t)
+    }
+
+    pub fn node(nt: NonTerminal, children: Vec<ParseTree<T>>) -> Self {
+        ParseTree::Node(nt, children)
+    }
+
+    /// Tokens at the leaves, left to right, i.e. the parsed sentence.
+    pub fn leaves(&self) -> Vec<&T> {
+        let mut ret = vec![];
+        self.collect_leaves(&mut ret);
+        ret
+    }
+
+    fn collect_leaves<'a>(&'a self, out: &mut Vec<&'a T>) {
+        match self {
+            ParseTree::Leaf(t) => out.push(t),
+            ParseTree::Empty => {}
+            ParseTree::Node(_, children) => {
+                for child in children {
+                    child.collect_leaves(out)
+                }
+            }
+        }
+    }
+}
+
+/// The input is not a sentence of the grammar. `position` is the index of the token the
+/// parser got stuck at, `found` that token, `None` at the end of input.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Error<T = Terminal> {
+    pub position: usize,
+    pub found: Option<T>,
+}
 
 ///////////////////////// eliminate left recursion /////////////////////////////////////////////////
 
@@ -177,20 +255,59 @@ impl PartialEq for Element {
 
 impl PartialEq<NonTerminal> for Element {
     fn eq(&self, other: &NonTerminal) -> bool {
-        match self {
-            Element::NT(nt) if nt.name == other.name => true,
-            _ => false,
-        }
+        matches!(self, Element::NT(nt) if nt.name == other.name)
     }
 }
 
 impl PartialEq<Terminal> for Element {
     fn eq(&self, other: &Terminal) -> bool {
-        match self {
-            Element::T(t) if t.name == other.name => true,
-            _ => false,
-        }
+        matches!(self, Element::T(t) if t.name == other.name)
     }
 }
 
 impl Eq for Element {}
+
+impl fmt::Display for Terminal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+impl fmt::Display for NonTerminal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+impl fmt::Display for Production {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ->", self.left)?;
+        for e in &self.right {
+            match e {
+                Element::T(t) => write!(f, " {}", t)?,
+                Element::NT(nt) => write!(f, " {}", nt)?,
+                Element::Empty => write!(f, " ε")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<T: token::Kind + fmt::Display> fmt::Display for Error<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.found {
+            None => write!(f, "unexpected end of input"),
+            Some(t) => {
+                match t.span() {
+                    Some(span) => write!(f, "at {}: ", span)?,
+                    None => write!(f, "at token {}: ", self.position)?,
+                }
+                if t.to_string() == t.kind().to_string() {
+                    write!(f, "unexpected `{}`", t)
+                } else {
+                    write!(f, "unexpected {} `{}`", t.kind(), t)
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use std::fmt;
+
+use crate::parser::ParseTree;
+
+const EPSILON: &str = "ε";
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RenderOptions {
+    /// Drop `ParseTree::Empty` leaves.
+    pub hide_empty: bool,
+    /// Merge chains like `Expr -> Term -> Factor` into one node labelled `Expr/Term/Factor`.
+    pub collapse_unit: bool,
+}
+
+impl RenderOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn hide_empty(mut self, hide: bool) -> Self {
+        self.hide_empty = hide;
+        self
+    }
+
+    pub fn collapse_unit(mut self, collapse: bool) -> Self {
+        self.collapse_unit = collapse;
+        self
+    }
+}
+
+pub fn to_dot<T: fmt::Display>(tree: &ParseTree<T>, opts: &RenderOptions) -> String {
+    let mut ret = String::from("digraph ParseTree {\n");
+    if let Some(shape) = Shape::build(tree, opts) {
+        let mut next = 0;
+        dot_node(&shape, &mut next, &mut ret);
+    }
+    ret += "}\n";
+    ret
+}
+
+/// Labels containing whitespace, parentheses or quotes are written as quoted strings.
+pub fn to_sexpr<T: fmt::Display>(tree: &ParseTree<T>, opts: &RenderOptions) -> String {
+    let mut ret = String::new();
+    if let Some(shape) = Shape::build(tree, opts) {
+        sexpr_node(&shape, &mut ret);
+    }
+    ret
+}
+
+pub fn to_text<T: fmt::Display>(tree: &ParseTree<T>, opts: &RenderOptions) -> String {
+    let mut ret = String::new();
+    if let Some(shape) = Shape::build(tree, opts) {
+        ret += &shape.label;
+        ret += "\n";
+        text_children(&shape, "", &mut ret);
+    }
+    ret
+}
+
+#[derive(Debug, Eq, PartialEq)]
+enum Kind {
+    Leaf,
+    Empty,
+    Node,
+}
+
+/// A parse tree with the render options already applied.
+struct Shape {
+    label: String,
+    kind: Kind,
+    children: Vec<Shape>,
+}
+
+impl Shape {
+    fn build<T: fmt::Display>(tree: &ParseTree<T>, opts: &RenderOptions) -> Option<Shape> {
+        match tree {
+            ParseTree::Leaf(t) => Some(Shape {
+                label: t.to_string(),
+                kind: Kind::Leaf,
+                children: vec![],
+            }),
+            ParseTree::Empty if opts.hide_empty => None,
+            ParseTree::Empty => Some(Shape {
+                label: EPSILON.to_string(),
+                kind: Kind::Empty,
+                children: vec![],
+            }),
+            ParseTree::Node(nt, children) => {
+                let mut label = nt.to_string();
+                let mut children: Vec<_> = children
+                    .iter()
+                    .filter_map(|c| Shape::build(c, opts))
+                    .collect();
+                while opts.collapse_unit && children.len() == 1 && children[0].kind == Kind::Node {
+                    let child = children.pop().unwrap();
+                    label += "/";
+                    label += &child.label;
+                    children = child.children;
+                }
+                Some(Shape {
+                    label,
+                    kind: Kind::Node,
+                    children,
+                })
+            }
+        }
+    }
+}
+
+fn dot_node(shape: &Shape, next: &mut usize, out: &mut String) -> usize {
+    let id = *next;
+    *next += 1;
+    let attr = match shape.kind {
+        Kind::Node => "",
+        Kind::Leaf => ", shape=box",
+        Kind::Empty => ", shape=plaintext",
+    };
+    out.push_str(&format!("    n{} [label=\"{}\"{}];\n", id, dot_escape(&shape.label), attr));
+    for child in &shape.children {
+        let child_id = dot_node(child, next, out);
+        out.push_str(&format!("    n{} -> n{};\n", id, child_id));
+    }
+    id
+}
+
+fn dot_escape(s: &str) -> String {
+    let mut ret = String::new();
+    for c in s.chars() {
+        if c == '"' || c == '\\' {
+            ret.push('\\');
+        }
+        ret.push(c);
+    }
+    ret
+}
+
+fn sexpr_node(shape: &Shape, out: &mut String) {
+    if shape.kind != Kind::Node {
+        sexpr_atom(&shape.label, out);
+        return;
+    }
+    out.push('(');
+    sexpr_atom(&shape.label, out);
+    for child in &shape.children {
+        out.push(' ');
+        sexpr_node(child, out);
+    }
+    out.push(')');
+}
+
+fn sexpr_atom(label: &str, out: &mut String) {
+    if label.is_empty() || label.chars().any(|c| c.is_whitespace() || "()\"".contains(c)) {
+        out.push('"');
+        out.push_str(&dot_escape(label));
+        out.push('"');
+    } else {
+        out.push_str(label);
+    }
+}
+
+fn text_children(shape: &Shape, prefix: &str, out: &mut String) {
+    let n = shape.children.len();
+    for (i, child) in shape.children.iter().enumerate() {
+        let last = i + 1 == n;
+        out.push_str(prefix);
+        out.push_str(if last { "`-- " } else { "|-- " });
+        out.push_str(&child.label);
+        out.push('\n');
+        let prefix = format!("{}{}", prefix, if last { "    " } else { "|   " });
+        text_children(child, &prefix, out);
+    }
+}
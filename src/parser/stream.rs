@@ -0,0 +1,130 @@
+use std::collections::VecDeque;
+
+/// A cursor over the input of a parser.
+///
+/// Tokens are pulled on demand, so a driver only holds the tokens it is looking at. Parsers
+/// that need to go back take a `Mark` and either `reset` to it or `release` it; marks nest like
+/// a stack and the stream keeps every token from the oldest outstanding mark onwards.
+pub trait TokenStream {
+    type Token;
+
+    /// The token `n` positions after the cursor, `None` past the end of input.
+    fn peek(&mut self, n: usize) -> Option<&Self::Token>;
+
+    fn forward(&mut self);
+
+    /// Number of tokens consumed so far.
+    fn position(&self) -> usize;
+
+    fn mark(&mut self) -> Mark;
+
+    /// Rewinds the cursor to `mark` and drops the mark.
+    fn reset(&mut self, mark: Mark);
+
+    /// Drops `mark` and keeps the cursor where it is.
+    fn release(&mut self, mark: Mark);
+
+    fn current(&mut self) -> Option<&Self::Token> {
+        self.peek(0)
+    }
+
+    /// Drains the rest of the input into the buffer and returns a copy of it, without moving
+    /// the cursor. Only meant for diagnostics such as parse traces.
+    fn remaining(&mut self) -> Vec<Self::Token>
+    where
+        Self::Token: Clone,
+    {
+        let mut ret = vec![];
+        while let Some(t) = self.peek(ret.len()) {
+            ret.push(t.clone());
+        }
+        ret
+    }
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct Mark(usize);
+
+/// `TokenStream` over any iterator, buffering only what lookahead and marks require.
+pub struct TokenBuffer<I: Iterator> {
+    iter: I,
+    buf: VecDeque<I::Item>,
+    // position of `buf[0]` in the input
+    base: usize,
+    pos: usize,
+    marks: Vec<usize>,
+}
+
+impl<I: Iterator> TokenBuffer<I> {
+    pub fn new<T: IntoIterator<IntoIter = I, Item = I::Item>>(tokens: T) -> Self {
+        TokenBuffer {
+            iter: tokens.into_iter(),
+            buf: VecDeque::new(),
+            base: 0,
+            pos: 0,
+            marks: vec![],
+        }
+    }
+
+    /// Number of tokens currently held in memory.
+    pub fn buffered(&self) -> usize {
+        self.buf.len()
+    }
+
+    fn fill(&mut self, n: usize) -> bool {
+        while self.buf.len() <= self.pos - self.base + n {
+            match self.iter.next() {
+                Some(t) => self.buf.push_back(t),
+                None => return false,
+            }
+        }
+        true
+    }
+
+    fn trim(&mut self) {
+        let keep = self.marks.first().cloned().unwrap_or(self.pos);
+        while self.base < keep && !self.buf.is_empty() {
+            self.buf.pop_front();
+            self.base += 1;
+        }
+    }
+}
+
+impl<I: Iterator> TokenStream for TokenBuffer<I> {
+    type Token = I::Item;
+
+    fn peek(&mut self, n: usize) -> Option<&I::Item> {
+        if self.fill(n) {
+            self.buf.get(self.pos - self.base + n)
+        } else {
+            None
+        }
+    }
+
+    fn forward(&mut self) {
+        if self.fill(0) {
+            self.pos += 1;
+            self.trim();
+        }
+    }
+
+    fn position(&self) -> usize {
+        self.pos
+    }
+
+    fn mark(&mut self) -> Mark {
+        self.marks.push(self.pos);
+        Mark(self.pos)
+    }
+
+    fn reset(&mut self, mark: Mark) {
+        assert_eq!(self.marks.pop(), Some(mark.0), "marks must be dropped in stack order");
+        self.pos = mark.0;
+        self.trim();
+    }
+
+    fn release(&mut self, mark: Mark) {
+        assert_eq!(self.marks.pop(), Some(mark.0), "marks must be dropped in stack order");
+        self.trim();
+    }
+}
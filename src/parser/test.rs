@@ -1,44 +1,9 @@
-use std::collections::{HashSet, BTreeSet, HashMap};
-
 use lazy_static::lazy_static;
 
 use super::*;
+use crate::grammar;
 
 lazy_static! {
-    static ref GRAMMER: Vec<(&'static str, Vec<&'static str>)> = vec! {
-        ("Goal", vec!["Expr"]),
-
-        ("Expr", vec!["Expr", "+", "Term"]),
-        ("Expr", vec!["Expr", "-", "Term"]),
-        ("Expr", vec!["Term"]),
-
-        ("Term", vec!["Term", "*", "Factor"]),
-        ("Term", vec!["Term", "/", "Factor"]),
-        ("Term", vec!["Factor"]),
-
-        ("Factor", vec!["(", "Expr", ")"]),
-        ("Factor", vec!["num"]),
-        ("Factor", vec!["name"])
-    };
-
-    static ref RIGHT_RECURSIVE_GRAMMER: Vec<(&'static str, Vec<&'static str>)> = vec! {
-        ("Goal", vec!["Expr"]),
-
-        ("Expr", vec!["Term", "Expr@"]),
-        ("Expr@", vec!["+", "Term", "Expr@"]),
-        ("Expr@", vec!["-", "Term", "Expr@"]),
-        ("Expr@", vec!["empty@@"]),
-
-        ("Term", vec!["Factor", "Term@"]),
-        ("Term@", vec!["*", "Factor", "Term@"]),
-        ("Term@", vec!["/", "Factor", "Term@"]),
-        ("Term@", vec!["empty@@"]),
-
-        ("Factor", vec!["(", "Expr", ")"]),
-        ("Factor", vec!["num"]),
-        ("Factor", vec!["name"])
-    };
-
     static ref FIRST: Vec<(&'static str, Vec<&'static str>)> = vec! {
         ("Goal", vec!["(", "name", "num"]),
         ("Expr", vec!["(", "name", "num"]),
@@ -57,3 +22,423 @@ lazy_static! {
         ("Factor", vec!["eof@@", "+", "-", "*", "/", ")"]),
     };
 }
+
+fn expr_grammar() -> CFG {
+    grammar! {
+        Goal -> Expr;
+        Expr -> Expr "+" Term | Expr "-" Term | Term;
+        Term -> Term "*" Factor | Term "/" Factor | Factor;
+        Factor -> "(" Expr ")" | "num" | "name";
+    }
+}
+
+// the forked names aren't identifiers, so this one goes through the builder
+fn right_recursive_grammar() -> CFG {
+    let e = |s: &str| match s {
+        "ε" => Element::Empty,
+        _ if s.starts_with(char::is_uppercase) => Element::NT(nt(s)),
+        _ => Element::T(Terminal::new(s)),
+    };
+    let rule = |alternatives: &[&[&str]]| alternatives.iter().map(|a| a.iter().map(|&s| e(s)).collect()).collect();
+    CFG::builder()
+        .rule(nt("Goal"), rule(&[&["Expr"]]))
+        .rule(nt("Expr"), rule(&[&["Term", "Expr@"]]))
+        .rule(nt("Expr@"), rule(&[&["+", "Term", "Expr@"], &["-", "Term", "Expr@"], &["ε"]]))
+        .rule(nt("Term"), rule(&[&["Factor", "Term@"]]))
+        .rule(nt("Term@"), rule(&[&["*", "Factor", "Term@"], &["/", "Factor", "Term@"], &["ε"]]))
+        .rule(nt("Factor"), rule(&[&["(", "Expr", ")"], &["num"], &["name"]]))
+        .build()
+        .unwrap()
+}
+
+fn terminals(ts: &[&str]) -> Vec<Terminal> {
+    ts.iter().map(|&s| Terminal::new(s)).collect()
+}
+
+fn nt(s: &str) -> NonTerminal {
+    NonTerminal::new(s)
+}
+
+fn leaf(s: &str) -> ParseTree {
+    ParseTree::leaf(Terminal::new(s))
+}
+
+// Goal -> Expr -> Term Expr@ for the sentence `num`.
+fn num_tree() -> ParseTree {
+    ParseTree::node(nt("Goal"), vec![
+        ParseTree::node(nt("Expr"), vec![
+            ParseTree::node(nt("Term"), vec![
+                ParseTree::node(nt("Factor"), vec![leaf("num")]),
+                ParseTree::node(nt("Term@"), vec![ParseTree::Empty]),
+            ]),
+            ParseTree::node(nt("Expr@"), vec![ParseTree::Empty]),
+        ]),
+    ])
+}
+
+#[test]
+fn test_render_sexpr() {
+    use render::{to_sexpr, RenderOptions};
+
+    let tree = num_tree();
+    assert_eq!(tree.leaves(), vec![&Terminal::new("num")]);
+
+    let opts = RenderOptions::new();
+    assert_eq!(to_sexpr(&tree, &opts), "(Goal (Expr (Term (Factor num) (Term@ ε)) (Expr@ ε)))");
+
+    let opts = RenderOptions::new().hide_empty(true);
+    assert_eq!(to_sexpr(&tree, &opts), "(Goal (Expr (Term (Factor num) (Term@)) (Expr@)))");
+
+    let opts = RenderOptions::new().hide_empty(true).collapse_unit(true);
+    assert_eq!(to_sexpr(&tree, &opts), "(Goal/Expr (Term (Factor num) (Term@)) (Expr@))");
+
+    // labels that would break the nesting are quoted
+    let tree = ParseTree::node(nt("Factor"), vec![leaf("("), leaf("\"x y\""), leaf(")")]);
+    assert_eq!(to_sexpr(&tree, &opts), r#"(Factor "(" "\"x y\"" ")")"#);
+}
+
+#[test]
+fn test_render_text() {
+    use render::{to_text, RenderOptions};
+
+    let opts = RenderOptions::new().collapse_unit(true);
+    let expected = "\
+Goal/Expr
+|-- Term
+|   |-- Factor
+|   |   `-- num
+|   `-- Term@
+|       `-- ε
+`-- Expr@
+    `-- ε
+";
+    assert_eq!(to_text(&num_tree(), &opts), expected);
+}
+
+#[test]
+fn test_render_dot() {
+    use render::{to_dot, RenderOptions};
+
+    let tree = ParseTree::node(nt("Factor"), vec![leaf("\"x\""), ParseTree::Empty]);
+    let expected = "\
+digraph ParseTree {
+    n0 [label=\"Factor\"];
+    n1 [label=\"\\\"x\\\"\", shape=box];
+    n0 -> n1;
+    n2 [label=\"ε\", shape=plaintext];
+    n0 -> n2;
+}
+";
+    assert_eq!(to_dot(&tree, &RenderOptions::new()), expected);
+}
+
+#[test]
+fn test_token_buffer() {
+    use stream::{TokenBuffer, TokenStream};
+
+    let mut pulled = 0;
+    let mut tokens = TokenBuffer::new((0..10).inspect(|_| pulled += 1));
+    assert_eq!(tokens.current(), Some(&0));
+    assert_eq!(tokens.peek(2), Some(&2));
+    tokens.forward();
+    assert_eq!(tokens.buffered(), 2);
+
+    let mark = tokens.mark();
+    tokens.forward();
+    tokens.forward();
+    tokens.forward();
+    assert_eq!(tokens.current(), Some(&4));
+    assert_eq!(tokens.buffered(), 4);
+    tokens.reset(mark);
+    assert_eq!(tokens.position(), 1);
+    assert_eq!(tokens.current(), Some(&1));
+
+    let mark = tokens.mark();
+    tokens.forward();
+    tokens.release(mark);
+    assert_eq!(tokens.buffered(), 3);
+    assert_eq!(tokens.remaining(), (2..10).collect::<Vec<_>>());
+    assert_eq!(tokens.position(), 2);
+    while tokens.current().is_some() {
+        tokens.forward();
+    }
+    assert_eq!(tokens.peek(0), None);
+    assert_eq!(tokens.position(), 10);
+    drop(tokens);
+    assert_eq!(pulled, 10);
+}
+
+#[test]
+fn test_backtrack_parse() {
+    use backtrack_parse::{backtrack_parse, backtrack_parse_stream};
+    use stream::TokenBuffer;
+
+    // left recursion is fine, the depth of the recursion is bounded by the input
+    let cfg = expr_grammar();
+    let tree = backtrack_parse(&cfg, &terminals(&["num", "-", "(", "name", ")", "*", "num"])).ok().unwrap();
+    let opts = render::RenderOptions::new().collapse_unit(true);
+    assert_eq!(
+        render::to_sexpr(&tree, &opts),
+        "(Goal/Expr (Expr/Term/Factor num) - (Term (Term/Factor \"(\" (Expr/Term/Factor name) \")\") * (Factor num)))"
+    );
+    for s in [&["num", "+"][..], &["(", "num"], &["(", "(", "num", ")"]] {
+        let err = backtrack_parse(&cfg, &terminals(s)).unwrap_err();
+        assert_eq!((err.position, err.found.clone()), (s.len(), None));
+        assert_eq!(err.to_string(), "unexpected end of input");
+    }
+    // `( num` could go on, `( num num` can't
+    let err = backtrack_parse(&cfg, &terminals(&["(", "num", "num", ")"])).unwrap_err();
+    assert_eq!((err.position, err.found.clone()), (2, Some(Terminal::new("num"))));
+    assert_eq!(err.to_string(), "at token 2: unexpected `num`");
+    let err = backtrack_parse(&cfg, &terminals(&[")", "num"])).unwrap_err();
+    assert_eq!(err.position, 0);
+    assert!(backtrack_parse(&cfg, &terminals(&["(", "num"])).is_err());
+    assert!(backtrack_parse(&cfg, &terminals(&[])).is_err());
+
+    let cfg = right_recursive_grammar();
+    let tokens = terminals(&["num", "*", "name", "+", "num"]);
+    let tree = backtrack_parse_stream(&cfg, TokenBuffer::new(tokens.clone())).ok().unwrap();
+    assert_eq!(tree.leaves(), tokens.iter().collect::<Vec<_>>());
+    // past the ε-alternatives of `Expr@` and `Term@`
+    let err = backtrack_parse(&cfg, &terminals(&["num", "*", "name", ")", "num"])).unwrap_err();
+    assert_eq!((err.position, err.found), (3, Some(Terminal::new(")"))));
+}
+
+fn show(cfg: &CFG) -> Vec<String> {
+    cfg.productions.iter().flat_map(|b| &b.productions).map(|p| {
+        let right: Vec<_> = p.right.iter().map(|e| match e {
+            Element::T(t) => t.to_string(),
+            Element::NT(nt) => nt.to_string(),
+            Element::Empty => "ε".to_string(),
+        }).collect();
+        format!("{} -> {}", p.left, right.join(" "))
+    }).collect()
+}
+
+#[test]
+fn test_desugar_ebnf() {
+    use ebnf::{desugar, Ebnf, EbnfBlock, EbnfGrammar, Family};
+
+    // Call -> name ( ( Arg ( , Arg )* )? ) ; Arg -> num+ | name
+    let args = Ebnf::Group(vec![vec![
+        Ebnf::nt("Arg"),
+        Ebnf::Group(vec![vec![Ebnf::t(","), Ebnf::nt("Arg")]]).star(),
+    ]]);
+    let grammar = EbnfGrammar {
+        start: nt("Call"),
+        blocks: vec![
+            EbnfBlock::new(nt("Call"), vec![vec![Ebnf::t("name"), Ebnf::t("("), args.optional(), Ebnf::t(")")]]),
+            EbnfBlock::new(nt("Arg"), vec![vec![Ebnf::t("num").plus()], vec![Ebnf::t("name")]]),
+        ],
+    };
+
+    let cfg = desugar(&grammar, Family::TopDown);
+    assert_eq!(show(&cfg), vec![
+        "Call -> name ( Call@@ )",
+        "Call@ -> , Arg Call@",
+        "Call@ -> ε",
+        "Call@@ -> Arg Call@",
+        "Call@@ -> ε",
+        "Arg -> Arg@",
+        "Arg -> name",
+        "Arg@ -> num Arg@@",
+        "Arg@@ -> num Arg@@",
+        "Arg@@ -> ε",
+    ]);
+
+    let cfg = desugar(&grammar, Family::BottomUp);
+    assert_eq!(show(&cfg)[1], "Call@ -> Call@ , Arg");
+    assert_eq!(show(&cfg)[7], "Arg@ -> Arg@ num");
+    assert_eq!(show(&cfg)[8], "Arg@ -> num");
+    assert_eq!(cfg.non_terminals, vec![nt("Call"), nt("Call@"), nt("Call@@"), nt("Arg"), nt("Arg@")]);
+
+    let input = terminals(&["name", "(", "num", "num", ",", "name", ")"]);
+    for &family in &[Family::TopDown, Family::BottomUp] {
+        let cfg = desugar(&grammar, family);
+        let tree = backtrack_parse::backtrack_parse(&cfg, &input).ok().unwrap();
+        assert_eq!(tree.leaves(), input.iter().collect::<Vec<_>>());
+        assert!(backtrack_parse::backtrack_parse(&cfg, &terminals(&["name", "(", ",", ")"])).is_err());
+    }
+
+    // List -> ( a | ε )*, the empty alternative mustn't become `List@ -> List@`
+    let grammar = EbnfGrammar {
+        start: nt("List"),
+        blocks: vec![EbnfBlock::new(
+            nt("List"),
+            vec![vec![Ebnf::Group(vec![vec![Ebnf::t("a")], vec![Ebnf::Element(Element::Empty)]]).star()]],
+        )],
+    };
+    let cfg = desugar(&grammar, Family::TopDown);
+    assert_eq!(show(&cfg), vec!["List -> List@", "List@ -> a List@", "List@ -> ε"]);
+    let cfg = desugar(&grammar, Family::BottomUp);
+    assert_eq!(show(&cfg), vec!["List -> List@", "List@ -> List@ a", "List@ -> ε"]);
+    assert!(backtrack_parse::backtrack_parse(&cfg, &terminals(&["a", "a"])).is_ok());
+}
+
+#[test]
+fn test_symbol_interning() {
+    use symbol::Symbol;
+
+    let a = Symbol::intern("Expr");
+    assert_eq!(a, Symbol::intern(&String::from("Expr")));
+    assert_ne!(a, Symbol::intern("Expr@"));
+    assert_eq!(a.as_str(), "Expr");
+    assert_eq!(format!("{} {:?}", a, a), "Expr \"Expr\"");
+
+    assert_eq!(nt("Expr").symbol(), a);
+    assert_eq!(nt("Expr").fork(), nt("Expr@"));
+    assert_eq!(NonTerminal::from(a), nt("Expr"));
+    assert_eq!(Terminal::from(Symbol::intern("num")), Terminal::new("num"));
+
+    // by name, not by when they were interned
+    let (z, y) = (Symbol::intern("zz interned first"), Symbol::intern("yy interned second"));
+    assert!(y < z);
+    assert_eq!(z.cmp(&Symbol::intern("zz interned first")), std::cmp::Ordering::Equal);
+}
+
+#[test]
+fn test_validate() {
+    use validate::Diagnostic;
+
+    assert!(expr_grammar().validate().is_ok());
+    assert!(right_recursive_grammar().validate().is_ok());
+
+    let mut cfg = expr_grammar();
+    cfg.start = nt("Program");
+    // Factor -> ( Expr ) | num | name | Call, and no block for Call
+    cfg.productions[3].productions.push(Production::new(nt("Factor"), vec![Element::NT(nt("Call"))]));
+    cfg.productions[3].productions.push(Production::new(nt("Factor"), vec![Element::T(Terminal::new("num"))]));
+    cfg.productions[3].productions.push(Production::new(nt("Term"), vec![Element::T(Terminal::new("name")); 2]));
+    cfg.productions[1].productions.push(Production::new(nt("Expr"), vec![Element::Empty, Element::NT(nt("Term"))]));
+    cfg.productions[1].productions.push(Production::new(nt("Expr"), vec![]));
+    cfg.productions[2].productions.push(Production::new(nt("Term"), vec![Element::T(Terminal::new("Expr"))]));
+    cfg.productions.push(ProdBlock::new(nt("Term"), vec![]));
+
+    let diagnostics = cfg.validate().err().unwrap();
+    let messages: Vec<_> = diagnostics.iter().map(|d| d.to_string()).collect();
+    assert_eq!(messages, vec![
+        "start symbol `Program` has no productions",
+        "`Expr -> ε Term` mixes `Element::Empty` with other elements",
+        "`Expr ->` has an empty right-hand side, use `Element::Empty` for an empty alternative",
+        "`Expr` is used in `Term -> Expr` but is not a declared terminal",
+        "`Call` is used in `Factor -> Call` but has no productions",
+        "`Factor -> num` is listed more than once",
+        "`Term -> name name` is in the block of `Factor`",
+        "`Term` has more than one block",
+        "`Term` has a block without productions",
+        "`Expr` is used both as a terminal and as a nonterminal",
+    ]);
+    assert!(matches!(diagnostics[4], Diagnostic::UndefinedNonTerminal(ref n, _) if n == &nt("Call")));
+    match &diagnostics[6] {
+        Diagnostic::MisplacedProduction(p, n) => assert_eq!((&p.left, n), (&nt("Term"), &nt("Factor"))),
+        d => panic!("{:?}", d),
+    }
+}
+
+#[test]
+fn test_generate() {
+    use backtrack_parse::backtrack_parse;
+    use generate::{covering, sentences, Generator};
+
+    let cfg = expr_grammar();
+    let all = sentences(&cfg, 3);
+    // num, name, ( x ) and x op y
+    assert_eq!(all.len(), 2 + 2 + 4 * 2 * 2);
+    assert_eq!(all[..3], [terminals(&["name"]), terminals(&["num"]), terminals(&["(", "name", ")"])]);
+    assert!(all.iter().all(|s| backtrack_parse(&cfg, s).is_ok()));
+    let longer = sentences(&cfg, 5);
+    assert_eq!(longer[..all.len()], all[..]);
+    assert!(longer[all.len()..].iter().all(|s| s.len() == 5 && backtrack_parse(&cfg, s).is_ok()));
+
+    let mut gen = Generator::new(&cfg, 7);
+    let random: Vec<_> = (0..50).map(|_| gen.random(6).unwrap()).collect();
+    assert!(random.iter().all(|s| backtrack_parse(&cfg, s).is_ok()));
+    assert!(random.iter().any(|s| s.len() > 3));
+    let mut again = Generator::new(&cfg, 7);
+    assert_eq!((0..50).map(|_| again.random(6).unwrap()).collect::<Vec<_>>(), random);
+    // too shallow for any sentence, the lowest derivation is used instead
+    assert_eq!(gen.random(0).unwrap().len(), 1);
+
+    let cover: Vec<Vec<_>> = covering(&cfg)
+        .iter()
+        .map(|s| s.iter().map(|t| t.to_string()).collect())
+        .collect();
+    assert_eq!(cover, vec![
+        vec!["num"],
+        vec!["num", "+", "num"],
+        vec!["num", "-", "num"],
+        vec!["num", "*", "num"],
+        vec!["num", "/", "num"],
+        vec!["(", "num", ")"],
+        vec!["name"],
+    ]);
+
+    // Loop never ends and Lost is never reached
+    let cfg = grammar! {
+        Goal -> "a" Goal | () | Loop;
+        Loop -> "b" Loop;
+        Lost -> "c";
+    };
+    assert_eq!(covering(&cfg), vec![terminals(&["a"])]);
+    assert_eq!(sentences(&cfg, 2), vec![vec![], terminals(&["a"]), terminals(&["a", "a"])]);
+    let mut gen = Generator::new(&cfg, 1);
+    assert!((0..20).all(|_| gen.random(3).unwrap().len() <= 2));
+    let mut cfg = cfg;
+    cfg.start = nt("Loop");
+    assert_eq!(Generator::new(&cfg, 1).random(10), None);
+}
+
+#[test]
+fn test_builder() {
+    use validate::Diagnostic;
+
+    let cfg = expr_grammar();
+    assert_eq!(cfg.start, nt("Goal"));
+    assert_eq!(cfg.non_terminals, vec![nt("Goal"), nt("Expr"), nt("Term"), nt("Factor")]);
+    assert_eq!(cfg.terminals, terminals(&["+", "-", "*", "/", "(", ")", "num", "name"]));
+    assert_eq!(show(&cfg)[..3], ["Goal -> Expr", "Expr -> Expr + Term", "Expr -> Expr - Term"]);
+
+    // the same grammar by hand, with blocks split up and an explicit start
+    let t = |s: &str| Element::T(Terminal::new(s));
+    let n = |s: &str| Element::NT(nt(s));
+    let built = CFG::builder()
+        .start(nt("Goal"))
+        .terminal(Terminal::new("unused"))
+        .rule(nt("Factor"), vec![vec![t("("), n("Expr"), t(")")], vec![t("num")]])
+        .rule(nt("Expr"), vec![vec![n("Expr"), t("+"), n("Factor")], vec![n("Factor")]])
+        .production(nt("Goal"), vec![n("Expr")])
+        .production(nt("Factor"), vec![t("name")])
+        .build()
+        .unwrap();
+    assert_eq!(built.start, nt("Goal"));
+    assert_eq!(built.terminals, terminals(&["unused", "(", ")", "num", "+", "name"]));
+    assert_eq!(show(&built), vec![
+        "Factor -> ( Expr )",
+        "Factor -> num",
+        "Factor -> name",
+        "Expr -> Expr + Factor",
+        "Expr -> Factor",
+        "Goal -> Expr",
+    ]);
+
+    let errors = CFG::builder()
+        .start(nt("Goal"))
+        .production(nt("Expr"), vec![n("Term")])
+        .build()
+        .err()
+        .unwrap();
+    let messages: Vec<_> = errors.iter().map(|d| d.to_string()).collect();
+    assert_eq!(messages, vec![
+        "start symbol `Goal` has no productions",
+        "`Term` is used in `Expr -> Term` but has no productions",
+    ]);
+    assert!(matches!(CFG::builder().build().err().unwrap()[..], [Diagnostic::NoProductions]));
+}
+
+#[test]
+#[should_panic(expected = "`Term` is used in `Expr -> Expr + Term` but has no productions")]
+fn test_grammar_macro_checks() {
+    grammar! {
+        Expr -> Expr "+" Term | "num"
+    };
+}
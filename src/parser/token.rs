@@ -0,0 +1,70 @@
+use std::fmt;
+
+use crate::parser::Terminal;
+
+/// Where a token is in the source: a byte range, and the line and column it starts at,
+/// both counted from 1, columns in characters.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// A terminal as it appears in the input.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Token {
+    pub kind: Terminal,
+    pub lexeme: String,
+    pub span: Span,
+}
+
+impl Token {
+    pub fn new<S: Into<String>>(kind: Terminal, lexeme: S, span: Span) -> Self {
+        Token {
+            kind,
+            lexeme: lexeme.into(),
+            span,
+        }
+    }
+}
+
+/// What parsers take as input. They only look at the kind; the rest is carried into parse
+/// tree leaves and errors.
+pub trait Kind {
+    fn kind(&self) -> &Terminal;
+
+    fn span(&self) -> Option<Span> {
+        None
+    }
+}
+
+impl Kind for Terminal {
+    fn kind(&self) -> &Terminal {
+        self
+    }
+}
+
+impl Kind for Token {
+    fn kind(&self) -> &Terminal {
+        &self.kind
+    }
+
+    fn span(&self) -> Option<Span> {
+        Some(self.span)
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// The lexeme, which is what a parse tree leaf should read as.
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.lexeme)
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::parser::symbol::Symbol;
+use crate::parser::{Element, NonTerminal, Production, Terminal, CFG};
+
+/// A problem found by `CFG::validate`.
+#[derive(Debug, Clone)]
+pub enum Diagnostic {
+    /// Nothing to take a start symbol from, see `CFGBuilder::build`.
+    NoProductions,
+    /// The start symbol has no block.
+    MissingStart(NonTerminal),
+    /// A block without any productions.
+    EmptyBlock(NonTerminal),
+    /// A second block for a nonterminal that already has one.
+    DuplicateBlock(NonTerminal),
+    /// A nonterminal used on a right-hand side that has no block.
+    UndefinedNonTerminal(NonTerminal, Production),
+    /// A terminal used on a right-hand side that is missing from `CFG::terminals`.
+    UndeclaredTerminal(Terminal, Production),
+    /// A name used both as a terminal and as a nonterminal.
+    Ambiguous(Symbol),
+    /// The same production listed twice in a block.
+    DuplicateProduction(Production),
+    /// A right-hand side that is empty, or has `Element::Empty` next to other elements.
+    MisplacedEmpty(Production),
+    /// A production in the block of another nonterminal than its left-hand side.
+    MisplacedProduction(Production, NonTerminal),
+}
+
+impl CFG {
+    /// Checks that the grammar is well formed, reporting every problem found in grammar order.
+    pub fn validate(&self) -> Result<(), Vec<Diagnostic>> {
+        let mut ret = vec![];
+        let defined: HashSet<_> = self.productions.iter().map(|b| b.left.symbol()).collect();
+        let declared: HashSet<_> = self.terminals.iter().map(|t| t.symbol()).collect();
+        if !defined.contains(&self.start.symbol()) {
+            ret.push(Diagnostic::MissingStart(self.start.clone()));
+        }
+
+        let mut seen_blocks = HashSet::new();
+        let mut as_terminal = vec![];
+        let mut as_non_terminal: Vec<_> = self.non_terminals.iter().map(|nt| nt.symbol()).collect();
+        as_non_terminal.extend(self.productions.iter().map(|b| b.left.symbol()));
+        for block in &self.productions {
+            if !seen_blocks.insert(block.left.symbol()) {
+                ret.push(Diagnostic::DuplicateBlock(block.left.clone()));
+            }
+            if block.productions.is_empty() {
+                ret.push(Diagnostic::EmptyBlock(block.left.clone()));
+            }
+            for (i, prod) in block.productions.iter().enumerate() {
+                if prod.left != block.left {
+                    ret.push(Diagnostic::MisplacedProduction(prod.clone(), block.left.clone()));
+                }
+                if block.productions[..i].iter().any(|p| p.right == prod.right) {
+                    ret.push(Diagnostic::DuplicateProduction(prod.clone()));
+                }
+                if prod.right.is_empty() || (prod.right.len() > 1 && prod.right.contains(&Element::Empty)) {
+                    ret.push(Diagnostic::MisplacedEmpty(prod.clone()));
+                }
+                for e in &prod.right {
+                    match e {
+                        Element::NT(nt) => {
+                            as_non_terminal.push(nt.symbol());
+                            if !defined.contains(&nt.symbol()) {
+                                ret.push(Diagnostic::UndefinedNonTerminal(nt.clone(), prod.clone()));
+                            }
+                        }
+                        Element::T(t) => {
+                            as_terminal.push(t.symbol());
+                            if !declared.contains(&t.symbol()) {
+                                ret.push(Diagnostic::UndeclaredTerminal(t.clone(), prod.clone()));
+                            }
+                        }
+                        Element::Empty => {}
+                    }
+                }
+            }
+        }
+
+        let as_non_terminal: HashSet<_> = as_non_terminal.into_iter().collect();
+        let mut reported = HashSet::new();
+        for sym in self.terminals.iter().map(|t| t.symbol()).chain(as_terminal) {
+            if as_non_terminal.contains(&sym) && reported.insert(sym) {
+                ret.push(Diagnostic::Ambiguous(sym));
+            }
+        }
+
+        if ret.is_empty() {
+            Ok(())
+        } else {
+            Err(ret)
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Diagnostic::NoProductions => write!(f, "the grammar has no productions"),
+            Diagnostic::MissingStart(nt) => write!(f, "start symbol `{}` has no productions", nt),
+            Diagnostic::EmptyBlock(nt) => write!(f, "`{}` has a block without productions", nt),
+            Diagnostic::DuplicateBlock(nt) => write!(f, "`{}` has more than one block", nt),
+            Diagnostic::UndefinedNonTerminal(nt, prod) => {
+                write!(f, "`{}` is used in `{}` but has no productions", nt, prod)
+            }
+            Diagnostic::UndeclaredTerminal(t, prod) => {
+                write!(f, "`{}` is used in `{}` but is not a declared terminal", t, prod)
+            }
+            Diagnostic::Ambiguous(sym) => {
+                write!(f, "`{}` is used both as a terminal and as a nonterminal", sym)
+            }
+            Diagnostic::DuplicateProduction(prod) => write!(f, "`{}` is listed more than once", prod),
+            Diagnostic::MisplacedEmpty(prod) if prod.right.is_empty() => write!(
+                f,
+                "`{}` has an empty right-hand side, use `Element::Empty` for an empty alternative",
+                prod
+            ),
+            Diagnostic::MisplacedEmpty(prod) => {
+                write!(f, "`{}` mixes `Element::Empty` with other elements", prod)
+            }
+            Diagnostic::MisplacedProduction(prod, nt) => write!(f, "`{}` is in the block of `{}`", prod, nt),
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use crate::regex::{ranges, Regex};
+
+/// Whether `regex` matches all of `input`, by trying every way of splitting the input among
+/// its parts. Exponential in the worst case; it is the reference the automata are checked
+/// against, not something to scan with.
+pub fn is_match(regex: &Regex, input: &str) -> bool {
+    let input: Vec<char> = input.chars().collect();
+    match_here(regex, &input, &mut |rest| rest.is_empty())
+}
+
+// whether a prefix of `input` matches `regex` with `k` accepting what is left after it
+fn match_here(regex: &Regex, input: &[char], k: &mut dyn FnMut(&[char]) -> bool) -> bool {
+    match regex {
+        Regex::Empty => k(input),
+        Regex::Char(c) => input.first() == Some(c) && k(&input[1..]),
+        Regex::Class(ranges) => input.first().is_some_and(|&c| ranges::contains(ranges, c)) && k(&input[1..]),
+        Regex::Alt(alternatives) => alternatives.iter().any(|r| match_here(r, input, k)),
+        Regex::Concat(parts) => match_seq(parts, input, k),
+        Regex::Star(r) => match_star(r, input, k),
+    }
+}
+
+fn match_seq(parts: &[Regex], input: &[char], k: &mut dyn FnMut(&[char]) -> bool) -> bool {
+    match parts.split_first() {
+        None => k(input),
+        Some((first, rest)) => match_here(first, input, &mut |after| match_seq(rest, after, k)),
+    }
+}
+
+// zero repetitions, or one that consumes something followed by more; an iteration matching
+// the empty string can't get anywhere the zero case doesn't
+fn match_star(r: &Regex, input: &[char], k: &mut dyn FnMut(&[char]) -> bool) -> bool {
+    k(input) || match_here(r, input, &mut |after| after.len() < input.len() && match_star(r, after, k))
+}
@@ -0,0 +1,140 @@
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+
+use crate::regex::nfa::{self, Label, Nfa};
+use crate::regex::ranges;
+
+pub type StateId = usize;
+
+#[derive(Debug, Clone, Default)]
+pub struct State {
+    /// Sorted, disjoint ranges of characters and the state each leads to. Adjacent ranges
+    /// lead to different states, so every DFA has one way of writing its transitions.
+    pub transitions: Vec<(char, char, StateId)>,
+    /// The NFA states this state stands for.
+    pub nfa_states: BTreeSet<nfa::StateId>,
+    /// Token kinds of the accepting NFA states among them.
+    pub kinds: BTreeSet<usize>,
+}
+
+impl State {
+    /// The token kind recognized here. When several kinds match, the lowest wins, so the
+    /// order of the rules is their priority.
+    pub fn accept(&self) -> Option<usize> {
+        self.kinds.iter().next().cloned()
+    }
+}
+
+/// A deterministic automaton. A character without a transition leads to the implicit dead
+/// state.
+#[derive(Debug, Clone)]
+pub struct Dfa {
+    pub states: Vec<State>,
+    pub start: StateId,
+}
+
+impl Dfa {
+    /// Subset construction: every state is the ε-closure of the NFA states reachable on some
+    /// input, numbered in the order they are discovered. Inputs are ranges of characters no
+    /// transition of the NFA tells apart, so classes cost a handful of steps, not one per
+    /// character.
+    pub fn from_nfa(nfa: &Nfa) -> Dfa {
+        let alphabet = alphabet(nfa);
+        let start = nfa.epsilon_closure(&Some(nfa.start).into_iter().collect());
+        let mut index = HashMap::new();
+        index.insert(start.clone(), 0);
+        let mut subsets = vec![start];
+        let mut transitions = vec![];
+        let mut next = 0;
+        while next < subsets.len() {
+            let mut edges = vec![];
+            for &(lo, hi) in &alphabet {
+                let to = nfa.epsilon_closure(&nfa.step(&subsets[next], lo));
+                if to.is_empty() {
+                    continue;
+                }
+                let len = subsets.len();
+                let state = *index.entry(to.clone()).or_insert(len);
+                if state == len {
+                    subsets.push(to);
+                }
+                ranges::push(&mut edges, lo, hi, state);
+            }
+            transitions.push(edges);
+            next += 1;
+        }
+
+        let states = subsets
+            .into_iter()
+            .zip(transitions)
+            .map(|(nfa_states, transitions)| State {
+                kinds: nfa_states.iter().filter_map(|&s| nfa.states[s].accept).collect(),
+                nfa_states,
+                transitions,
+            })
+            .collect();
+        Dfa { states, start: 0 }
+    }
+
+    pub fn next(&self, state: StateId, c: char) -> Option<StateId> {
+        ranges::lookup(&self.states[state].transitions, c)
+    }
+
+    /// The state reached after all of `input`, `None` if that is the dead state.
+    pub fn run(&self, input: &str) -> Option<StateId> {
+        input.chars().try_fold(self.start, |s, c| self.next(s, c))
+    }
+
+    pub fn matches(&self, input: &str) -> bool {
+        self.accepts(input).is_some()
+    }
+
+    /// A shortest input the two automata recognize as different token kinds, or that only
+    /// one of them accepts; `None` if there is none. Explores the product automaton breadth
+    /// first, with the dead state standing in for missing transitions.
+    pub fn distinguish(&self, other: &Dfa) -> Option<String> {
+        let accept = |dfa: &Dfa, s: Option<StateId>| s.and_then(|s| dfa.states[s].accept());
+        let start = (Some(self.start), Some(other.start));
+        let mut seen = HashSet::new();
+        seen.insert(start);
+        let mut work = VecDeque::new();
+        work.push_back((start, String::new()));
+        while let Some(((a, b), input)) = work.pop_front() {
+            if accept(self, a) != accept(other, b) {
+                return Some(input);
+            }
+            let edges = (a.into_iter().flat_map(|a| &self.states[a].transitions))
+                .chain(b.into_iter().flat_map(|b| &other.states[b].transitions));
+            let inputs = ranges::partition(edges.map(|&(lo, hi, _)| (lo, hi)));
+            for (c, _) in inputs {
+                let next = (a.and_then(|a| self.next(a, c)), b.and_then(|b| other.next(b, c)));
+                if seen.insert(next) {
+                    work.push_back((next, format!("{}{}", input, c)));
+                }
+            }
+        }
+        None
+    }
+
+    pub fn equivalent(&self, other: &Dfa) -> bool {
+        self.distinguish(other).is_none()
+    }
+
+    /// The token kind all of `input` is recognized as.
+    pub fn accepts(&self, input: &str) -> Option<usize> {
+        self.run(input).and_then(|s| self.states[s].accept())
+    }
+}
+
+// the characters some transition of the NFA consumes, in ranges that each transition takes
+// either all or none of
+fn alphabet(nfa: &Nfa) -> Vec<(char, char)> {
+    let mut consumed = vec![];
+    for (label, _) in nfa.states.iter().flat_map(|s| &s.transitions) {
+        match label {
+            Label::Epsilon => {}
+            Label::Char(c) => consumed.push((*c, *c)),
+            Label::Class(ranges) => consumed.extend(ranges),
+        }
+    }
+    ranges::partition(consumed)
+}
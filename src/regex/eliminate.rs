@@ -0,0 +1,90 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::regex::dfa::{Dfa, State};
+use crate::regex::simplify::{alt, concat, star};
+use crate::regex::Regex;
+
+/// What `dfa` accepts, whatever the token kind, as a regex; `None` if it accepts nothing.
+pub fn to_regex(dfa: &Dfa) -> Option<Regex> {
+    eliminate(dfa, |s| s.accept().is_some())
+}
+
+/// The strings `dfa` recognizes as token kind `kind`, i.e. those of the rule for `kind` that
+/// no rule before it takes.
+pub fn kind_to_regex(dfa: &Dfa, kind: usize) -> Option<Regex> {
+    eliminate(dfa, |s| s.accept() == Some(kind))
+}
+
+// State elimination (EaC section 2.6.1): the DFA becomes a graph with regexes on its edges,
+// plus a new start and a new final state joined to it by empty strings. Removing a state `q`
+// reroutes every path `p -> q -> r` to an edge `p -> r` labelled `a b* c`, with `b` the loop on
+// `q`, until only the edge from the new start to the new final state is left.
+fn eliminate<F: Fn(&State) -> bool>(dfa: &Dfa, accepting: F) -> Option<Regex> {
+    let n = dfa.states.len();
+    let (start, end) = (n, n + 1);
+    // edges by source and then target, and the sources of the edges into each state
+    let mut out: Vec<BTreeMap<usize, Regex>> = vec![BTreeMap::new(); n + 2];
+    let mut into: Vec<BTreeSet<usize>> = vec![BTreeSet::new(); n + 2];
+    let mut add = |out: &mut Vec<BTreeMap<usize, Regex>>, from: usize, to: usize, r: Regex| {
+        into[to].insert(from);
+        let label = match out[from].remove(&to) {
+            Some(old) => alt(vec![old, r]),
+            None => r,
+        };
+        out[from].insert(to, label);
+    };
+    for (p, state) in dfa.states.iter().enumerate() {
+        for &(lo, hi, q) in &state.transitions {
+            add(&mut out, p, q, alt(vec![Regex::class(vec![(lo, hi)])]));
+        }
+        if accepting(state) {
+            add(&mut out, p, end, Regex::Empty);
+        }
+    }
+    add(&mut out, start, dfa.start, Regex::Empty);
+
+    // states from which the final state can't be reached only add dead ends
+    let mut live = BTreeSet::new();
+    let mut work = vec![end];
+    while let Some(s) = work.pop() {
+        for &p in &into[s] {
+            if live.insert(p) {
+                work.push(p);
+            }
+        }
+    }
+    let mut remaining: BTreeSet<usize> = (0..n).filter(|s| live.contains(s)).collect();
+    for edges in out.iter_mut() {
+        edges.retain(|to, _| *to >= n || remaining.contains(to));
+    }
+    for sources in into.iter_mut() {
+        sources.retain(|from| *from >= n || remaining.contains(from));
+    }
+
+    // the state with the fewest paths through it first keeps the labels small
+    while let Some(&q) = remaining.iter().min_by_key(|&&q| into[q].len() * out[q].len()) {
+        remaining.remove(&q);
+        let looped = match out[q].remove(&q) {
+            Some(r) => star(r),
+            None => Regex::Empty,
+        };
+        into[q].remove(&q);
+        let targets = std::mem::take(&mut out[q]);
+        for &r in targets.keys() {
+            into[r].remove(&q);
+        }
+        for p in std::mem::take(&mut into[q]) {
+            let before = out[p].remove(&q).unwrap();
+            for (&r, after) in &targets {
+                let through = concat(vec![before.clone(), looped.clone(), after.clone()]);
+                let label = match out[p].remove(&r) {
+                    Some(old) => alt(vec![old, through]),
+                    None => through,
+                };
+                out[p].insert(r, label);
+                into[r].insert(p);
+            }
+        }
+    }
+    out[start].remove(&end)
+}
@@ -0,0 +1,265 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+
+use crate::regex::dfa::{Dfa, State, StateId};
+use crate::regex::ranges;
+
+/// Hopcroft's partition refinement. States start out grouped by the token kind they accept,
+/// so states recognizing different tokens are never merged, and groups are split until every
+/// character takes all states of a group into the same group. One character stands for each
+/// range of them that no transition tells apart. States that can't reach an
+/// accepting state end up with the dead state and are dropped.
+pub fn hopcroft(dfa: &Dfa) -> Dfa {
+    let n = dfa.states.len();
+    // the implicit dead state is state `n`
+    let dead = n;
+    let alphabet: Vec<char> = ranges::partition(edges(dfa)).into_iter().map(|(lo, _)| lo).collect();
+    let mut preimage: HashMap<(char, StateId), Vec<StateId>> = HashMap::new();
+    for s in 0..=n {
+        for &c in &alphabet {
+            let to = if s == dead { dead } else { dfa.next(s, c).unwrap_or(dead) };
+            preimage.entry((c, to)).or_default().push(s);
+        }
+    }
+
+    let mut initial: BTreeMap<Option<usize>, Vec<StateId>> = BTreeMap::new();
+    for s in 0..=n {
+        let kind = if s == dead { None } else { dfa.states[s].accept() };
+        initial.entry(kind).or_default().push(s);
+    }
+    let mut blocks: Vec<Vec<StateId>> = initial.into_values().collect();
+    let mut block_of = vec![0; n + 1];
+    for (b, block) in blocks.iter().enumerate() {
+        for &s in block {
+            block_of[s] = b;
+        }
+    }
+    let mut work: Vec<usize> = (0..blocks.len()).collect();
+    let mut in_work = vec![true; blocks.len()];
+
+    while let Some(splitter) = work.pop() {
+        in_work[splitter] = false;
+        let members = blocks[splitter].clone();
+        for &c in &alphabet {
+            // states that go into the splitter on `c`, by the block they are in
+            let mut hit: BTreeMap<usize, Vec<StateId>> = BTreeMap::new();
+            for &to in &members {
+                for &s in preimage.get(&(c, to)).into_iter().flatten() {
+                    hit.entry(block_of[s]).or_default().push(s);
+                }
+            }
+            for (b, inside) in hit {
+                if inside.len() == blocks[b].len() {
+                    continue;
+                }
+                let inside: BTreeSet<_> = inside.into_iter().collect();
+                let (moved, kept): (Vec<_>, Vec<_>) = blocks[b].iter().partition(|s| inside.contains(s));
+                let new = blocks.len();
+                for &s in &moved {
+                    block_of[s] = new;
+                }
+                blocks[b] = kept;
+                blocks.push(moved);
+                in_work.push(false);
+                let add = if in_work[b] || blocks[new].len() <= blocks[b].len() { new } else { b };
+                if !in_work[add] {
+                    in_work[add] = true;
+                    work.push(add);
+                }
+            }
+        }
+    }
+
+    quotient(dfa, &block_of, block_of[dead])
+}
+
+/// The automaton over the groups of `block_of`, leaving out the `dead` group and numbering
+/// the rest in breadth-first order from the start.
+pub(crate) fn quotient(dfa: &Dfa, block_of: &[usize], dead: usize) -> Dfa {
+    let mut number = HashMap::new();
+    let mut order = VecDeque::new();
+    let mut states: Vec<State> = vec![];
+    let start = block_of[dfa.start];
+    if start != dead {
+        number.insert(start, 0);
+        order.push_back(dfa.start);
+        states.push(State::default());
+    }
+    // one member of each group is enough to find the group's transitions
+    while let Some(s) = order.pop_front() {
+        let from = number[&block_of[s]];
+        for &(lo, hi, to) in &dfa.states[s].transitions {
+            let b = block_of[to];
+            if b == dead {
+                continue;
+            }
+            let len = number.len();
+            let id = *number.entry(b).or_insert(len);
+            if id == len {
+                order.push_back(to);
+                states.push(State::default());
+            }
+            ranges::push(&mut states[from].transitions, lo, hi, id);
+        }
+    }
+    for (s, state) in dfa.states.iter().enumerate() {
+        if let Some(&id) = number.get(&block_of[s]) {
+            states[id].nfa_states.extend(&state.nfa_states);
+            states[id].kinds.extend(&state.kinds);
+        }
+    }
+    if states.is_empty() {
+        states.push(State::default());
+    }
+    Dfa { states, start: 0 }
+}
+
+// the ranges of characters of all transitions
+fn edges(dfa: &Dfa) -> impl Iterator<Item = (char, char)> + '_ {
+    dfa.states.iter().flat_map(|s| &s.transitions).map(|&(lo, hi, _)| (lo, hi))
+}
+
+// a range of input characters no transition tells apart, or the marker for accepting a token
+// kind at the end of the input
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+enum Symbol {
+    Chars(char, char),
+    Accept(usize),
+}
+
+// nondeterministic, without ε-transitions, possibly with several start states
+struct Automaton {
+    edges: Vec<BTreeMap<Symbol, BTreeSet<usize>>>,
+    starts: BTreeSet<usize>,
+    finals: BTreeSet<usize>,
+}
+
+/// Brzozowski's algorithm: determinizing the reversed automaton, and then doing it again,
+/// yields the minimal one. Token kinds are kept apart by ending every accepted input with a
+/// marker for its kind, which the result turns back into accepting states.
+pub fn brzozowski(dfa: &Dfa) -> Dfa {
+    let n = dfa.states.len();
+    let alphabet = ranges::partition(edges(dfa));
+    let mut edges = vec![BTreeMap::new(); n + 1];
+    for (s, state) in dfa.states.iter().enumerate() {
+        for &(lo, hi, to) in &state.transitions {
+            for &(a, b) in alphabet.iter().filter(|&&(a, b)| lo <= a && b <= hi) {
+                edges[s].insert(Symbol::Chars(a, b), Some(to).into_iter().collect());
+            }
+        }
+        if let Some(kind) = state.accept() {
+            edges[s].insert(Symbol::Accept(kind), Some(n).into_iter().collect());
+        }
+    }
+    let marked = Automaton {
+        edges,
+        starts: Some(dfa.start).into_iter().collect(),
+        finals: Some(n).into_iter().collect(),
+    };
+    let min = determinize(&reverse(&determinize(&reverse(&marked))));
+
+    // the final state is only reached through markers, number the others from the start
+    let start = *min.starts.iter().next().unwrap();
+    let mut number = HashMap::new();
+    number.insert(start, 0);
+    let mut order = VecDeque::new();
+    order.push_back(start);
+    let mut states = vec![State::default()];
+    while let Some(s) = order.pop_front() {
+        let from = number[&s];
+        for (&sym, to) in &min.edges[s] {
+            let to = *to.iter().next().unwrap();
+            match sym {
+                Symbol::Accept(kind) => {
+                    states[from].kinds.insert(kind);
+                }
+                Symbol::Chars(lo, hi) => {
+                    let len = number.len();
+                    let id = *number.entry(to).or_insert(len);
+                    if id == len {
+                        order.push_back(to);
+                        states.push(State::default());
+                    }
+                    ranges::push(&mut states[from].transitions, lo, hi, id);
+                }
+            }
+        }
+    }
+    let mut ret = Dfa { states, start: 0 };
+    merge_details(dfa, &mut ret);
+    ret
+}
+
+fn reverse(a: &Automaton) -> Automaton {
+    let mut edges = vec![BTreeMap::new(); a.edges.len()];
+    for (s, out) in a.edges.iter().enumerate() {
+        for (&sym, targets) in out {
+            for &to in targets {
+                edges[to].entry(sym).or_insert_with(BTreeSet::new).insert(s);
+            }
+        }
+    }
+    Automaton {
+        edges,
+        starts: a.finals.clone(),
+        finals: a.starts.clone(),
+    }
+}
+
+fn determinize(a: &Automaton) -> Automaton {
+    let mut index = HashMap::new();
+    index.insert(a.starts.clone(), 0);
+    let mut subsets = vec![a.starts.clone()];
+    let mut edges = vec![];
+    let mut next = 0;
+    while next < subsets.len() {
+        let mut out: BTreeMap<Symbol, BTreeSet<usize>> = BTreeMap::new();
+        for &s in &subsets[next] {
+            for (&sym, targets) in &a.edges[s] {
+                out.entry(sym).or_default().extend(targets);
+            }
+        }
+        let mut det = BTreeMap::new();
+        for (sym, to) in out {
+            let len = subsets.len();
+            let id = *index.entry(to.clone()).or_insert(len);
+            if id == len {
+                subsets.push(to);
+            }
+            det.insert(sym, Some(id).into_iter().collect());
+        }
+        edges.push(det);
+        next += 1;
+    }
+    let finals = subsets
+        .iter()
+        .enumerate()
+        .filter(|(_, set)| !set.is_disjoint(&a.finals))
+        .map(|(i, _)| i)
+        .collect();
+    Automaton {
+        edges,
+        starts: Some(0).into_iter().collect(),
+        finals,
+    }
+}
+
+// walks `original` and its equivalent `min` in step, giving every state of `min` the NFA
+// states and token kinds of the states it replaces
+fn merge_details(original: &Dfa, min: &mut Dfa) {
+    let mut seen = BTreeSet::new();
+    let mut work = vec![(original.start, min.start)];
+    while let Some((s, m)) = work.pop() {
+        if !seen.insert((s, m)) {
+            continue;
+        }
+        let state = &original.states[s];
+        min.states[m].nfa_states.extend(&state.nfa_states);
+        min.states[m].kinds.extend(&state.kinds);
+        for &(c, _, to) in &state.transitions {
+            // no counterpart means `to` can't reach an accepting state
+            if let Some(m_to) = min.next(m, c) {
+                work.push((to, m_to));
+            }
+        }
+    }
+}
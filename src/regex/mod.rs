@@ -0,0 +1,167 @@
+pub mod backtrack;
+pub mod dfa;
+pub mod eliminate;
+pub mod minimize;
+pub mod nfa;
+pub mod parse;
+pub mod ranges;
+mod simplify;
+
+use std::fmt;
+
+#[cfg(test)]
+mod test;
+
+/// A regular expression, as `exercises/re.cfg` structures it: alternation (`or`) binds
+/// loosest, then concatenation (`and`), then closure.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum Regex {
+    /// `()`, the empty string.
+    Empty,
+    Char(char),
+    /// `[a-z_]`, the characters of sorted, disjoint and non-adjacent ranges, more than one.
+    Class(Vec<(char, char)>),
+    /// `a|b|c`, at least two alternatives.
+    Alt(Vec<Regex>),
+    /// `abc`, at least two parts.
+    Concat(Vec<Regex>),
+    /// `a*`
+    Star(Box<Regex>),
+}
+
+impl Regex {
+    /// Alternation of `alternatives`, with nested alternations flattened into it.
+    pub fn alt(alternatives: Vec<Regex>) -> Regex {
+        let mut flat = vec![];
+        for r in alternatives {
+            match r {
+                Regex::Alt(inner) => flat.extend(inner),
+                r => flat.push(r),
+            }
+        }
+        if flat.len() == 1 {
+            flat.pop().unwrap()
+        } else {
+            Regex::Alt(flat)
+        }
+    }
+
+    /// Concatenation of `parts`, with nested concatenations flattened into it and empty
+    /// strings left out.
+    pub fn concat(parts: Vec<Regex>) -> Regex {
+        let mut flat = vec![];
+        for r in parts {
+            match r {
+                Regex::Concat(inner) => flat.extend(inner),
+                Regex::Empty => {}
+                r => flat.push(r),
+            }
+        }
+        match flat.len() {
+            0 => Regex::Empty,
+            1 => flat.pop().unwrap(),
+            _ => Regex::Concat(flat),
+        }
+    }
+
+    /// Any character in one of `ranges`, which must hold at least one.
+    pub fn class(ranges: Vec<(char, char)>) -> Regex {
+        let ranges = ranges::normalize(ranges);
+        assert!(!ranges.is_empty(), "a class matches some character");
+        match ranges[..] {
+            [(lo, hi)] if lo == hi => Regex::Char(lo),
+            _ => Regex::Class(ranges),
+        }
+    }
+
+    pub fn star(self) -> Regex {
+        Regex::Star(Box::new(self))
+    }
+
+    // 0 for alternation, 1 for concatenation, 2 for what binds tighter
+    fn precedence(&self) -> u8 {
+        match self {
+            Regex::Alt(_) => 0,
+            Regex::Concat(_) => 1,
+            _ => 2,
+        }
+    }
+
+    fn fmt_at(&self, f: &mut fmt::Formatter, precedence: u8) -> fmt::Result {
+        if self.precedence() < precedence {
+            write!(f, "(")?;
+            self.fmt_at(f, 0)?;
+            return write!(f, ")");
+        }
+        match self {
+            Regex::Empty => write!(f, "()"),
+            Regex::Char(c) => write_char(f, *c),
+            Regex::Class(ranges) => write_class(f, ranges),
+            Regex::Alt(alternatives) => {
+                for (i, r) in alternatives.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "|")?;
+                    }
+                    r.fmt_at(f, 1)?;
+                }
+                Ok(())
+            }
+            Regex::Concat(parts) => parts.iter().try_for_each(|r| r.fmt_at(f, 2)),
+            Regex::Star(r) => {
+                r.fmt_at(f, 2)?;
+                write!(f, "*")
+            }
+        }
+    }
+}
+
+fn write_char(f: &mut fmt::Formatter, c: char) -> fmt::Result {
+    match c {
+        '\n' => write!(f, "\\n"),
+        '\t' => write!(f, "\\t"),
+        '\r' => write!(f, "\\r"),
+        c if parse::is_meta(c) => write!(f, "\\{}", c),
+        c if c.is_control() => write!(f, "\\u{{{:x}}}", c as u32),
+        c => write!(f, "{}", c),
+    }
+}
+
+// the predefined classes by name, or a bracketed class, negated if that takes fewer ranges
+fn write_class(f: &mut fmt::Formatter, ranges: &[(char, char)]) -> fmt::Result {
+    let named = [(ranges::DOT, "."), (ranges::DIGIT, "\\d"), (ranges::WORD, "\\w"), (ranges::SPACE, "\\s")];
+    for (class, name) in named {
+        if ranges == class {
+            return write!(f, "{}", name);
+        }
+    }
+    let negated = ranges::negate(ranges);
+    let (ranges, caret) = if !negated.is_empty() && negated.len() < ranges.len() { (&negated[..], "^") } else { (ranges, "") };
+    write!(f, "[{}", caret)?;
+    for &(lo, hi) in ranges {
+        write_class_char(f, lo)?;
+        if lo == hi {
+            continue;
+        }
+        if ranges::succ(lo) != Some(hi) {
+            write!(f, "-")?;
+        }
+        write_class_char(f, hi)?;
+    }
+    write!(f, "]")
+}
+
+fn write_class_char(f: &mut fmt::Formatter, c: char) -> fmt::Result {
+    match c {
+        '\\' | ']' | '-' | '^' => write!(f, "\\{}", c),
+        // other metacharacters stand for themselves in a class
+        c if parse::is_meta(c) => write!(f, "{}", c),
+        c => write_char(f, c),
+    }
+}
+
+/// Concrete syntax that parses back to the same regex, with as few parentheses as possible.
+impl fmt::Display for Regex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_at(f, 0)
+    }
+}
@@ -0,0 +1,159 @@
+use std::collections::BTreeSet;
+
+use crate::regex::Regex;
+
+pub type StateId = usize;
+
+/// What a transition consumes.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum Label {
+    Epsilon,
+    Char(char),
+    /// Any character in one of the inclusive ranges.
+    Class(Vec<(char, char)>),
+}
+
+impl Label {
+    pub fn matches(&self, c: char) -> bool {
+        match self {
+            Label::Epsilon => false,
+            Label::Char(x) => *x == c,
+            Label::Class(ranges) => ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct State {
+    pub transitions: Vec<(Label, StateId)>,
+    /// The kind of token recognized on reaching this state, `None` if it isn't accepting.
+    pub accept: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Nfa {
+    pub states: Vec<State>,
+    pub start: StateId,
+}
+
+impl Nfa {
+    /// Thompson's construction: one start and one accepting state per subexpression, glued
+    /// together with ε-transitions. The accepting state recognizes token kind 0.
+    pub fn thompson(regex: &Regex) -> Nfa {
+        let mut nfa = Nfa {
+            states: vec![],
+            start: 0,
+        };
+        let (start, end) = nfa.fragment(regex);
+        nfa.start = start;
+        nfa.states[end].accept = Some(0);
+        nfa
+    }
+
+    /// One automaton for several regexes, accepting token kind `i` where `regexes[i]` matches.
+    pub fn union(regexes: &[Regex]) -> Nfa {
+        let mut nfa = Nfa {
+            states: vec![State::default()],
+            start: 0,
+        };
+        for (kind, regex) in regexes.iter().enumerate() {
+            let (start, end) = nfa.fragment(regex);
+            nfa.add_transition(0, Label::Epsilon, start);
+            nfa.states[end].accept = Some(kind);
+        }
+        nfa
+    }
+
+    pub fn add_state(&mut self) -> StateId {
+        self.states.push(State::default());
+        self.states.len() - 1
+    }
+
+    pub fn add_transition(&mut self, from: StateId, label: Label, to: StateId) {
+        self.states[from].transitions.push((label, to));
+    }
+
+    // start and end state of the automaton for `regex`
+    fn fragment(&mut self, regex: &Regex) -> (StateId, StateId) {
+        match regex {
+            Regex::Empty => {
+                let (s, e) = (self.add_state(), self.add_state());
+                self.add_transition(s, Label::Epsilon, e);
+                (s, e)
+            }
+            Regex::Char(c) => {
+                let (s, e) = (self.add_state(), self.add_state());
+                self.add_transition(s, Label::Char(*c), e);
+                (s, e)
+            }
+            Regex::Class(ranges) => {
+                let (s, e) = (self.add_state(), self.add_state());
+                self.add_transition(s, Label::Class(ranges.clone()), e);
+                (s, e)
+            }
+            Regex::Concat(parts) => {
+                let (start, mut end) = self.fragment(&parts[0]);
+                for r in &parts[1..] {
+                    let (s, e) = self.fragment(r);
+                    self.add_transition(end, Label::Epsilon, s);
+                    end = e;
+                }
+                (start, end)
+            }
+            Regex::Alt(alternatives) => {
+                let (s, e) = (self.add_state(), self.add_state());
+                for r in alternatives {
+                    let (rs, re) = self.fragment(r);
+                    self.add_transition(s, Label::Epsilon, rs);
+                    self.add_transition(re, Label::Epsilon, e);
+                }
+                (s, e)
+            }
+            Regex::Star(r) => {
+                let (s, e) = (self.add_state(), self.add_state());
+                let (rs, re) = self.fragment(r);
+                self.add_transition(s, Label::Epsilon, rs);
+                self.add_transition(s, Label::Epsilon, e);
+                self.add_transition(re, Label::Epsilon, rs);
+                self.add_transition(re, Label::Epsilon, e);
+                (s, e)
+            }
+        }
+    }
+
+    /// `states` and every state reachable from them through ε-transitions.
+    pub fn epsilon_closure(&self, states: &BTreeSet<StateId>) -> BTreeSet<StateId> {
+        let mut ret = states.clone();
+        let mut work: Vec<_> = states.iter().cloned().collect();
+        while let Some(s) = work.pop() {
+            for (label, to) in &self.states[s].transitions {
+                if *label == Label::Epsilon && ret.insert(*to) {
+                    work.push(*to);
+                }
+            }
+        }
+        ret
+    }
+
+    /// The states reached from `states` by consuming `c`, before taking ε-transitions.
+    pub fn step(&self, states: &BTreeSet<StateId>, c: char) -> BTreeSet<StateId> {
+        states
+            .iter()
+            .flat_map(|&s| &self.states[s].transitions)
+            .filter(|(label, _)| label.matches(c))
+            .map(|&(_, to)| to)
+            .collect()
+    }
+
+    /// Simulates the automaton on all of `input`, tracking every state it could be in.
+    pub fn matches(&self, input: &str) -> bool {
+        let mut current = self.epsilon_closure(&Some(self.start).into_iter().collect());
+        for c in input.chars() {
+            if current.is_empty() {
+                return false;
+            }
+            current = self.epsilon_closure(&self.step(&current, c));
+        }
+        current.iter().any(|&s| self.states[s].accept.is_some())
+    }
+}
@@ -0,0 +1,492 @@
+use std::fmt;
+use std::str::FromStr;
+
+use eac2_macros::lr1;
+
+use crate::grammar;
+use crate::parser::CFG;
+use crate::regex::ranges::{self, DIGIT, SPACE, WORD};
+use crate::regex::Regex;
+
+// characters that need a backslash to stand for themselves; those without a meaning yet are
+// reserved so that giving them one later doesn't change what existing regexes match
+const META: &str = "|*()\\+?[]{}.";
+const RESERVED: &str = "+?]}";
+
+/// The largest count `{m,n}` takes. Repetitions are written out in full, so counts need a bound.
+pub const MAX_REPEAT: usize = 1000;
+
+/// The most nodes a regex grows to once its repetitions are written out, so that nesting them
+/// doesn't multiply the counts past what fits in memory.
+pub const MAX_SIZE: usize = 100_000;
+
+/// The deepest nesting of `(` a regex takes, building the `Regex` recurses once per level.
+pub const MAX_DEPTH: usize = 200;
+
+pub fn is_meta(c: char) -> bool {
+    META.contains(c)
+}
+
+/// The grammar of `exercises/re.cfg` over concrete syntax: `or` is `|`, `and` is writing
+/// regexes next to each other, `closure` is `*` and `char` any character, escaped or not;
+/// `()` stands for the empty string. Beyond that, `class` is a set of characters, `[a-z_]`,
+/// `[^a-z_]`, `.`, `\d`, `\w` or `\s`, and `repeat` a count, `{m}`, `{m,}` or `{m,n}`, both
+/// single tokens to the grammar.
+///
+/// `Regex::parse` runs the LR(1) parser `lr1` generates from the same grammar, written out again
+/// on `Kind`, and the tests check that it accepts exactly the strings this one derives.
+pub fn grammar() -> CFG {
+    grammar! {
+        Goal -> RE;
+        RE -> RE "|" Expr | Expr;
+        Expr -> Expr Term | Term;
+        Term -> Term "*" | Term "repeat" | Factor;
+        Factor -> "(" RE ")" | "(" ")" | "char" | "class";
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Error {
+    /// Position of the offending character, counted in characters.
+    pub position: usize,
+    pub kind: ErrorKind,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ErrorKind {
+    /// The regex ends where an operand was expected.
+    Empty,
+    /// `|` or `)` where an operand was expected.
+    Unexpected(char),
+    /// `*` or a count with nothing before it.
+    NothingToRepeat,
+    /// A `(` without its `)`, positioned at the `(`.
+    Unclosed,
+    /// A `)` without its `(`.
+    Unmatched,
+    /// A `(` nested more than `MAX_DEPTH` deep.
+    TooDeep,
+    /// A backslash at the end of the regex.
+    DanglingEscape,
+    /// A backslash before a character that has no escape.
+    UnknownEscape(char),
+    /// `\u` not followed by the hex code of a character in braces.
+    BadCodePoint,
+    /// A `[` without its `]`, positioned at the `[`.
+    UnclosedClass,
+    /// A class without characters, such as `[]`.
+    EmptyClass,
+    /// A range in a class that ends before it starts.
+    ReversedRange(char, char),
+    /// A `{` not starting a count, or a count out of order or above `MAX_REPEAT`.
+    BadRepetition,
+    /// A character set aside for future syntax.
+    Reserved(char),
+    /// A repetition that takes the regex over `MAX_SIZE` nodes.
+    TooLarge,
+}
+
+#[lr1(
+    Goal -> RE;
+    RE -> RE "|" Expr | Expr;
+    Expr -> Expr Term | Term;
+    Term -> Term "*" | Term "repeat" | Factor;
+    Factor -> "(" RE ")" | "(" ")" | "char" | "class";
+)]
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Kind {
+    #[token("char")]
+    Char(char),
+    #[token("class")]
+    Class(Vec<(char, char)>),
+    #[token("|")]
+    Or,
+    #[token("*")]
+    Star,
+    #[token("repeat")]
+    Repeat { min: usize, max: Option<usize>, position: usize },
+    #[token("(")]
+    Open,
+    #[token(")")]
+    Close,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: Kind,
+    position: usize,
+}
+
+impl Regex {
+    pub fn parse(s: &str) -> Result<Regex, Error> {
+        let tokens = lex(s)?;
+        let tree = match Kind::parse(tokens.iter().map(|t| t.kind.clone())) {
+            Ok(tree) => tree,
+            Err(error) => return Err(syntax_error(&tokens, error, s.chars().count())),
+        };
+        let mut builder = Builder { too_large: None };
+        let (ret, _) = builder.goal(tree);
+        match builder.too_large {
+            None => Ok(ret),
+            Some(position) => Err(Error::new(position, ErrorKind::TooLarge)),
+        }
+    }
+}
+
+impl FromStr for Regex {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Regex, Error> {
+        Regex::parse(s)
+    }
+}
+
+fn lex(s: &str) -> Result<Vec<Token>, Error> {
+    let mut lexer = Lexer {
+        chars: s.chars().collect(),
+        at: 0,
+    };
+    let mut ret = vec![];
+    // `(`s not closed yet, the tree is only as deep as the lexer lets it be
+    let mut depth = 0;
+    while let Some(c) = lexer.bump() {
+        let position = lexer.at - 1;
+        let kind = match c {
+            '|' => Kind::Or,
+            '*' => Kind::Star,
+            '(' if depth == MAX_DEPTH => return Err(Error::new(position, ErrorKind::TooDeep)),
+            '(' => {
+                depth += 1;
+                Kind::Open
+            }
+            ')' => {
+                depth = depth.saturating_sub(1);
+                Kind::Close
+            }
+            '.' => Kind::Class(ranges::DOT.to_vec()),
+            '[' => Kind::Class(lexer.class(position)?),
+            '{' => lexer.repeat(position)?,
+            '\\' => match lexer.escape(position)?[..] {
+                [(lo, hi)] if lo == hi => Kind::Char(lo),
+                ref class => Kind::Class(class.to_vec()),
+            },
+            c if RESERVED.contains(c) => return Err(Error::new(position, ErrorKind::Reserved(c))),
+            c => Kind::Char(c),
+        };
+        ret.push(Token { kind, position });
+    }
+    Ok(ret)
+}
+
+struct Lexer {
+    chars: Vec<char>,
+    at: usize,
+}
+
+impl Lexer {
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.get(self.at).cloned();
+        self.at += c.is_some() as usize;
+        c
+    }
+
+    fn peek(&self, n: usize) -> Option<char> {
+        self.chars.get(self.at + n).cloned()
+    }
+
+    // the characters the escape after the backslash at `position` stands for
+    fn escape(&mut self, position: usize) -> Result<Vec<(char, char)>, Error> {
+        let single = |c| Ok(vec![(c, c)]);
+        match self.bump() {
+            None => Err(Error::new(position, ErrorKind::DanglingEscape)),
+            Some('n') => single('\n'),
+            Some('t') => single('\t'),
+            Some('r') => single('\r'),
+            Some('u') => single(self.code_point(position)?),
+            Some('d') => Ok(DIGIT.to_vec()),
+            Some('w') => Ok(WORD.to_vec()),
+            Some('s') => Ok(SPACE.to_vec()),
+            Some('D') => Ok(ranges::negate(DIGIT)),
+            Some('W') => Ok(ranges::negate(WORD)),
+            Some('S') => Ok(ranges::negate(SPACE)),
+            Some(c) if is_meta(c) || c == '-' || c == '^' => single(c),
+            Some(c) => Err(Error::new(position, ErrorKind::UnknownEscape(c))),
+        }
+    }
+
+    // `{1f600}` after `\u`
+    fn code_point(&mut self, position: usize) -> Result<char, Error> {
+        let error = Error::new(position, ErrorKind::BadCodePoint);
+        if self.bump() != Some('{') {
+            return Err(error);
+        }
+        let mut digits = String::new();
+        while let Some(c) = self.bump() {
+            match c {
+                '}' => return u32::from_str_radix(&digits, 16).ok().and_then(char::from_u32).ok_or(error),
+                c if c.is_ascii_hexdigit() && digits.len() < 6 => digits.push(c),
+                _ => break,
+            }
+        }
+        Err(error)
+    }
+
+    // the characters of the class after the `[` at `position`; a `-` between two characters
+    // makes a range, elsewhere it stands for itself, as `^` does anywhere but first
+    fn class(&mut self, position: usize) -> Result<Vec<(char, char)>, Error> {
+        let negated = self.peek(0) == Some('^');
+        self.at += negated as usize;
+        let mut ret = vec![];
+        loop {
+            let start = self.at;
+            let lo = match self.bump() {
+                None => return Err(Error::new(position, ErrorKind::UnclosedClass)),
+                Some(']') => break,
+                Some('\\') => match self.escape(start)?[..] {
+                    [(lo, hi)] if lo == hi => lo,
+                    ref class => {
+                        ret.extend(class);
+                        continue;
+                    }
+                },
+                Some(c) => c,
+            };
+            if self.peek(0) != Some('-') || matches!(self.peek(1), None | Some(']')) {
+                ret.push((lo, lo));
+                continue;
+            }
+            self.at += 1;
+            let end = self.at;
+            let hi = match self.bump() {
+                Some('\\') => self.escape(end)?,
+                Some(c) => vec![(c, c)],
+                None => unreachable!(),
+            };
+            match hi[..] {
+                [(hi, _)] if hi < lo => return Err(Error::new(start, ErrorKind::ReversedRange(lo, hi))),
+                [(hi, x)] if hi == x => ret.push((lo, hi)),
+                // `[a-\d]` has no range to make
+                _ => ret.extend([(lo, lo), ('-', '-')].iter().chain(&hi)),
+            }
+        }
+        let mut ret = ranges::normalize(ret);
+        if negated {
+            ret = ranges::negate(&ret);
+        }
+        if ret.is_empty() {
+            return Err(Error::new(position, ErrorKind::EmptyClass));
+        }
+        Ok(ret)
+    }
+
+    // `m}`, `m,}` or `m,n}` after the `{` at `position`
+    fn repeat(&mut self, position: usize) -> Result<Kind, Error> {
+        let error = Error::new(position, ErrorKind::BadRepetition);
+        let min = self.count().ok_or_else(|| error.clone())?;
+        let max = match self.bump() {
+            Some('}') => return Ok(Kind::Repeat { min, max: Some(min), position }),
+            Some(',') if self.peek(0) == Some('}') => None,
+            Some(',') => Some(self.count().filter(|&max| max >= min).ok_or_else(|| error.clone())?),
+            _ => return Err(error),
+        };
+        match self.bump() {
+            Some('}') => Ok(Kind::Repeat { min, max, position }),
+            _ => Err(error),
+        }
+    }
+
+    fn count(&mut self) -> Option<usize> {
+        let mut digits = String::new();
+        while let Some(c) = self.peek(0).filter(char::is_ascii_digit) {
+            digits.push(c);
+            self.at += 1;
+        }
+        digits.parse().ok().filter(|&n| n <= MAX_REPEAT)
+    }
+}
+
+// turns the tree into a `Regex` along with its size in nodes; the tree is taken apart as it
+// goes, the left recursive spines of `RE`, `Expr` and `Term` are as long as the regex and would
+// overflow the stack if dropped, or walked, recursively
+struct Builder {
+    // the leftmost repetition that went over `MAX_SIZE`; from then on repetitions are only
+    // counted, not written out, while the rest of the tree comes apart a piece at a time
+    too_large: Option<usize>,
+}
+
+impl Builder {
+    // Goal -> RE
+    fn goal(&mut self, tree: KindTree) -> (Regex, usize) {
+        self.re(children(tree).pop().unwrap())
+    }
+
+    // RE -> RE "|" Expr | Expr
+    fn re(&mut self, mut tree: KindTree) -> (Regex, usize) {
+        let mut alternatives = vec![];
+        let mut size = 1;
+        loop {
+            let mut children = children(tree);
+            let (r, n) = self.expr(children.pop().unwrap());
+            alternatives.push(r);
+            size += n;
+            if children.is_empty() {
+                break;
+            }
+            tree = children.swap_remove(0);
+        }
+        alternatives.reverse();
+        (Regex::alt(alternatives), size)
+    }
+
+    // Expr -> Expr Term | Term
+    fn expr(&mut self, mut tree: KindTree) -> (Regex, usize) {
+        let mut parts = vec![];
+        let mut size = 1;
+        loop {
+            let mut children = children(tree);
+            let (r, n) = self.term(children.pop().unwrap());
+            parts.push(r);
+            size += n;
+            if children.is_empty() {
+                break;
+            }
+            tree = children.pop().unwrap();
+        }
+        parts.reverse();
+        (Regex::concat(parts), size)
+    }
+
+    // Term -> Term "*" | Term "repeat" | Factor
+    fn term(&mut self, mut tree: KindTree) -> (Regex, usize) {
+        let mut operators = vec![];
+        let mut children = children(tree);
+        while children.len() == 2 {
+            operators.push(children.pop().unwrap());
+            tree = children.pop().unwrap();
+            children = self::children(tree);
+        }
+        let (mut ret, mut size) = self.factor(children.pop().unwrap());
+        for operator in operators.into_iter().rev() {
+            match operator {
+                KindTree::Leaf(Kind::Repeat { min, max, position }) => {
+                    // past the budget the size only needs to stay past it
+                    size = repeat_size(size, min, max).min(MAX_SIZE + 1);
+                    if size > MAX_SIZE {
+                        self.too_large = Some(self.too_large.map_or(position, |p| p.min(position)));
+                    }
+                    if self.too_large.is_none() {
+                        ret = repeat(ret, min, max);
+                    }
+                }
+                _ => {
+                    ret = ret.star();
+                    size += 1;
+                }
+            }
+        }
+        (ret, size)
+    }
+
+    // Factor -> "(" RE ")" | "(" ")" | "char" | "class"
+    fn factor(&mut self, tree: KindTree) -> (Regex, usize) {
+        let mut children = children(tree);
+        match children.len() {
+            3 => self.re(children.swap_remove(1)),
+            2 => (Regex::Empty, 1),
+            _ => match children.pop() {
+                Some(KindTree::Leaf(Kind::Char(c))) => (Regex::Char(c), 1),
+                Some(KindTree::Leaf(Kind::Class(ranges))) => (Regex::class(ranges), 1),
+                _ => unreachable!(),
+            },
+        }
+    }
+}
+
+// the children of `tree`, a node for a production of a nonterminal
+fn children(tree: KindTree) -> Vec<KindTree> {
+    match tree {
+        KindTree::Node(_, children) => children,
+        _ => unreachable!(),
+    }
+}
+
+// the error for the token `error` is at, in the terms of the regex rather than the grammar
+fn syntax_error(tokens: &[Token], error: KindError, end: usize) -> Error {
+    // positions of the `(`s still open before the token
+    let mut open = vec![];
+    for token in &tokens[..error.position] {
+        match token.kind {
+            Kind::Open => open.push(token.position),
+            Kind::Close => {
+                open.pop();
+            }
+            _ => {}
+        }
+    }
+    let position = tokens.get(error.position).map_or(end, |t| t.position);
+    match error.found {
+        // the regex ends after an operand, so all it lacks is a `)`
+        None => match (tokens.last().map(|t| &t.kind), open.last()) {
+            (Some(Kind::Char(_) | Kind::Class(_) | Kind::Star | Kind::Repeat { .. } | Kind::Close), Some(&open)) => {
+                Error::new(open, ErrorKind::Unclosed)
+            }
+            _ => Error::new(end, ErrorKind::Empty),
+        },
+        Some(Kind::Star | Kind::Repeat { .. }) => Error::new(position, ErrorKind::NothingToRepeat),
+        Some(Kind::Close) if open.is_empty() => Error::new(position, ErrorKind::Unmatched),
+        Some(Kind::Close) => Error::new(position, ErrorKind::Unexpected(')')),
+        Some(Kind::Or) => Error::new(position, ErrorKind::Unexpected('|')),
+        // an operand fits anywhere another one would have
+        Some(_) => unreachable!(),
+    }
+}
+
+// `r{m,n}` written out: `r` `m` times, then `r*` without an `n`, or else `n - m` optional
+// `r`s one after the other, `r{1,3}` is `r(()|r)(()|r)`, so the result nests no deeper than `r`
+fn repeat(r: Regex, min: usize, max: Option<usize>) -> Regex {
+    let mut parts = vec![r.clone(); min];
+    match max {
+        None => parts.push(r.star()),
+        Some(max) => parts.extend(vec![Regex::alt(vec![Regex::Empty, r]); max - min]),
+    }
+    Regex::concat(parts)
+}
+
+// the size of `r{min,max}` written out by `repeat`, where `r` is `size` nodes
+fn repeat_size(size: usize, min: usize, max: Option<usize>) -> usize {
+    match max {
+        None => (min + 1) * size + 2,
+        Some(max) => min * size + (max - min) * (size + 2) + 1,
+    }
+}
+
+impl Error {
+    fn new(position: usize, kind: ErrorKind) -> Self {
+        Error { position, kind }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "at {}: ", self.position)?;
+        match self.kind {
+            ErrorKind::Empty => write!(f, "expected a character or `(`, found the end of the regex"),
+            ErrorKind::Unexpected(c) => write!(f, "expected a character or `(`, found `{}`", c),
+            ErrorKind::NothingToRepeat => write!(f, "nothing before the repetition to repeat"),
+            ErrorKind::Unclosed => write!(f, "`(` is never closed"),
+            ErrorKind::Unmatched => write!(f, "`)` has no matching `(`"),
+            ErrorKind::TooDeep => write!(f, "`(` nested more than {} deep", MAX_DEPTH),
+            ErrorKind::DanglingEscape => write!(f, "`\\` at the end of the regex"),
+            ErrorKind::UnknownEscape(c) => write!(f, "unknown escape `\\{}`", c),
+            ErrorKind::BadCodePoint => write!(f, "expected the hex code of a character in braces after `\\u`"),
+            ErrorKind::UnclosedClass => write!(f, "`[` is never closed"),
+            ErrorKind::EmptyClass => write!(f, "the class matches no character"),
+            ErrorKind::ReversedRange(lo, hi) => write!(f, "range `{}-{}` ends before it starts", lo, hi),
+            ErrorKind::BadRepetition => {
+                write!(f, "expected a count `{{m}}`, `{{m,}}` or `{{m,n}}` with m ≤ n ≤ {}", MAX_REPEAT)
+            }
+            ErrorKind::Reserved(c) => write!(f, "`{}` is reserved, write `\\{}` to match it", c, c),
+            ErrorKind::TooLarge => write!(f, "the repetition makes the regex larger than {} nodes", MAX_SIZE),
+        }
+    }
+}
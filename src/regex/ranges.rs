@@ -0,0 +1,119 @@
+//! Sets of characters as sorted, disjoint inclusive ranges, which is how classes and DFA
+//! transitions store them. Ranges are kept apart only where something lies between them, and
+//! the surrogates, which are no characters, don't count as something.
+
+use std::cmp::Ordering;
+
+/// Any character but a line feed, what `.` matches.
+pub const DOT: &[(char, char)] = &[('\0', '\t'), ('\u{b}', char::MAX)];
+/// `\d`
+pub const DIGIT: &[(char, char)] = &[('0', '9')];
+/// `\w`
+pub const WORD: &[(char, char)] = &[('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')];
+/// `\s`: space, tab, line feed, vertical tab, form feed and carriage return.
+pub const SPACE: &[(char, char)] = &[('\t', '\r'), (' ', ' ')];
+
+/// The character after `c`, skipping the surrogates.
+pub fn succ(c: char) -> Option<char> {
+    match c {
+        '\u{d7ff}' => Some('\u{e000}'),
+        c => char::from_u32(c as u32 + 1),
+    }
+}
+
+/// The character before `c`, skipping the surrogates.
+pub fn pred(c: char) -> Option<char> {
+    match c {
+        '\0' => None,
+        '\u{e000}' => Some('\u{d7ff}'),
+        c => char::from_u32(c as u32 - 1),
+    }
+}
+
+/// `ranges` sorted, with overlapping and adjacent ones merged.
+pub fn normalize(mut ranges: Vec<(char, char)>) -> Vec<(char, char)> {
+    ranges.sort_unstable();
+    let mut ret: Vec<(char, char)> = vec![];
+    for (lo, hi) in ranges {
+        match ret.last_mut() {
+            Some((_, last)) if succ(*last).is_none_or(|next| lo <= next) => *last = hi.max(*last),
+            _ => ret.push((lo, hi)),
+        }
+    }
+    ret
+}
+
+/// Every character not in the normalized `ranges`.
+pub fn negate(ranges: &[(char, char)]) -> Vec<(char, char)> {
+    let mut ret = vec![];
+    let mut from = Some('\0');
+    for &(lo, hi) in ranges {
+        if let (Some(from), Some(to)) = (from, pred(lo)) {
+            if from <= to {
+                ret.push((from, to));
+            }
+        }
+        from = succ(hi);
+    }
+    if let Some(from) = from {
+        ret.push((from, char::MAX));
+    }
+    ret
+}
+
+/// How many characters the normalized `ranges` hold.
+pub fn len(ranges: &[(char, char)]) -> usize {
+    let surrogates = |lo: char, hi: char| if (lo as u32) < 0xd800 && hi as u32 > 0xdfff { 0x800 } else { 0 };
+    ranges.iter().map(|&(lo, hi)| (hi as usize - lo as usize + 1) - surrogates(lo, hi)).sum()
+}
+
+pub fn contains(ranges: &[(char, char)], c: char) -> bool {
+    ranges.binary_search_by(|&(lo, hi)| order(lo, hi, c)).is_ok()
+}
+
+/// What `c` maps to in `ranges`, sorted and disjoint ranges with a value each.
+pub fn lookup<T: Copy>(ranges: &[(char, char, T)], c: char) -> Option<T> {
+    let i = ranges.binary_search_by(|&(lo, hi, _)| order(lo, hi, c)).ok()?;
+    Some(ranges[i].2)
+}
+
+/// Appends `lo..=hi` mapping to `value` to `ranges`, which it must come after, extending the
+/// last range instead if that is adjacent and maps to the same.
+pub fn push<T: Copy + Eq>(ranges: &mut Vec<(char, char, T)>, lo: char, hi: char, value: T) {
+    match ranges.last_mut() {
+        Some((_, last, v)) if *v == value && succ(*last) == Some(lo) => *last = hi,
+        _ => ranges.push((lo, hi, value)),
+    }
+}
+
+/// The coarsest partition of the characters in any of `ranges` into ranges that each lie
+/// inside or outside every one of `ranges`, sorted. Automata treat all characters of such a
+/// range alike, so they only need to look at one of them.
+pub fn partition<I: IntoIterator<Item = (char, char)>>(ranges: I) -> Vec<(char, char)> {
+    let ranges: Vec<_> = ranges.into_iter().collect();
+    let mut starts: Vec<char> = ranges.iter().flat_map(|&(lo, hi)| Some(lo).into_iter().chain(succ(hi))).collect();
+    starts.sort_unstable();
+    starts.dedup();
+    let covered = normalize(ranges);
+    let mut ret = vec![];
+    for (i, &lo) in starts.iter().enumerate() {
+        let hi = match starts.get(i + 1) {
+            Some(&next) => pred(next).unwrap(),
+            None => char::MAX,
+        };
+        if contains(&covered, lo) {
+            ret.push((lo, hi));
+        }
+    }
+    ret
+}
+
+fn order(lo: char, hi: char, c: char) -> Ordering {
+    if hi < c {
+        Ordering::Less
+    } else if lo > c {
+        Ordering::Greater
+    } else {
+        Ordering::Equal
+    }
+}
@@ -0,0 +1,265 @@
+use crate::regex::{ranges, Regex};
+
+impl Regex {
+    /// An equivalent regex tidied up by identities such as `()r = r`, `(r*)* = r*`,
+    /// `r|r* = r*` and `()|rr* = r*`, by factoring common prefixes and suffixes out of
+    /// alternations, and by joining alternative characters into a class, `a|[b-z]` is `[a-z]`.
+    pub fn simplify(&self) -> Regex {
+        match self {
+            Regex::Empty | Regex::Char(_) | Regex::Class(_) => self.clone(),
+            Regex::Alt(alternatives) => alt(alternatives.iter().map(Regex::simplify).collect()),
+            Regex::Concat(parts) => concat(parts.iter().map(Regex::simplify).collect()),
+            Regex::Star(r) => star(r.simplify()),
+        }
+    }
+
+    /// Whether the regex matches the empty string.
+    pub fn nullable(&self) -> bool {
+        match self {
+            Regex::Empty | Regex::Star(_) => true,
+            Regex::Char(_) | Regex::Class(_) => false,
+            Regex::Alt(alternatives) => alternatives.iter().any(Regex::nullable),
+            Regex::Concat(parts) => parts.iter().all(Regex::nullable),
+        }
+    }
+}
+
+// the constructors below take simplified regexes and keep them simplified
+
+pub(crate) fn star(r: Regex) -> Regex {
+    match r {
+        Regex::Empty | Regex::Star(_) => r,
+        // (()|r|s*)* = (r|s)*
+        Regex::Alt(alternatives) => {
+            let inner = alternatives.into_iter().map(|a| match a {
+                Regex::Star(r) => *r,
+                a => a,
+            });
+            match alt(inner.filter(|a| *a != Regex::Empty).collect()) {
+                Regex::Star(r) => Regex::Star(r),
+                // (r|rr)* = r*
+                Regex::Alt(mut alternatives) => {
+                    let mut i = 0;
+                    while i < alternatives.len() {
+                        let others = Regex::Alt([&alternatives[..i], &alternatives[i + 1..]].concat());
+                        if within_star(&alternatives[i], &others) {
+                            alternatives.remove(i);
+                        } else {
+                            i += 1;
+                        }
+                    }
+                    match alternatives.len() {
+                        1 => star(alternatives.pop().unwrap()),
+                        _ => Regex::Alt(alternatives).star(),
+                    }
+                }
+                r => Regex::Star(Box::new(r)),
+            }
+        }
+        // (r*s*)* = (r|s)*
+        Regex::Concat(parts) if parts.iter().all(|p| matches!(p, Regex::Star(_))) => {
+            let inner = parts.into_iter().map(|p| match p {
+                Regex::Star(r) => *r,
+                p => p,
+            });
+            star(alt(inner.collect()))
+        }
+        r => r.star(),
+    }
+}
+
+pub(crate) fn concat(parts: Vec<Regex>) -> Regex {
+    let mut ret: Vec<Regex> = vec![];
+    for part in flatten_concat(parts) {
+        // r*r* = r*
+        if matches!(part, Regex::Star(_)) && ret.last() == Some(&part) {
+            continue;
+        }
+        ret.push(part);
+    }
+    Regex::concat(ret)
+}
+
+pub(crate) fn alt(alternatives: Vec<Regex>) -> Regex {
+    let mut flat: Vec<Regex> = vec![];
+    for a in alternatives {
+        match a {
+            Regex::Alt(inner) => flat.extend(inner),
+            a => flat.push(a),
+        }
+    }
+    let mut unique: Vec<Regex> = vec![];
+    for a in flat {
+        if !unique.contains(&a) {
+            unique.push(a);
+        }
+    }
+    if let Some(first) = unique.iter().position(|a| chars_of(a).is_some()) {
+        let chars = unique.iter().filter_map(chars_of).flatten().collect();
+        unique.retain(|a| chars_of(a).is_none());
+        unique.splice(first..first, characters(Regex::class(chars)));
+    }
+
+    // ()|rr* = ()|r*r = r*
+    if unique.contains(&Regex::Empty) {
+        for a in unique.iter_mut() {
+            if let Some(r) = plus_of(a) {
+                *a = star(r);
+            }
+        }
+    }
+    // what a starred alternative matches anyway goes
+    let starred: Vec<Regex> = unique
+        .iter()
+        .filter_map(|a| match a {
+            Regex::Star(r) => Some((**r).clone()),
+            _ => None,
+        })
+        .collect();
+    let nullable = unique.iter().any(|a| *a != Regex::Empty && a.nullable());
+    let mut kept: Vec<Regex> = vec![];
+    for a in unique {
+        let subsumed = match a {
+            Regex::Empty => nullable,
+            Regex::Star(_) => false,
+            ref a => starred.iter().any(|r| within_star(a, r)),
+        };
+        if !subsumed && !kept.contains(&a) {
+            kept.push(a);
+        }
+    }
+
+    let factored = factor(factor(kept.clone(), true), false);
+    if factored.len() < kept.len() {
+        // the merged alternatives may simplify further, `()|b|bb*` is `()|bb*` first
+        return alt(factored);
+    }
+    match kept.len() {
+        0 => Regex::Empty,
+        1 => kept.pop().unwrap(),
+        _ => Regex::Alt(kept),
+    }
+}
+
+// whether `regex` only matches what `r*` does, going by their shape: it is made of
+// alternatives of `r` with concatenation, alternation and closure
+fn within_star(regex: &Regex, r: &Regex) -> bool {
+    let alternatives = match r {
+        Regex::Alt(alternatives) => &alternatives[..],
+        r => std::slice::from_ref(r),
+    };
+    if alternatives.contains(regex) {
+        return true;
+    }
+    match regex {
+        Regex::Empty => true,
+        Regex::Char(_) | Regex::Class(_) => {
+            let chars = chars_of(regex).unwrap();
+            let covers = |a: &Regex| chars_of(a).is_some_and(|r| chars.iter().all(|&(lo, hi)| within(lo, hi, &r)));
+            alternatives.iter().any(covers)
+        }
+        Regex::Star(inner) => within_star(inner, r),
+        Regex::Alt(parts) | Regex::Concat(parts) => parts.iter().all(|p| within_star(p, r)),
+    }
+}
+
+// the characters of a `Char` or `Class`
+fn chars_of(regex: &Regex) -> Option<Vec<(char, char)>> {
+    match regex {
+        Regex::Char(c) => Some(vec![(*c, *c)]),
+        Regex::Class(ranges) => Some(ranges.clone()),
+        _ => None,
+    }
+}
+
+fn within(lo: char, hi: char, ranges: &[(char, char)]) -> bool {
+    ranges.iter().any(|&(l, h)| l <= lo && hi <= h)
+}
+
+// a class as alternative characters where that reads shorter, `[ab]` is `a|b`
+fn characters(class: Regex) -> Vec<Regex> {
+    if let Regex::Class(ranges) = &class {
+        if ranges::len(ranges) <= 4 {
+            let chars: Vec<_> = ranges.iter().flat_map(|&(lo, hi)| lo..=hi).map(Regex::Char).collect();
+            if Regex::Alt(chars.clone()).to_string().len() < class.to_string().len() {
+                return chars;
+            }
+        }
+    }
+    vec![class]
+}
+
+// `r` for `rr*` and `r*r`
+fn plus_of(regex: &Regex) -> Option<Regex> {
+    let parts = match regex {
+        Regex::Concat(parts) => parts,
+        _ => return None,
+    };
+    let n = parts.len();
+    let (init, tail) = (concat(parts[..n - 1].to_vec()), concat(parts[1..].to_vec()));
+    for (r, s) in [(init, &parts[n - 1]), (tail, &parts[0])] {
+        if *s == star(r.clone()) {
+            return Some(r);
+        }
+    }
+    None
+}
+
+fn flatten_concat(parts: Vec<Regex>) -> Vec<Regex> {
+    let mut ret = vec![];
+    for p in parts {
+        match p {
+            Regex::Concat(inner) => ret.extend(inner),
+            Regex::Empty => {}
+            p => ret.push(p),
+        }
+    }
+    ret
+}
+
+// alternatives with the same first part, or the same last part if not `prefix`, become one
+// alternative with the rest alternated: `ab|ac|d` is `a(b|c)|d`
+fn factor(alternatives: Vec<Regex>, prefix: bool) -> Vec<Regex> {
+    let split = |r: &Regex| {
+        let mut parts = flatten_concat(vec![r.clone()]);
+        if parts.is_empty() {
+            return None;
+        }
+        let part = if prefix { parts.remove(0) } else { parts.pop().unwrap() };
+        Some((part, Regex::concat(parts)))
+    };
+    let mut groups: Vec<(Option<Regex>, Vec<Regex>)> = vec![];
+    for a in alternatives {
+        match split(&a) {
+            Some((part, rest)) => match groups.iter_mut().find(|(p, _)| p.as_ref() == Some(&part)) {
+                Some((_, rests)) => rests.push(rest),
+                None => groups.push((Some(part), vec![rest])),
+            },
+            None => groups.push((None, vec![a])),
+        }
+    }
+    let mut ret = vec![];
+    for (part, rests) in groups {
+        let separate: Vec<_> = rests.iter().map(|r| joined(part.clone(), r.clone(), prefix)).collect();
+        if rests.len() == 1 {
+            ret.extend(separate);
+            continue;
+        }
+        // only where it pays off, `a|ab` is clearer than `a(()|b)`
+        let merged = joined(part, alt(rests), prefix);
+        if merged.to_string().len() < Regex::Alt(separate.clone()).to_string().len() {
+            ret.push(merged);
+        } else {
+            ret.extend(separate);
+        }
+    }
+    ret
+}
+
+fn joined(part: Option<Regex>, rest: Regex, prefix: bool) -> Regex {
+    match part {
+        None => rest,
+        Some(part) if prefix => concat(vec![part, rest]),
+        Some(part) => concat(vec![rest, part]),
+    }
+}
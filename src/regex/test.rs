@@ -0,0 +1,459 @@
+use super::parse::{grammar, ErrorKind, MAX_DEPTH, MAX_REPEAT, MAX_SIZE};
+use super::*;
+use crate::parser::backtrack_parse::backtrack_parse;
+use crate::parser::Terminal;
+
+fn parse(s: &str) -> Regex {
+    Regex::parse(s).unwrap_or_else(|e| panic!("{}: {}", s, e))
+}
+
+fn error(s: &str) -> (usize, ErrorKind) {
+    let e = Regex::parse(s).unwrap_err();
+    (e.position, e.kind)
+}
+
+#[test]
+fn test_parse() {
+    use Regex::*;
+
+    assert_eq!(parse("a"), Char('a'));
+    assert_eq!(parse("ab|c"), Alt(vec![Concat(vec![Char('a'), Char('b')]), Char('c')]));
+    assert_eq!(parse("a|b|c"), Alt(vec![Char('a'), Char('b'), Char('c')]));
+    assert_eq!(parse("ab*"), Concat(vec![Char('a'), Char('b').star()]));
+    assert_eq!(parse("(ab)*"), Concat(vec![Char('a'), Char('b')]).star());
+    assert_eq!(parse("a**"), Char('a').star().star());
+    // grouping only changes the shape where it changes the meaning
+    assert_eq!(parse("(a|b)|(c)"), parse("a|b|c"));
+    assert_eq!(parse("a(bc)d"), parse("abcd"));
+    assert_eq!(parse("\\(\\*\\\\\\n"), Concat(vec![Char('('), Char('*'), Char('\\'), Char('\n')]));
+    assert_eq!(parse("é|∅"), Alt(vec![Char('é'), Char('∅')]));
+    assert_eq!(parse(" "), Char(' '));
+    assert_eq!(parse("()"), Empty);
+    assert_eq!(parse("a()b"), parse("ab"));
+    assert_eq!(parse("()*|a"), Alt(vec![Empty.star(), Char('a')]));
+
+    for s in &["a", "ab|c*", "(a|b)*c", "a(b|c)", "(ab)*", "a**", "\\|\\(\\+|\\t", "(a|b)(c|d)", "a(b|())"] {
+        assert_eq!(parse(s).to_string(), *s);
+    }
+    assert_eq!(parse("((a)(b))|((c))").to_string(), "ab|c");
+}
+
+#[test]
+fn test_parse_errors() {
+    assert_eq!(error(""), (0, ErrorKind::Empty));
+    assert_eq!(error("ab|"), (3, ErrorKind::Empty));
+    assert_eq!(error("a||b"), (2, ErrorKind::Unexpected('|')));
+    assert_eq!(error("(|a)"), (1, ErrorKind::Unexpected('|')));
+    assert_eq!(error("a(()"), (1, ErrorKind::Unclosed));
+    assert_eq!(error("*a"), (0, ErrorKind::NothingToRepeat));
+    assert_eq!(error("a|*"), (2, ErrorKind::NothingToRepeat));
+    assert_eq!(error("a(b(c)"), (1, ErrorKind::Unclosed));
+    assert_eq!(error("ab)c"), (2, ErrorKind::Unmatched));
+    assert_eq!(error("ab\\"), (2, ErrorKind::DanglingEscape));
+    assert_eq!(error("a\\q"), (1, ErrorKind::UnknownEscape('q')));
+    assert_eq!(error("éa+"), (2, ErrorKind::Reserved('+')));
+
+    // nesting is bounded instead of running out of stack
+    let nested = |n: usize| format!("{}b{}", "(a".repeat(n), ")*".repeat(n));
+    let re = parse(&nested(MAX_DEPTH));
+    assert_eq!(parse(&re.to_string()), re);
+    let nfa = nfa::Nfa::thompson(&re);
+    assert!(nfa.matches(&format!("{}b", "a".repeat(MAX_DEPTH))) && nfa.matches("aa") && !nfa.matches("ab"));
+    assert_eq!(error(&nested(MAX_DEPTH + 1)), (2 * MAX_DEPTH, ErrorKind::TooDeep));
+    assert_eq!(error(&"(".repeat(100_000)), (MAX_DEPTH, ErrorKind::TooDeep));
+
+    let messages: Vec<_> = ["a(b(c)", "a||b", "x+y"].iter().map(|s| Regex::parse(s).unwrap_err().to_string()).collect();
+    assert_eq!(messages, vec![
+        "at 1: `(` is never closed",
+        "at 2: expected a character or `(`, found `|`",
+        "at 1: `+` is reserved, write `\\+` to match it",
+    ]);
+}
+
+// the parser accepts exactly what the grammar derives, checked on every short string
+#[test]
+fn test_parse_matches_grammar() {
+    let cfg = grammar();
+    let alphabet = ['a', '|', '*', '(', ')'];
+    let mut strings = vec![String::new()];
+    for len in 1..=5 {
+        let shorter: Vec<_> = strings.iter().filter(|s| s.len() == len - 1).cloned().collect();
+        for s in shorter {
+            strings.extend(alphabet.iter().map(|&c| format!("{}{}", s, c)));
+        }
+    }
+    for s in &strings {
+        let tokens: Vec<_> = s
+            .chars()
+            .map(|c| Terminal::new(if c == 'a' { "char".to_string() } else { c.to_string() }))
+            .collect();
+        assert_eq!(Regex::parse(s).is_ok(), backtrack_parse(&cfg, &tokens).is_ok(), "{:?}", s);
+    }
+}
+
+// every string over `alphabet` of at most `max_len` characters, shortest first
+fn strings(alphabet: &[char], max_len: usize) -> Vec<String> {
+    let mut ret = vec![String::new()];
+    let mut start = 0;
+    for _ in 0..max_len {
+        let end = ret.len();
+        for i in start..end {
+            for c in alphabet {
+                let s = format!("{}{}", ret[i], c);
+                ret.push(s);
+            }
+        }
+        start = end;
+    }
+    ret
+}
+
+// random regexes over a and b, at most `depth` operators deep
+fn random_regex(seed: &mut u64, depth: usize) -> Regex {
+    *seed ^= *seed << 13;
+    *seed ^= *seed >> 7;
+    *seed ^= *seed << 17;
+    let pick = if depth == 0 { *seed % 2 } else { *seed % 6 };
+    match pick {
+        0 => Regex::Char('a'),
+        1 => Regex::Char('b'),
+        2 | 3 => {
+            let parts = vec![random_regex(seed, depth - 1), random_regex(seed, depth - 1)];
+            if pick == 2 {
+                Regex::alt(parts)
+            } else {
+                Regex::concat(parts)
+            }
+        }
+        4 => random_regex(seed, depth - 1).star(),
+        _ => Regex::alt(vec![random_regex(seed, depth - 1), Regex::Empty]),
+    }
+}
+
+#[test]
+fn test_backtrack() {
+    use backtrack::is_match;
+
+    let r = parse("(a|b)*abb");
+    assert!(is_match(&r, "abb"));
+    assert!(is_match(&r, "babaabb"));
+    assert!(!is_match(&r, "abba"));
+    assert!(!is_match(&r, ""));
+    // stars over something that matches the empty string still terminate
+    let r = parse("(a*)*b");
+    assert!(is_match(&r, "aaab"));
+    assert!(!is_match(&r, "aaa"));
+    assert!(is_match(&parse("(a**|b)*"), ""));
+}
+
+#[test]
+fn test_thompson() {
+    use backtrack::is_match;
+    use nfa::{Label, Nfa};
+
+    let nfa = Nfa::thompson(&parse("a(b|c)*"));
+    // a: 2, b and c: 2 each plus 2 for the alternation, 2 for the star
+    assert_eq!(nfa.states.len(), 10);
+    assert_eq!(nfa.states.iter().filter(|s| s.accept.is_some()).count(), 1);
+    assert!(nfa.matches("a"));
+    assert!(nfa.matches("abcbb"));
+    assert!(!nfa.matches("ab a"));
+    assert!(!nfa.matches(""));
+
+    let mut nfa = Nfa::thompson(&parse("x"));
+    let (s, e) = (nfa.add_state(), nfa.add_state());
+    nfa.add_transition(s, Label::Class(vec![('0', '9'), ('a', 'f')]), e);
+    nfa.add_transition(nfa.start, Label::Epsilon, s);
+    nfa.states[e].accept = Some(1);
+    assert!(nfa.matches("7") && nfa.matches("c") && nfa.matches("x"));
+    assert!(!nfa.matches("g") && !nfa.matches("7c"));
+
+    let inputs = strings(&['a', 'b', 'c'], 5);
+    let mut seed = 0x2545_f491;
+    for _ in 0..200 {
+        let regex = random_regex(&mut seed, 4);
+        let nfa = Nfa::thompson(&regex);
+        for s in &inputs {
+            assert_eq!(nfa.matches(s), is_match(&regex, s), "{} on {:?}", regex, s);
+        }
+    }
+}
+
+#[test]
+fn test_subset_construction() {
+    use dfa::Dfa;
+    use nfa::Nfa;
+
+    // the same five states as the textbook example
+    let dfa = Dfa::from_nfa(&Nfa::thompson(&parse("(a|b)*abb")));
+    assert_eq!(dfa.states.len(), 5);
+    assert!(dfa.matches("aababb"));
+    assert!(!dfa.matches("abab"));
+    assert_eq!(dfa.run("abc"), None);
+    let accepting: Vec<_> = dfa.states.iter().filter(|s| s.accept().is_some()).collect();
+    assert_eq!(accepting.len(), 1);
+    assert_eq!(accepting[0].kinds.iter().collect::<Vec<_>>(), vec![&0]);
+
+    // keyword, identifier and number: `if` is both a keyword and an identifier
+    let rules: Vec<_> = ["if", "(i|f|x)(i|f|x|0|1)*", "(0|1)(0|1)*"].iter().map(|s| parse(s)).collect();
+    let nfa = Nfa::union(&rules);
+    let dfa = Dfa::from_nfa(&nfa);
+    let kinds = |s: &str| dfa.run(s).map(|state| dfa.states[state].kinds.iter().cloned().collect::<Vec<_>>());
+    assert_eq!(kinds("if"), Some(vec![0, 1]));
+    assert_eq!(kinds("i"), Some(vec![1]));
+    assert_eq!(kinds("iff"), Some(vec![1]));
+    assert_eq!(kinds("101"), Some(vec![2]));
+    assert_eq!(kinds("1x"), None);
+    assert_eq!(dfa.states[dfa.run("if").unwrap()].accept(), Some(0));
+    // every DFA state is closed under ε and holds the accepting NFA states it reports
+    for state in &dfa.states {
+        assert_eq!(nfa.epsilon_closure(&state.nfa_states), state.nfa_states);
+        let accepting: std::collections::BTreeSet<_> =
+            state.nfa_states.iter().filter_map(|&s| nfa.states[s].accept).collect();
+        assert_eq!(accepting, state.kinds);
+    }
+
+    let inputs = strings(&['a', 'b', 'c'], 5);
+    let mut seed = 0x9e37_79b9;
+    for _ in 0..200 {
+        let regex = random_regex(&mut seed, 4);
+        let nfa = Nfa::thompson(&regex);
+        let dfa = Dfa::from_nfa(&nfa);
+        for s in &inputs {
+            assert_eq!(dfa.matches(s), nfa.matches(s), "{} on {:?}", regex, s);
+        }
+    }
+}
+
+// token kind of every input, or `None`, is the same for both automata
+fn assert_same_tokens(a: &dfa::Dfa, b: &dfa::Dfa, inputs: &[String]) {
+    for s in inputs {
+        assert_eq!(a.accepts(s), b.accepts(s), "{:?}", s);
+    }
+}
+
+#[test]
+fn test_hopcroft() {
+    use dfa::Dfa;
+    use minimize::hopcroft;
+    use nfa::Nfa;
+
+    let dfa = Dfa::from_nfa(&Nfa::thompson(&parse("(a|b)*abb")));
+    let min = hopcroft(&dfa);
+    assert_eq!(min.states.len(), 4);
+    assert_same_tokens(&dfa, &min, &strings(&['a', 'b'], 8));
+    assert_eq!(hopcroft(&min).states.len(), 4);
+
+    // states accepting different kinds are never merged, even where the rest agrees
+    let rules: Vec<_> = ["ab", "a(a|b)*", "b(a|b)(a|b)*"].iter().map(|s| parse(s)).collect();
+    let dfa = Dfa::from_nfa(&Nfa::union(&rules));
+    let min = hopcroft(&dfa);
+    assert!(min.states.len() < dfa.states.len());
+    assert_eq!(min.accepts("ab"), Some(0));
+    assert_eq!(min.accepts("aba"), Some(1));
+    assert_eq!(min.accepts("ba"), Some(2));
+    assert_same_tokens(&dfa, &min, &strings(&['a', 'b', 'c'], 6));
+
+    // nothing accepted: only the start state is left
+    let mut empty = Dfa::from_nfa(&Nfa::thompson(&parse("ab")));
+    for state in &mut empty.states {
+        state.kinds.clear();
+    }
+    let min = hopcroft(&empty);
+    assert_eq!(min.states.len(), 1);
+    assert!(min.states[0].transitions.is_empty());
+
+    let inputs = strings(&['a', 'b', 'c'], 6);
+    let mut seed = 0x5851_f42d;
+    for _ in 0..200 {
+        let regex = random_regex(&mut seed, 4);
+        let dfa = Dfa::from_nfa(&Nfa::thompson(&regex));
+        let min = hopcroft(&dfa);
+        assert!(min.states.len() <= dfa.states.len());
+        assert_same_tokens(&dfa, &min, &inputs);
+        // already minimal
+        assert_eq!(hopcroft(&min).states.len(), min.states.len(), "{}", regex);
+    }
+}
+
+#[test]
+fn test_brzozowski_and_equivalence() {
+    use dfa::Dfa;
+    use minimize::{brzozowski, hopcroft};
+    use nfa::Nfa;
+
+    let dfa = |s: &str| Dfa::from_nfa(&Nfa::thompson(&parse(s)));
+    let min = brzozowski(&dfa("(a|b)*abb"));
+    assert_eq!(min.states.len(), 4);
+    assert!(min.equivalent(&dfa("(a|b)*abb")));
+    assert!(min.equivalent(&dfa("(b|a)*(ab)b")));
+
+    assert_eq!(dfa("a*").distinguish(&dfa("(a|aa)*")), None);
+    assert_eq!(dfa("a*").distinguish(&dfa("aa*")), Some(String::new()));
+    assert_eq!(dfa("(a|b)*abb").distinguish(&dfa("(a|b)*bb")), Some("bb".to_string()));
+    assert_eq!(dfa("ab|ba").distinguish(&dfa("ab|bc")), Some("ba".to_string()));
+    // the same strings, recognized as different tokens
+    let rules = |rules: &[&str]| Dfa::from_nfa(&Nfa::union(&rules.iter().map(|s| parse(s)).collect::<Vec<_>>()));
+    assert_eq!(rules(&["a", "b"]).distinguish(&rules(&["b", "a"])), Some("a".to_string()));
+    assert_eq!(rules(&["ab", "a*b"]).distinguish(&rules(&["ab", "a*b|ab"])), None);
+
+    let tokens = rules(&["ab", "a(a|b)*", "b(a|b)(a|b)*"]);
+    let min = brzozowski(&tokens);
+    assert!(min.equivalent(&tokens));
+    assert_eq!(min.states.len(), hopcroft(&tokens).states.len());
+    for (state, other) in min.states.iter().zip(&hopcroft(&tokens).states) {
+        assert_eq!(state.transitions, other.transitions);
+        assert_eq!(state.kinds, other.kinds);
+        assert_eq!(state.nfa_states, other.nfa_states);
+    }
+
+    let mut seed = 0x1405_7b7e;
+    for _ in 0..200 {
+        let regex = random_regex(&mut seed, 4);
+        let dfa = Dfa::from_nfa(&Nfa::thompson(&regex));
+        let (h, b) = (hopcroft(&dfa), brzozowski(&dfa));
+        assert_eq!(h.states.len(), b.states.len(), "{}", regex);
+        assert_eq!(h.distinguish(&b), None, "{}", regex);
+        assert_eq!(dfa.distinguish(&b), None, "{}", regex);
+        // a distinguishing string tells the languages apart
+        let other = Dfa::from_nfa(&Nfa::thompson(&random_regex(&mut seed, 3)));
+        if let Some(s) = dfa.distinguish(&other) {
+            assert_ne!(dfa.matches(&s), other.matches(&s));
+        }
+    }
+}
+
+#[test]
+fn test_state_elimination() {
+    use dfa::Dfa;
+    use eliminate::{kind_to_regex, to_regex};
+    use minimize::hopcroft;
+    use nfa::Nfa;
+
+    let dfa = |r: &Regex| Dfa::from_nfa(&Nfa::thompson(r));
+    let back = |s: &str| to_regex(&hopcroft(&dfa(&parse(s)))).unwrap().to_string();
+    assert_eq!(back("(a|b)*abb"), "b*a(b*a)*bb");
+    assert_eq!(back("(ab|ac)*"), "(a(b|c))*");
+    assert_eq!(back("a(()|b)|ab*"), "ab*");
+    assert_eq!(back("(a*b*)*"), "(a|b)*");
+    assert_eq!(back("()"), "()");
+
+    // per token kind, with what earlier rules take left out
+    let tokens = Dfa::from_nfa(&Nfa::union(&[parse("ab"), parse("a*b"), parse("c")]));
+    assert_eq!(kind_to_regex(&tokens, 0).unwrap().to_string(), "ab");
+    assert_eq!(kind_to_regex(&tokens, 1).unwrap().to_string(), "b|aaa*b");
+    assert_eq!(kind_to_regex(&tokens, 3), None);
+
+    let mut seed = 0x0e11_3141;
+    for _ in 0..300 {
+        let regex = random_regex(&mut seed, 5);
+        let min = hopcroft(&dfa(&regex));
+        let back = to_regex(&min).unwrap();
+        assert!(dfa(&back).equivalent(&min), "{} -> {}", regex, back);
+        let simple = regex.simplify();
+        assert!(dfa(&simple).equivalent(&min), "{} -> {}", regex, simple);
+        assert!(simple.to_string().len() <= regex.to_string().len() + 2, "{} -> {}", regex, simple);
+    }
+}
+
+#[test]
+fn test_classes() {
+    use backtrack::is_match;
+    use dfa::Dfa;
+    use eliminate::to_regex;
+    use minimize::{brzozowski, hopcroft};
+    use nfa::Nfa;
+    use Regex::*;
+
+    assert_eq!(parse("[a-z0-9_]"), Class(vec![('0', '9'), ('_', '_'), ('a', 'z')]));
+    assert_eq!(parse("[^\\n]"), parse("."));
+    assert_eq!(parse("[a]"), Char('a'));
+    assert_eq!(parse("[-a-]"), Class(vec![('-', '-'), ('a', 'a')]));
+    assert_eq!(parse("[a-c-e]"), Class(vec![('-', '-'), ('a', 'c'), ('e', 'e')]));
+    assert_eq!(parse("[a^]"), parse("[\\^a]"));
+    assert_eq!(parse("[b-da-c]"), parse("[a-d]"));
+    assert_eq!(parse("[\\d\\s]"), Class(vec![('\t', '\r'), (' ', ' '), ('0', '9')]));
+    assert_eq!(parse("[α-ω]"), Class(vec![('α', 'ω')]));
+    assert_eq!(parse("\\u{1F600}"), Char('😀'));
+    assert_eq!(parse("[\\u{d7ff}-\\u{e000}]"), Class(vec![('\u{d7ff}', '\u{e000}')]));
+    assert_eq!(parse("a{3}"), parse("aaa"));
+    assert_eq!(parse("a{2,}"), parse("aaa*"));
+    assert_eq!(parse("a{1,3}"), parse("a(()|a)(()|a)"));
+    assert_eq!(parse("(ab){0}c"), parse("c"));
+    assert_eq!(parse("a{0,1}*"), parse("(()|a)*"));
+    // the largest count prints flat and parses back
+    let re = parse(&format!("(ab){{0,{}}}", MAX_REPEAT));
+    assert_eq!(parse(&re.to_string()), re);
+    assert!(nfa::Nfa::thompson(&re).matches(&"ab".repeat(3)));
+
+    for s in &["[_a-z]", ".", "\\d", "\\w*", "\\s|x", "[^a]", "[^\\-\\]]", "[ab]", "[\\^a]", "[\\na-f]", "[(*.]", "é[α-ω]"] {
+        assert_eq!(parse(s).to_string(), *s);
+    }
+    assert_eq!(parse("\\D").to_string(), "[^0-9]");
+    assert_eq!(parse("[\\u{0}-\\u{10FFFF}]").to_string(), "[\\u{0}-\u{10ffff}]");
+    assert_eq!(parse("\\u{7f}").to_string(), "\\u{7f}");
+
+    assert_eq!(error("[a-z"), (0, ErrorKind::UnclosedClass));
+    assert_eq!(error("a[]"), (1, ErrorKind::EmptyClass));
+    assert_eq!(error("[^\\s\\S]"), (0, ErrorKind::EmptyClass));
+    assert_eq!(error("[xz-a]"), (2, ErrorKind::ReversedRange('z', 'a')));
+    assert_eq!(error("a{2"), (1, ErrorKind::BadRepetition));
+    assert_eq!(error("a{3,2}"), (1, ErrorKind::BadRepetition));
+    assert_eq!(error("a{,2}"), (1, ErrorKind::BadRepetition));
+    assert_eq!(error("a{1001}"), (1, ErrorKind::BadRepetition));
+    // counts stacked or nested multiply, the first one past the budget is the error
+    assert_eq!(error("a{1000}{1000}{1000}"), (7, ErrorKind::TooLarge));
+    assert_eq!(error("((a{1000}){1000}){1000}"), (10, ErrorKind::TooLarge));
+    assert_eq!(error("a{2}(a{1000}){1000}b{1000}{1000}"), (13, ErrorKind::TooLarge));
+    assert!(Regex::parse(&format!("(a{{1000}}){{{}}}", MAX_SIZE / 1001)).is_ok());
+    assert_eq!(error("{2}"), (0, ErrorKind::NothingToRepeat));
+    assert_eq!(error("a|{2}"), (2, ErrorKind::NothingToRepeat));
+    assert_eq!(error("\\u{d800}"), (0, ErrorKind::BadCodePoint));
+    assert_eq!(error("\\u41"), (0, ErrorKind::BadCodePoint));
+    assert_eq!(error("a]"), (1, ErrorKind::Reserved(']')));
+
+    // a class is one transition per range, however many characters it holds
+    let dfa = |s: &str| Dfa::from_nfa(&Nfa::thompson(&parse(s)));
+    let names = hopcroft(&dfa("[a-zA-Z_][a-zA-Z_0-9]*"));
+    assert_eq!(names.states.len(), 2);
+    assert_eq!(names.states[1].transitions.len(), 4);
+    assert_eq!(hopcroft(&dfa(".*")).states[0].transitions, vec![('\0', '\t', 0), ('\u{b}', char::MAX, 0)]);
+    assert!(names.matches("x_1") && !names.matches("1x"));
+    assert!(dfa("[^a-c]").matches("é") && !dfa("[^a-c]").matches("b"));
+    assert_eq!(dfa("[a-c]*").distinguish(&dfa("(a|b|c)*")), None);
+    assert_eq!(dfa("\\w*").distinguish(&dfa("[a-z]*")), Some("0".to_string()));
+    assert_eq!(dfa("[^x]").distinguish(&dfa(".")), Some("\n".to_string()));
+    for s in &["(a|[b-y])[a-z]*z", "\\d{2,4}|[^0-9]", ".[\\u{100}-\\u{10ffff}]*|\\s"] {
+        let (h, b) = (hopcroft(&dfa(s)), brzozowski(&dfa(s)));
+        assert_eq!(h.states.len(), b.states.len(), "{}", s);
+        for (state, other) in h.states.iter().zip(&b.states) {
+            assert_eq!(state.transitions, other.transitions, "{}", s);
+        }
+    }
+    let back = |s: &str| to_regex(&hopcroft(&dfa(s))).unwrap().to_string();
+    assert_eq!(back("[a-z]|[0-9]|_"), "[0-9_a-z]");
+    assert_eq!(back("[a-z]|[A-Z]|[0-9]|_"), "\\w");
+    assert_eq!(back("(a|[b-y])z|z[a-z]"), "[a-y]z|z[a-z]");
+    assert_eq!(back("[ab]"), "a|b");
+
+    let inputs = strings(&['a', 'b', '-', '0', '\n', 'λ'], 4);
+    for s in &["[a-b]*", "[^b]{1,2}", "\\d|\\w\\s*|.", "[a\\-0]{2,}", "(\\S|b{0,3})*", "[λ-λ]a{0}", "\\W{3}"] {
+        let regex = parse(s);
+        let nfa = Nfa::thompson(&regex);
+        let dfa = Dfa::from_nfa(&nfa);
+        let back = to_regex(&hopcroft(&dfa)).unwrap();
+        for input in &inputs {
+            let expected = is_match(&regex, input);
+            assert_eq!(nfa.matches(input), expected, "{} on {:?}", s, input);
+            assert_eq!(dfa.matches(input), expected, "{} on {:?}", s, input);
+            assert_eq!(is_match(&back, input), expected, "{} -> {} on {:?}", s, back, input);
+        }
+        assert_eq!(parse(&regex.to_string()), regex);
+    }
+}
+#[test]
+fn test_parse_long() {
+    // as long as the left recursive spines of the parse tree
+    assert!(Regex::parse(&"a".repeat(200_000)).is_ok());
+    assert!(Regex::parse(&("a|".repeat(100_000) + "a")).is_ok());
+}
@@ -0,0 +1,322 @@
+//! Rust source for a scanner, in the styles of EaC section 2.5: a driver over transition
+//! tables, code with the DFA built into its control flow, or that code dispatching on
+//! character classes from a table.
+//!
+//! The output is a list of items to put in a module of their own:
+//!
+//! - `KINDS` and `SKIP`, the rule names and whether their text is dropped, indexed by kind
+//! - `munch(input) -> Option<(usize, usize)>`, the length in bytes and the kind of the longest
+//!   prefix of `input` some rule matches
+//! - `scan(input) -> Result<Vec<(usize, &str)>, usize>`, the kinds and lexemes of the tokens
+//!   of `input` or the byte offset no token starts at
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::regex::dfa::{Dfa, StateId};
+use crate::regex::ranges;
+use crate::scanner::Scanner;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Style {
+    /// A generic driver over character classes and a row-displaced transition table.
+    Table,
+    /// A `match` per state on the characters themselves.
+    Direct,
+    /// A `match` per state on the character classes of `Table`.
+    Hybrid,
+}
+
+/// The DFA of a scanner as compressed tables. Characters that no state tells apart share a
+/// class, so a row needs one entry per class instead of one per character; the rows are then
+/// overlaid in one vector, each shifted by its `base`, with `check` recording which row an
+/// entry belongs to.
+#[derive(Debug, Clone)]
+pub struct Tables {
+    pub start: StateId,
+    /// Disjoint ranges of characters, sorted, with their class. Characters outside all of them
+    /// are in class 0, which has no transitions.
+    pub classes: Vec<(char, char, usize)>,
+    pub class_count: usize,
+    pub base: Vec<usize>,
+    pub next: Vec<StateId>,
+    pub check: Vec<Option<StateId>>,
+    pub accept: Vec<Option<usize>>,
+}
+
+impl Tables {
+    pub fn new(dfa: &Dfa) -> Tables {
+        let (classes, class_count) = classes(dfa);
+        // a representative character per class gives its column
+        let mut members = vec![None; class_count];
+        for &(lo, _, class) in &classes {
+            members[class].get_or_insert(lo);
+        }
+        let rows: Vec<Vec<(usize, StateId)>> = dfa
+            .states
+            .iter()
+            .map(|s| {
+                let column = |class: usize| members[class].and_then(|c| ranges::lookup(&s.transitions, c));
+                (1..class_count).filter_map(|class| column(class).map(|to| (class, to))).collect()
+            })
+            .collect();
+
+        // first fit, densest rows first
+        let mut order: Vec<_> = (0..rows.len()).collect();
+        order.sort_by_key(|&s| std::cmp::Reverse(rows[s].len()));
+        let mut base = vec![0; rows.len()];
+        let mut next = vec![];
+        let mut check = vec![];
+        for s in order {
+            let free = |i: usize| check.get(i).is_none_or(Option::is_none);
+            let fits = |b: usize| rows[s].iter().all(|&(class, _)| free(b + class));
+            let b = (0..).find(|&b| fits(b)).unwrap();
+            base[s] = b;
+            for &(class, to) in &rows[s] {
+                if next.len() <= b + class {
+                    next.resize(b + class + 1, 0);
+                    check.resize(b + class + 1, None);
+                }
+                next[b + class] = to;
+                check[b + class] = Some(s);
+            }
+        }
+        // every row can be indexed by any class without running off the end
+        let len = base.iter().map(|b| b + class_count).max().unwrap_or(0);
+        next.resize(len, 0);
+        check.resize(len, None);
+
+        Tables {
+            start: dfa.start,
+            classes,
+            class_count,
+            base,
+            next,
+            check,
+            accept: dfa.states.iter().map(|s| s.accept()).collect(),
+        }
+    }
+
+    pub fn class(&self, c: char) -> usize {
+        ranges::lookup(&self.classes, c).unwrap_or(0)
+    }
+
+    pub fn next(&self, state: StateId, c: char) -> Option<StateId> {
+        let i = self.base[state] + self.class(c);
+        match self.check[i] {
+            Some(s) if s == state => Some(self.next[i]),
+            _ => None,
+        }
+    }
+}
+
+// characters with the same transition in every state share a class, numbered from 1 in order
+// of their first character; returns the classes as ranges and the number of classes
+fn classes(dfa: &Dfa) -> (Vec<(char, char, usize)>, usize) {
+    let edges = dfa.states.iter().flat_map(|s| &s.transitions).map(|&(lo, hi, _)| (lo, hi));
+    let mut ids = HashMap::new();
+    let mut ret = vec![];
+    for (lo, hi) in ranges::partition(edges) {
+        let column: Vec<_> = dfa.states.iter().map(|s| ranges::lookup(&s.transitions, lo)).collect();
+        let len = ids.len();
+        let class = *ids.entry(column).or_insert(len + 1);
+        ranges::push(&mut ret, lo, hi, class);
+    }
+    let count = ids.len() + 1;
+    (ret, count)
+}
+
+/// Source of a module scanning like `scanner`, in `style`.
+pub fn generate(scanner: &Scanner, style: Style) -> String {
+    let rules = scanner.rules();
+    let dfa = scanner.dfa();
+    let mut out = String::new();
+    let names: Vec<_> = rules.iter().map(|r| format!("{:?}", r.name)).collect();
+    let skip: Vec<_> = rules.iter().map(|r| r.skip.to_string()).collect();
+    let (n, states) = (rules.len(), dfa.states.len());
+    writeln!(out, "// {:?} scanner generated from {} rules and {} states", style, n, states).unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "pub const KINDS: [&str; {}] = [{}];", rules.len(), names.join(", ")).unwrap();
+    writeln!(out, "pub const SKIP: [bool; {}] = [{}];", rules.len(), skip.join(", ")).unwrap();
+    writeln!(out).unwrap();
+    match style {
+        Style::Table => table(&mut out, &Tables::new(dfa)),
+        Style::Direct => direct(&mut out, dfa),
+        Style::Hybrid => hybrid(&mut out, dfa, &Tables::new(dfa)),
+    }
+    out.push_str(SCAN);
+    out
+}
+
+const SCAN: &str = "
+pub fn scan(input: &str) -> Result<Vec<(usize, &str)>, usize> {
+    let mut ret = Vec::new();
+    let mut offset = 0;
+    while offset < input.len() {
+        let (len, kind) = munch(&input[offset..]).ok_or(offset)?;
+        if !SKIP[kind] {
+            ret.push((kind, &input[offset..offset + len]));
+        }
+        offset += len;
+    }
+    Ok(ret)
+}
+";
+
+fn table(out: &mut String, tables: &Tables) {
+    let none = tables.base.len();
+    let check: Vec<_> = tables.check.iter().map(|c| c.unwrap_or(none)).collect();
+    let accept: Vec<_> = tables.accept.iter().map(|a| format!("{:?}", a)).collect();
+    writeln!(out, "const START: usize = {};", tables.start).unwrap();
+    class_table(out, tables);
+    array(out, "BASE", "u32", &tables.base);
+    array(out, "NEXT", "u32", &tables.next);
+    writeln!(out, "// {} for entries of no row", none).unwrap();
+    array(out, "CHECK", "u32", &check);
+    writeln!(out, "const ACCEPT: [Option<usize>; {}] = [{}];", accept.len(), accept.join(", ")).unwrap();
+    out.push_str(
+        "
+pub fn munch(input: &str) -> Option<(usize, usize)> {
+    let mut state = START;
+    let mut last = None;
+    for (i, c) in input.char_indices() {
+        let at = BASE[state] as usize + class(c);
+        if CHECK[at] as usize != state {
+            break;
+        }
+        state = NEXT[at] as usize;
+        if let Some(kind) = ACCEPT[state] {
+            last = Some((i + c.len_utf8(), kind));
+        }
+    }
+    last
+}
+",
+    );
+}
+
+fn direct(out: &mut String, dfa: &Dfa) {
+    let arms = dfa.states.iter().map(|s| {
+        let mut targets: Vec<(StateId, Vec<String>)> = vec![];
+        for &(lo, hi, to) in &s.transitions {
+            let pattern = if lo == hi { format!("{:?}", lo) } else { format!("{:?}..={:?}", lo, hi) };
+            match targets.iter_mut().find(|(t, _)| *t == to) {
+                Some((_, patterns)) => patterns.push(pattern),
+                None => targets.push((to, vec![pattern])),
+            }
+        }
+        let ranges: Vec<_> = s.transitions.iter().map(|&(lo, hi, _)| (lo, hi)).collect();
+        let arms = targets.into_iter().map(|(to, patterns)| (patterns.join(" | "), to)).collect();
+        (arms, ranges::negate(&ranges).is_empty())
+    });
+    dispatch(out, dfa, "c", arms.collect());
+}
+
+fn hybrid(out: &mut String, dfa: &Dfa, tables: &Tables) {
+    class_table(out, tables);
+    let arms = dfa.states.iter().map(|s| {
+        let mut targets: Vec<(StateId, Vec<String>)> = vec![];
+        for class in 1..tables.class_count {
+            let c = tables.classes.iter().find(|r| r.2 == class).unwrap().0;
+            if let Some(to) = ranges::lookup(&s.transitions, c) {
+                match targets.iter_mut().find(|(t, _)| *t == to) {
+                    Some((_, patterns)) => patterns.push(class.to_string()),
+                    None => targets.push((to, vec![class.to_string()])),
+                }
+            }
+        }
+        // a `usize` is never covered
+        (targets.into_iter().map(|(to, patterns)| (patterns.join(" | "), to)).collect(), false)
+    });
+    dispatch(out, dfa, "class(c)", arms.collect());
+}
+
+// the driver of the direct-coded styles: a `match` on the state, in which a `match` on
+// `scrutinee` picks the next state from `arms`, patterns and targets per state along with
+// whether they cover every value of `scrutinee`, and need no arm to stop at
+fn dispatch(out: &mut String, dfa: &Dfa, scrutinee: &str, arms: Vec<(Vec<(String, StateId)>, bool)>) {
+    out.push_str("pub fn munch(input: &str) -> Option<(usize, usize)> {\n");
+    writeln!(out, "    let mut state = {};", dfa.start).unwrap();
+    out.push_str(
+        "    let mut last = None;
+    for (i, c) in input.char_indices() {
+        state = match state {
+",
+    );
+    for (s, (arms, total)) in arms.iter().enumerate() {
+        if arms.is_empty() {
+            writeln!(out, "            {} => break,", s).unwrap();
+            continue;
+        }
+        writeln!(out, "            {} => match {} {{", s, scrutinee).unwrap();
+        for (pattern, to) in arms {
+            writeln!(out, "                {} => {},", pattern, to).unwrap();
+        }
+        if !total {
+            out.push_str("                _ => break,\n");
+        }
+        out.push_str("            },\n");
+    }
+    out.push_str(
+        "            _ => unreachable!(),
+        };
+        let kind = match state {
+",
+    );
+    let mut accepting: Vec<(usize, Vec<String>)> = vec![];
+    for (s, state) in dfa.states.iter().enumerate() {
+        if let Some(kind) = state.accept() {
+            match accepting.iter_mut().find(|(k, _)| *k == kind) {
+                Some((_, states)) => states.push(s.to_string()),
+                None => accepting.push((kind, vec![s.to_string()])),
+            }
+        }
+    }
+    for (kind, states) in accepting {
+        writeln!(out, "            {} => {},", states.join(" | "), kind).unwrap();
+    }
+    // the start state never accepts, rules don't match the empty string, so the arm is reached
+    out.push_str(
+        "            _ => continue,
+        };
+        last = Some((i + c.len_utf8(), kind));
+    }
+    last
+}
+",
+    );
+}
+
+fn class_table(out: &mut String, tables: &Tables) {
+    let ranges: Vec<_> = tables
+        .classes
+        .iter()
+        .map(|&(lo, hi, class)| format!("({:?}, {:?}, {})", lo, hi, class))
+        .collect();
+    writeln!(out, "const CLASSES: [(char, char, usize); {}] = [{}];", ranges.len(), ranges.join(", ")).unwrap();
+    out.push_str(
+        "
+fn class(c: char) -> usize {
+    let found = CLASSES.binary_search_by(|&(lo, hi, _)| {
+        if hi < c {
+            std::cmp::Ordering::Less
+        } else if lo > c {
+            std::cmp::Ordering::Greater
+        } else {
+            std::cmp::Ordering::Equal
+        }
+    });
+    match found {
+        Ok(i) => CLASSES[i].2,
+        Err(_) => 0,
+    }
+}
+
+",
+    );
+}
+
+fn array(out: &mut String, name: &str, ty: &str, values: &[usize]) {
+    let values: Vec<_> = values.iter().map(|v| v.to_string()).collect();
+    writeln!(out, "const {}: [{}; {}] = [{}];", name, ty, values.len(), values.join(", ")).unwrap();
+}
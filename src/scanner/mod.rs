@@ -0,0 +1,222 @@
+pub mod codegen;
+
+use std::cmp::Reverse;
+use std::fmt;
+
+use crate::parser::token::{Span, Token};
+use crate::parser::Terminal;
+use crate::regex::dfa::Dfa;
+use crate::regex::eliminate::kind_to_regex;
+use crate::regex::minimize::hopcroft;
+use crate::regex::nfa::Nfa;
+use crate::regex::{parse, Regex};
+
+#[cfg(test)]
+mod test;
+
+/// A token and the regex it is written as.
+#[derive(Debug, Clone)]
+pub struct Rule {
+    pub name: String,
+    pub pattern: String,
+    /// Decides between rules matching the same longest text, higher first. Rules of equal
+    /// priority go by their order.
+    pub priority: i32,
+    /// Matched text is dropped, as for whitespace and comments.
+    pub skip: bool,
+}
+
+impl Rule {
+    pub fn token<S: Into<String>, P: Into<String>>(name: S, pattern: P) -> Self {
+        Rule {
+            name: name.into(),
+            pattern: pattern.into(),
+            priority: 0,
+            skip: false,
+        }
+    }
+
+    pub fn skip<S: Into<String>, P: Into<String>>(name: S, pattern: P) -> Self {
+        Rule {
+            skip: true,
+            ..Rule::token(name, pattern)
+        }
+    }
+
+    pub fn priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+}
+
+/// Why a set of rules can't make a scanner.
+#[derive(Debug, Clone)]
+pub enum BuildError {
+    Regex(String, parse::Error),
+    /// The rule matches the empty string, so scanning could get stuck on it.
+    MatchesEmpty(String),
+    /// Two rules have this name, so tokens of that kind couldn't be told apart.
+    DuplicateName(String),
+}
+
+/// No rule matches any text at `offset`, a byte offset into the input, which is on `line`
+/// and `column` counted as in `Span`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ScanError {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// All rules compiled into one minimal DFA, whose token kinds are indices into `rules`
+/// sorted by priority.
+#[derive(Debug, Clone)]
+pub struct Scanner {
+    rules: Vec<Rule>,
+    terminals: Vec<Terminal>,
+    dfa: Dfa,
+}
+
+impl Scanner {
+    pub fn new(mut rules: Vec<Rule>) -> Result<Scanner, BuildError> {
+        // stable, so equal priorities keep their order
+        rules.sort_by_key(|r| Reverse(r.priority));
+        let mut regexes = vec![];
+        for (i, rule) in rules.iter().enumerate() {
+            if rules[..i].iter().any(|r| r.name == rule.name) {
+                return Err(BuildError::DuplicateName(rule.name.clone()));
+            }
+            let regex = Regex::parse(&rule.pattern).map_err(|e| BuildError::Regex(rule.name.clone(), e))?;
+            if regex.nullable() {
+                return Err(BuildError::MatchesEmpty(rule.name.clone()));
+            }
+            regexes.push(regex);
+        }
+        let dfa = hopcroft(&Dfa::from_nfa(&Nfa::union(&regexes)));
+        let terminals = rules.iter().map(|r| Terminal::new(&r.name)).collect();
+        Ok(Scanner { rules, terminals, dfa })
+    }
+
+    /// The rules by priority, so that a token kind of the DFA indexes them.
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    pub fn dfa(&self) -> &Dfa {
+        &self.dfa
+    }
+
+    /// What the scanner makes a `name` token of, as a regex: the rule's pattern less what
+    /// rules before it take. `None` if that is nothing or there is no such rule.
+    pub fn describe(&self, name: &str) -> Option<Regex> {
+        let kind = self.rules.iter().position(|r| r.name == name)?;
+        kind_to_regex(&self.dfa, kind)
+    }
+
+    /// The tokens of `input`, lazily. Skipped text is left out.
+    pub fn scan<'a, 's>(&'a self, input: &'s str) -> Tokens<'a, 's> {
+        Tokens {
+            scanner: self,
+            input,
+            offset: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+
+    /// Just the kinds of the tokens of `input`.
+    pub fn terminals(&self, input: &str) -> Result<Vec<Terminal>, ScanError> {
+        self.scan(input).map(|t| t.map(|t| t.kind)).collect()
+    }
+
+    // the longest prefix of `input` some rule matches, as its length and token kind: runs
+    // the DFA as far as it goes and rolls back to the last accepting state it passed
+    fn munch(&self, input: &str) -> Option<(usize, usize)> {
+        let mut state = self.dfa.start;
+        let mut last = None;
+        for (i, c) in input.char_indices() {
+            state = match self.dfa.next(state, c) {
+                Some(s) => s,
+                None => break,
+            };
+            if let Some(kind) = self.dfa.states[state].accept() {
+                last = Some((i + c.len_utf8(), kind));
+            }
+        }
+        last
+    }
+}
+
+pub struct Tokens<'a, 's> {
+    scanner: &'a Scanner,
+    input: &'s str,
+    offset: usize,
+    line: usize,
+    column: usize,
+}
+
+impl<'a, 's> Tokens<'a, 's> {
+    // moves past the next `len` bytes and returns their span
+    fn advance(&mut self, len: usize) -> Span {
+        let (start, line, column) = (self.offset, self.line, self.column);
+        for c in self.input[start..start + len].chars() {
+            if c == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+        }
+        self.offset += len;
+        Span {
+            start,
+            end: self.offset,
+            line,
+            column,
+        }
+    }
+}
+
+impl<'a, 's> Iterator for Tokens<'a, 's> {
+    type Item = Result<Token, ScanError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.offset < self.input.len() {
+            let rest = &self.input[self.offset..];
+            let (len, kind) = match self.scanner.munch(rest) {
+                Some(m) => m,
+                None => {
+                    let err = ScanError {
+                        offset: self.offset,
+                        line: self.line,
+                        column: self.column,
+                    };
+                    // nothing sensible follows an error
+                    self.offset = self.input.len();
+                    return Some(Err(err));
+                }
+            };
+            let span = self.advance(len);
+            if !self.scanner.rules[kind].skip {
+                return Some(Ok(Token::new(self.scanner.terminals[kind].clone(), &rest[..len], span)));
+            }
+        }
+        None
+    }
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BuildError::Regex(rule, e) => write!(f, "rule `{}`: {}", rule, e),
+            BuildError::MatchesEmpty(rule) => write!(f, "rule `{}` matches the empty string", rule),
+            BuildError::DuplicateName(rule) => write!(f, "there is more than one rule `{}`", rule),
+        }
+    }
+}
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "at {}:{}: no token starts here", self.line, self.column)
+    }
+}
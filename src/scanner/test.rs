@@ -0,0 +1,324 @@
+use super::*;
+use crate::grammar;
+use crate::parser::backtrack_parse::backtrack_parse;
+use crate::parser::render::{to_sexpr, RenderOptions};
+
+// `(a|b|c)` for the characters of `chars`, escaped where needed
+fn one_of(chars: &str) -> String {
+    let alternatives: Vec<_> = chars.chars().map(|c| Regex::Char(c).to_string()).collect();
+    format!("({})", alternatives.join("|"))
+}
+
+fn expr_scanner() -> Scanner {
+    let digit = one_of("0123456789");
+    let letter = one_of("abcdefghijklmnopqrstuvwxyz_");
+    Scanner::new(vec![
+        Rule::skip("ws", format!("{}{}*", one_of(" \t\n"), one_of(" \t\n")))
+            .priority(1),
+        Rule::skip("comment", format!("//{}*\n", one_of("abcdefghijklmnopqrstuvwxyz0123456789 +-*/()"))),
+        Rule::token("num", format!("{}{}*", digit, digit)),
+        Rule::token("if", "if").priority(1),
+        Rule::token("name", format!("{}({}|{})*", letter, letter, digit)),
+        Rule::token("+", "\\+"),
+        Rule::token("-", "-"),
+        Rule::token("*", "\\*"),
+        Rule::token("/", "/"),
+        Rule::token("(", "\\("),
+        Rule::token(")", "\\)"),
+    ])
+    .unwrap()
+}
+
+// the same kinds of token as far as ASCII goes, written with classes, and with letters and
+// strings from all of Unicode
+fn class_scanner() -> Scanner {
+    Scanner::new(vec![
+        Rule::skip("ws", "\\s\\s*"),
+        Rule::token("num", "\\d{1,9}"),
+        Rule::token("name", "[_a-zA-Zß-ÿα-ω\\u{100}-\\u{17f}][\\w\\u{80}-\\u{10ffff}]*"),
+        Rule::token("string", "\"[^\"\\n]*\""),
+        Rule::token("op", "[-+*/=,]"),
+        Rule::token("other", "."),
+    ])
+    .unwrap()
+}
+
+fn line_scanner() -> Scanner {
+    Scanner::new(vec![Rule::token("line", "[^\\n]*\\n"), Rule::token("rest", "[^\\n][^\\n]*")]).unwrap()
+}
+
+fn scan(scanner: &Scanner, input: &str) -> Vec<(String, String)> {
+    scanner.scan(input).map(|t| t.unwrap()).map(|t| (t.kind.to_string(), t.lexeme)).collect()
+}
+
+#[test]
+fn test_scanner() {
+    let scanner = expr_scanner();
+    assert_eq!(scan(&scanner, "x1 + 42*(if_ - iff) // note\n/if"), vec![
+        ("name".to_string(), "x1".to_string()),
+        ("+".to_string(), "+".to_string()),
+        ("num".to_string(), "42".to_string()),
+        ("*".to_string(), "*".to_string()),
+        ("(".to_string(), "(".to_string()),
+        ("name".to_string(), "if_".to_string()),
+        ("-".to_string(), "-".to_string()),
+        ("name".to_string(), "iff".to_string()),
+        (")".to_string(), ")".to_string()),
+        ("/".to_string(), "/".to_string()),
+        ("if".to_string(), "if".to_string()),
+    ]);
+    assert_eq!(scan(&scanner, "  \n "), vec![]);
+    // `//` without the end of its line is two divisions
+    assert_eq!(scanner.terminals("1//2").unwrap(), vec![
+        Terminal::new("num"),
+        Terminal::new("/"),
+        Terminal::new("/"),
+        Terminal::new("num"),
+    ]);
+
+    let err = scanner.terminals("ab + ?c").unwrap_err();
+    assert_eq!(err, ScanError { offset: 5, line: 1, column: 6 });
+    assert_eq!(err.to_string(), "at 1:6: no token starts here");
+    let tokens: Vec<_> = scanner.scan("a\n é").collect();
+    assert_eq!(tokens.len(), 2);
+    assert_eq!(tokens[1], Err(ScanError { offset: 3, line: 2, column: 2 }));
+
+    // the output is what the parsers take
+    let cfg = grammar! {
+        Expr -> Expr "+" Term | Term;
+        Term -> Term "*" Factor | Factor;
+        Factor -> "(" Expr ")" | "num" | "name";
+    };
+    assert!(backtrack_parse(&cfg, &scanner.terminals("(a + 1) * b2").unwrap()).is_ok());
+    assert!(backtrack_parse(&cfg, &scanner.terminals("(a + 1) b2").unwrap()).is_err());
+}
+
+#[test]
+fn test_maximal_munch() {
+    // `abc` gets as far as `abca` before it fails and backs up to `ab`
+    let scanner = Scanner::new(vec![
+        Rule::token("a", "a"),
+        Rule::token("ab", "ab"),
+        Rule::token("abcd", "abcd"),
+        Rule::token("c", "c"),
+    ])
+    .unwrap();
+    assert_eq!(scan(&scanner, "abcab"), vec![
+        ("ab".to_string(), "ab".to_string()),
+        ("c".to_string(), "c".to_string()),
+        ("ab".to_string(), "ab".to_string()),
+    ]);
+    assert_eq!(scan(&scanner, "abcda"), vec![("abcd".to_string(), "abcd".to_string()), ("a".to_string(), "a".to_string())]);
+    assert_eq!(scanner.terminals("abcx"), Err(ScanError { offset: 3, line: 1, column: 4 }));
+
+    // equal length: priority first, then the order of the rules
+    let rules = vec![Rule::token("name", "(a|b)(a|b)*"), Rule::token("kw", "ab")];
+    let scanner = Scanner::new(rules.clone()).unwrap();
+    assert_eq!(scan(&scanner, "ab"), vec![("name".to_string(), "ab".to_string())]);
+    let scanner = Scanner::new(vec![rules[0].clone(), rules[1].clone().priority(1)]).unwrap();
+    assert_eq!(scan(&scanner, "ab"), vec![("kw".to_string(), "ab".to_string())]);
+    assert_eq!(scan(&scanner, "aba"), vec![("name".to_string(), "aba".to_string())]);
+    // one minimal DFA for all rules: the start, after `a`, after `ab` and after any other name
+    assert_eq!(scanner.dfa().states.len(), 4);
+    // what the rules come to once the keyword takes `ab`
+    assert_eq!(scanner.describe("kw").unwrap().to_string(), "ab");
+    assert_eq!(scanner.describe("name").unwrap().to_string(), "a|(b|a(a|b(a|b)))(a|b)*");
+    let shadowed = Scanner::new(rules).unwrap();
+    assert_eq!(shadowed.describe("kw"), None);
+    assert_eq!(shadowed.describe("nothing"), None);
+
+    let err = Scanner::new(vec![Rule::token("a", "a"), Rule::token("as", "a*")]).unwrap_err();
+    assert_eq!(err.to_string(), "rule `as` matches the empty string");
+    let err = Scanner::new(vec![Rule::token("ws", " "), Rule::skip("ws", "\t")]).unwrap_err();
+    assert_eq!(err.to_string(), "there is more than one rule `ws`");
+    let err = Scanner::new(vec![Rule::token("as", "(()|a)(a|())*")]).unwrap_err();
+    assert_eq!(err.to_string(), "rule `as` matches the empty string");
+    let err = Scanner::new(vec![Rule::token("bad", "a|")]).unwrap_err();
+    assert_eq!(err.to_string(), "rule `bad`: at 2: expected a character or `(`, found the end of the regex");
+}
+
+#[test]
+fn test_spans() {
+    let scanner = expr_scanner();
+    let spans = |scanner: &Scanner, input| -> Vec<_> {
+        scanner.scan(input).map(|t| t.unwrap().span).map(|s| (s.start, s.end, s.to_string())).collect()
+    };
+    assert_eq!(spans(&scanner, "a +\n  (x1)\n"), vec![
+        (0, 1, "1:1".to_string()),
+        (2, 3, "1:3".to_string()),
+        (6, 7, "2:3".to_string()),
+        (7, 9, "2:4".to_string()),
+        (9, 10, "2:6".to_string()),
+    ]);
+    let tokens: Vec<_> = scanner.scan("\n\n12").map(|t| t.unwrap()).collect();
+    assert_eq!(tokens, vec![Token::new(Terminal::new("num"), "12", Span { start: 2, end: 4, line: 3, column: 1 })]);
+    // `é` is two bytes but one column
+    let words = Scanner::new(vec![Rule::token("word", "éé*"), Rule::skip("ws", " ")]).unwrap();
+    assert_eq!(spans(&words, "éé é"), vec![(0, 4, "1:1".to_string()), (5, 7, "1:4".to_string())]);
+
+    // the tokens go into the tree and the errors as they are
+    let cfg = grammar! {
+        Expr -> Expr "+" Term | Term;
+        Term -> "(" Expr ")" | "num" | "name";
+    };
+    let tokens: Vec<_> = scanner.scan("1 + (x + 22)").map(|t| t.unwrap()).collect();
+    let tree = backtrack_parse(&cfg, &tokens).unwrap();
+    assert_eq!(tree.leaves(), tokens.iter().collect::<Vec<_>>());
+    assert_eq!(to_sexpr(&tree, &RenderOptions::new()), "(Expr (Expr (Term 1)) + (Term \"(\" (Expr (Expr (Term x)) + (Term 22)) \")\"))");
+
+    let tokens: Vec<_> = scanner.scan("1 +\n  (x 22)").map(|t| t.unwrap()).collect();
+    let err = backtrack_parse(&cfg, &tokens).unwrap_err();
+    assert_eq!(err.position, 4);
+    assert_eq!(err.found.as_ref(), Some(&tokens[4]));
+    assert_eq!(err.to_string(), "at 2:6: unexpected num `22`");
+    let err = backtrack_parse(&cfg, &tokens[..3]).unwrap_err();
+    assert_eq!((err.position, err.found), (3, None));
+}
+
+#[test]
+fn test_tables() {
+    use codegen::Tables;
+
+    let scanner = expr_scanner();
+    let dfa = scanner.dfa();
+    let tables = Tables::new(dfa);
+    let chars: Vec<_> = (0u8..128).map(char::from).chain("éλ".chars()).collect();
+    for s in 0..dfa.states.len() {
+        for &c in &chars {
+            assert_eq!(tables.next(s, c), dfa.next(s, c), "{} {:?}", s, c);
+        }
+    }
+    // digits other than the first of a name all go the same way, and so do the letters
+    // other than `i` and `f`
+    assert_eq!(tables.class('1'), tables.class('9'));
+    assert_eq!(tables.class('a'), tables.class('z'));
+    assert_ne!(tables.class('i'), tables.class('a'));
+    assert_eq!(tables.class('é'), 0);
+    assert!(tables.class_count < 20);
+    assert!(tables.next.len() < dfa.states.len() * tables.class_count);
+}
+
+#[test]
+fn test_classes() {
+    use codegen::Tables;
+
+    let scanner = class_scanner();
+    assert_eq!(scan(&scanner, "Straße=\"wörld\"\t12 λ😀"), vec![
+        ("name".to_string(), "Straße".to_string()),
+        ("op".to_string(), "=".to_string()),
+        ("string".to_string(), "\"wörld\"".to_string()),
+        ("num".to_string(), "12".to_string()),
+        ("name".to_string(), "λ😀".to_string()),
+    ]);
+    // at most nine digits to a number
+    assert_eq!(scanner.terminals("1234567890").unwrap(), vec![Terminal::new("num"), Terminal::new("num")]);
+    assert_eq!(scan(&scanner, "😀\"x"), vec![
+        ("other".to_string(), "😀".to_string()),
+        ("other".to_string(), "\"".to_string()),
+        ("name".to_string(), "x".to_string()),
+    ]);
+
+    // ranges keep the automaton and its tables small, though the rules span a million characters
+    let dfa = scanner.dfa();
+    assert!(dfa.states.len() < 20);
+    assert!(dfa.states.iter().map(|s| s.transitions.len()).sum::<usize>() < 60);
+    let tables = Tables::new(dfa);
+    assert!(tables.class_count < 20);
+    let chars: Vec<_> = (0u8..128).map(char::from).chain("ßÿĀſƀαω😀\u{10ffff}".chars()).collect();
+    for s in 0..dfa.states.len() {
+        for &c in &chars {
+            assert_eq!(tables.next(s, c), dfa.next(s, c), "{} {:?}", s, c);
+        }
+    }
+    assert_eq!(scanner.describe("op").unwrap().to_string(), "[*-\\-/=]");
+    assert_eq!(scanner.describe("string").unwrap().to_string(), "\"[^\\n\"]*\"");
+}
+
+// removed when the test ends, whether it passes or not
+struct TempDir(std::path::PathBuf);
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+// compiles the generated source with a `main` printing the tokens of each argument, and
+// checks it scans like the scanner it came from
+#[test]
+fn test_generated_scanners() {
+    use codegen::{generate, Style};
+    use std::process::Command;
+
+    let cases = vec![
+        (expr_scanner(), vec!["x1 + 42*(if_ - iff) // note\n/if", "1//2", "ab + ?c", "", "  \n ", "iffy if"]),
+        (class_scanner(), vec!["Straße = \"héllo, wörld\" 12", "λ_1 \"\"", "x \"open", "😀 ∑"]),
+        // every character leaves the start state, the direct style needs no fallback arm there
+        (line_scanner(), vec!["ab\ncd", "\n\n", "😀"]),
+    ];
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    if !Command::new(&rustc).arg("--version").output().is_ok_and(|o| o.status.success()) {
+        eprintln!("skipping test_generated_scanners: `{}` can't be run", rustc);
+        return;
+    }
+    let temp = TempDir(std::env::temp_dir().join(format!("eac2-codegen-{}", std::process::id())));
+    std::fs::create_dir_all(&temp.0).unwrap();
+    let dir = &temp.0;
+    for (case, (scanner, inputs)) in cases.into_iter().enumerate() {
+        let expected: Vec<_> = inputs
+            .iter()
+            .map(|input| {
+                let mut ret = String::new();
+                for t in scanner.scan(input) {
+                    match t {
+                        Ok(t) => ret += &format!("{} {:?}\n", t.kind, t.lexeme),
+                        Err(e) => ret += &format!("error at {}\n", e.offset),
+                    }
+                }
+                ret
+            })
+            .collect();
+
+        for &style in &[Style::Table, Style::Direct, Style::Hybrid] {
+            let source = generate(&scanner, style)
+                + "
+    fn main() {
+        for input in std::env::args().skip(1) {
+            match scan(&input) {
+                Ok(tokens) => {
+                    for (kind, text) in tokens {
+                        println!(\"{} {:?}\", KINDS[kind], text);
+                    }
+                }
+                Err(offset) => println!(\"error at {}\", offset),
+            }
+            println!(\"--\");
+        }
+    }
+    ";
+            let name = format!("{:?}{}", style, case).to_lowercase();
+            let path = dir.join(format!("{}.rs", name));
+            std::fs::write(&path, source).unwrap();
+            let compiled = Command::new(&rustc)
+                .args(["--edition", "2018", "-D", "warnings", "-o"])
+                .arg(dir.join(&name))
+                .arg(&path)
+                .output()
+                .unwrap();
+            let stderr = String::from_utf8_lossy(&compiled.stderr);
+            let clean = compiled.status.success() && stderr.is_empty();
+            assert!(clean, "{:?} scanner doesn't compile cleanly:\n{}", style, stderr);
+            let output = Command::new(dir.join(&name)).args(&inputs).output().unwrap();
+            let output = String::from_utf8(output.stdout).unwrap();
+            let runs: Vec<_> = output.split("--\n").collect();
+            // the scanner stops at an error, the generated one reports it instead of any tokens
+            for (i, run) in runs[..inputs.len()].iter().enumerate() {
+                if expected[i].contains("error") {
+                    assert_eq!(run.lines().last(), expected[i].lines().last(), "{:?} on {:?}", style, inputs[i]);
+                } else {
+                    assert_eq!(run, &expected[i], "{:?} on {:?}", style, inputs[i]);
+                }
+            }
+        }
+    }
+}